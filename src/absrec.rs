@@ -0,0 +1,152 @@
+use ::objrec::*;
+
+/* R-module header: a linked module with its record counts and sizes */
+pub struct Rheadr {
+    pub name: String,
+    pub attribute: u8,
+    pub segment_count: u16,
+    pub group_count: u16,
+    pub overlay_count: u16,
+    pub overlay_offset: u32,
+    pub static_size: u32,
+    pub max_static_size: u32,
+    pub dynamic_storage: u32,
+    pub max_dynamic_storage: u32
+}
+
+/* overlay definition */
+pub struct Ovldef {
+    pub name: String,
+    pub location: u32,
+    pub attribute: u8,
+    pub shared: Option<u8>,
+    pub adjacent: Option<u8>
+}
+
+/* data at an absolute address from PEDATA or PIDATA */
+pub struct PhysicalData {
+    pub frame: u16,
+    pub offset: u8,
+    pub data: Vec<u8>
+}
+
+/* library header: number of modules and where the names are */
+pub struct Libhed {
+    pub module_count: u16,
+    pub block: u16,
+    pub byte: u16
+}
+
+impl PhysicalData {
+    pub fn address(&self) -> u32 {
+        self.frame as u32 * 16 + self.offset as u32
+    }
+}
+
+fn read_u32(vec: &[u8]) -> u32 {
+    read_offset(vec, true)
+}
+
+pub fn read_rheadr(vec: &[u8]) -> Result<Rheadr, String> {
+    let (name, i) = read_name(vec)?;
+    if vec.len() < i + 27 {
+        return Err("record is truncated".to_string());
+    }
+    let p = &vec[i..];
+    Ok(Rheadr {
+        name,
+        attribute: p[0],
+        segment_count: read_u16(&p[1..]),
+        group_count: read_u16(&p[3..]),
+        overlay_count: read_u16(&p[5..]),
+        overlay_offset: read_u32(&p[7..]),
+        static_size: read_u32(&p[11..]),
+        max_static_size: read_u32(&p[15..]),
+        dynamic_storage: read_u32(&p[19..]),
+        max_dynamic_storage: read_u32(&p[23..])
+    })
+}
+
+/* LHEADR has the same layout as THEADR: the module name */
+pub fn read_lheadr(vec: &[u8]) -> Result<String, String> {
+    read_name(vec).map(|(name, _)| name)
+}
+
+pub fn read_ovldef(vec: &[u8]) -> Result<Ovldef, String> {
+    let (name, mut i) = read_name(vec)?;
+    if vec.len() < i + 5 {
+        return Err("record is truncated".to_string());
+    }
+    let location = read_u32(&vec[i..]);
+    let attribute = vec[i+4];
+    i += 5;
+    let mut next_index = |present: bool| -> Result<Option<u8>, String> {
+        if !present {
+            return Ok(None);
+        }
+        let index = *vec.get(i).ok_or("overlay index is missing")?;
+        i += 1;
+        Ok(Some(index))
+    };
+    let shared = next_index(attribute & 0x02 != 0)?;
+    let adjacent = next_index(attribute & 0x01 != 0)?;
+    Ok(Ovldef { name, location, attribute, shared, adjacent })
+}
+
+/* frame number, offset and the data; iterated data is expanded */
+pub fn read_physical_data(vec: &[u8], iterated: bool) -> Result<PhysicalData, String> {
+    if vec.len() < 3 {
+        return Err("record is truncated".to_string());
+    }
+    let data = if iterated {
+        expand_iterated(&vec[3..], false)?
+    } else {
+        vec[3..].to_vec()
+    };
+    Ok(PhysicalData { frame: read_u16(vec), offset: vec[2], data })
+}
+
+pub fn read_libhed(vec: &[u8]) -> Result<Libhed, String> {
+    if vec.len() < 6 {
+        return Err("record is truncated".to_string());
+    }
+    Ok(Libhed {
+        module_count: read_u16(vec),
+        block: read_u16(&vec[2..]),
+        byte: read_u16(&vec[4..])
+    })
+}
+
+#[test]
+fn rheadr() {
+    let rheadr = read_rheadr(&[0x02, 0x52, 0x4d, 0x01,
+            0x03, 0x00, 0x01, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00,
+            0x00, 0x10, 0x00, 0x00,
+            0x00, 0x20, 0x00, 0x00,
+            0x00, 0x01, 0x00, 0x00,
+            0x00, 0x02, 0x00, 0x00]).unwrap();
+    assert_eq!(rheadr.name, "RM");
+    assert_eq!((rheadr.segment_count, rheadr.group_count), (3, 1));
+    assert_eq!((rheadr.static_size, rheadr.max_static_size), (0x1000, 0x2000));
+    assert_eq!(rheadr.max_dynamic_storage, 0x200);
+    assert!(read_rheadr(&[0x02, 0x52, 0x4d, 0x01]).is_err());
+}
+
+#[test]
+fn ovldef() {
+    let ovldef = read_ovldef(&[0x02, 0x4f, 0x31, 0x00, 0x04, 0x00, 0x00,
+            0x03, 0x02, 0x01]).unwrap();
+    assert_eq!((ovldef.name.as_str(), ovldef.location), ("O1", 0x400));
+    assert_eq!((ovldef.shared, ovldef.adjacent), (Some(2), Some(1)));
+    assert!(read_ovldef(&[0x02, 0x4f, 0x31, 0x00, 0x04, 0x00, 0x00, 0x02]).is_err());
+}
+
+#[test]
+fn physical_data() {
+    let data = read_physical_data(&[0x00, 0x10, 0x08, 0x01, 0x02], false).unwrap();
+    assert_eq!((data.address(), data.data), (0x10008, vec![0x01, 0x02]));
+    let data = read_physical_data(&[0x00, 0x00, 0x00,
+            0x03, 0x00, 0x00, 0x00, 0x01, 0x90], true).unwrap();
+    assert_eq!(data.data, vec![0x90, 0x90, 0x90]);
+}
@@ -0,0 +1,123 @@
+use ::module::*;
+
+/* a source position found for an address */
+pub struct Location<'a> {
+    pub source: &'a str,
+    pub line: u16
+}
+
+/* the code generated for a line: offsets start..end in a segment */
+pub struct AddressRange<'a> {
+    pub source: &'a str,
+    pub segment: &'a str,
+    pub start: u16,
+    pub end: u32
+}
+
+/* line number entries of a segment sorted by offset */
+fn segment_lines(module: &Module, segment: usize) -> Vec<&LineNumber> {
+    let mut lines: Vec<&LineNumber> = module.lines.iter()
+        .filter(|l| l.segment == segment)
+        .collect();
+    lines.sort_by_key(|l| l.offset);
+    lines
+}
+
+/* the line whose code contains the offset in the named segment */
+pub fn addr2line<'a>(module: &'a Module, segment: &str, offset: u16)
+        -> Option<Location<'a>> {
+    let segment_index = module.find_segment(segment);
+    if segment_index == 0 {
+        return None;
+    }
+    let length = module.segments[segment_index - 1].length;
+    if offset as u32 >= length {
+        return None;
+    }
+    segment_lines(module, segment_index).iter()
+        .rev()
+        .find(|l| l.offset <= offset)
+        .map(|l| Location {
+            source: &module.sources[l.source],
+            line: l.line
+        })
+}
+
+/* the address ranges generated for a line, optionally only in one source */
+pub fn line2addr<'a>(module: &'a Module, source: Option<&str>, line: u16)
+        -> Vec<AddressRange<'a>> {
+    let mut ranges = Vec::new();
+    for segment_index in 1..=module.segments.len() {
+        let lines = segment_lines(module, segment_index);
+        for (i, l) in lines.iter().enumerate() {
+            if l.line != line {
+                continue;
+            }
+            let source_name = &module.sources[l.source];
+            if let Some(s) = source {
+                if !source_name.eq_ignore_ascii_case(s) {
+                    continue;
+                }
+            }
+            /* the range ends where the code of the next offset starts */
+            let end = match lines[i+1..].iter().find(|n| n.offset > l.offset) {
+                Some(n) => n.offset as u32,
+                None => module.segments[segment_index - 1].length
+            };
+            ranges.push(AddressRange {
+                source: source_name,
+                segment: module.segment_name(segment_index),
+                start: l.offset,
+                end
+            });
+        }
+    }
+    ranges
+}
+
+#[cfg(test)]
+use ::objrec::*;
+
+#[cfg(test)]
+fn test_module() -> Module {
+    let records = vec![
+        ObjectRecord { rtype: RecordType::THEADR,
+            data: vec![0x04, 0x4d, 0x41, 0x49, 0x4e] },
+        ObjectRecord { rtype: RecordType::LNAMES,
+            data: vec![0x00, 0x04, 0x43, 0x4f, 0x44, 0x45] },
+        ObjectRecord { rtype: RecordType::SEGDEF,
+            data: vec![0x28, 0x40, 0x00, 0x02, 0x01, 0x01] },
+        ObjectRecord { rtype: RecordType::LINNUM,
+            data: vec![0x00, 0x01, 0x0a, 0x00, 0x00, 0x00,
+                    0x0b, 0x00, 0x08, 0x00, 0x0d, 0x00, 0x20, 0x00] },
+        ObjectRecord { rtype: RecordType::COMENT,
+            data: vec![0x00, 0xe8, 0x00, 0x05, 0x49, 0x4f, 0x2e, 0x49,
+                    0x4e, 0x00, 0x00, 0x00, 0x00] },
+        ObjectRecord { rtype: RecordType::LINNUM,
+            data: vec![0x00, 0x01, 0x03, 0x00, 0x30, 0x00] }
+    ];
+    read_module(records.into_iter())
+}
+
+#[test]
+fn address_to_line() {
+    let module = test_module();
+    let location = addr2line(&module, "CODE", 0x0c).unwrap();
+    assert_eq!((location.source, location.line), ("MAIN", 11));
+    let location = addr2line(&module, "code", 0x3f).unwrap();
+    assert_eq!((location.source, location.line), ("IO.IN", 3));
+    assert!(addr2line(&module, "CODE", 0x40).is_none());
+    assert!(addr2line(&module, "DATA", 0x00).is_none());
+}
+
+#[test]
+fn line_to_address() {
+    let module = test_module();
+    let ranges = line2addr(&module, None, 11);
+    assert_eq!(ranges.len(), 1);
+    assert_eq!((ranges[0].segment, ranges[0].start, ranges[0].end),
+            ("CODE", 0x08, 0x20));
+    let ranges = line2addr(&module, Some("IO.IN"), 3);
+    assert_eq!((ranges[0].start, ranges[0].end), (0x30, 0x40));
+    assert!(line2addr(&module, Some("MAIN"), 3).is_empty());
+}
@@ -0,0 +1,141 @@
+use std::fmt;
+
+use ::objrec::*;
+
+/* the size of a patched location */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Location {
+    Byte,
+    Word,
+    DoubleWord
+}
+
+/* what a back-patch applies to */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Target {
+    /* BAKPAT: a segment index */
+    Segment(usize),
+    /* NBKPAT: the name index of a COMDAT */
+    Comdat(usize)
+}
+
+/* a BAKPAT or NBKPAT record: values to add at offsets */
+pub struct Bakpat {
+    pub target: Target,
+    pub location: Location,
+    pub patches: Vec<(u32, u32)>
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Location::Byte => write!(f, "byte"),
+            Location::Word => write!(f, "word"),
+            Location::DoubleWord => write!(f, "double word")
+        }
+    }
+}
+
+fn read_location(b: u8, is32: bool) -> Result<Location, String> {
+    match b {
+        0 => Ok(Location::Byte),
+        1 => Ok(Location::Word),
+        2 if is32 => Ok(Location::DoubleWord),
+        n => Err(format!("bad location type {}", n))
+    }
+}
+
+fn read_patches(vec: &[u8], is32: bool) -> Result<Vec<(u32, u32)>, String> {
+    let size = if is32 { 4 } else { 2 };
+    if !vec.len().is_multiple_of(2 * size) {
+        return Err("offset and value pair is truncated".to_string());
+    }
+    Ok(vec.chunks(2 * size)
+        .map(|p| (read_offset(p, is32), read_offset(&p[size..], is32)))
+        .collect())
+}
+
+/* segment index, location type, offset and value pairs */
+pub fn read_bakpat(vec: &[u8], is32: bool) -> Result<Bakpat, String> {
+    let (segment, n) = read_index(vec)?;
+    let location = match vec.get(n) {
+        Some(&b) => read_location(b, is32)?,
+        None => return Err("record is truncated".to_string())
+    };
+    Ok(Bakpat {
+        target: Target::Segment(segment),
+        location,
+        patches: read_patches(&vec[n+1..], is32)?
+    })
+}
+
+/* location type, COMDAT name index, offset and value pairs */
+pub fn read_nbkpat(vec: &[u8], is32: bool) -> Result<Bakpat, String> {
+    let location = match vec.first() {
+        Some(&b) => read_location(b, is32)?,
+        None => return Err("record is truncated".to_string())
+    };
+    let (name_index, n) = read_index(&vec[1..])?;
+    Ok(Bakpat {
+        target: Target::Comdat(name_index),
+        location,
+        patches: read_patches(&vec[1+n..], is32)?
+    })
+}
+
+impl Bakpat {
+    /* adds the values to the locations of an image */
+    pub fn apply(&self, image: &mut [u8]) -> Result<(), String> {
+        let size = match self.location {
+            Location::Byte => 1,
+            Location::Word => 2,
+            Location::DoubleWord => 4
+        };
+        for &(offset, value) in &self.patches {
+            let offset = offset as usize;
+            let bytes = match image.get_mut(offset..offset+size) {
+                Some(bytes) => bytes,
+                None => return Err(format!("offset {} is outside the data", offset))
+            };
+            let old = bytes.iter().rev().fold(0u32, |v, &b| (v << 8) | b as u32);
+            let new = old.wrapping_add(value);
+            for (i, b) in bytes.iter_mut().enumerate() {
+                *b = (new >> (8 * i)) as u8;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn bakpat() {
+    let bakpat = read_bakpat(&[0x02, 0x01, 0x04, 0x00, 0x10, 0x00,
+            0x00, 0x00, 0xff, 0x00], false).unwrap();
+    assert_eq!(bakpat.target, Target::Segment(2));
+    assert_eq!(bakpat.location, Location::Word);
+    assert_eq!(bakpat.patches, vec![(4, 0x10), (0, 0xff)]);
+    let mut image = vec![0x01, 0xff, 0x00, 0x00, 0xf8, 0xff];
+    bakpat.apply(&mut image).unwrap();
+    assert_eq!(image, vec![0x00, 0x00, 0x00, 0x00, 0x08, 0x00]);
+    assert!(read_bakpat(&[0x02, 0x02, 0x00, 0x00, 0x01, 0x00], false).is_err());
+    assert!(read_bakpat(&[0x02, 0x00, 0x00, 0x00, 0x01], false).is_err());
+    assert!(bakpat.apply(&mut [0x00; 5]).is_err());
+    let bakpat = read_bakpat(&[0x81, 0x02, 0x00], false).unwrap();
+    assert_eq!(bakpat.target, Target::Segment(0x102));
+    assert!(bakpat.patches.is_empty());
+    assert!(read_bakpat(&[0x81, 0x02], false).is_err());
+}
+
+#[test]
+fn nbkpat() {
+    let nbkpat = read_nbkpat(&[0x02, 0x03, 0x01, 0x00, 0x00, 0x00,
+            0x01, 0x00, 0x00, 0x00], true).unwrap();
+    assert_eq!(nbkpat.target, Target::Comdat(3));
+    assert_eq!(nbkpat.location, Location::DoubleWord);
+    let mut image = vec![0x00, 0xfe, 0xff, 0xff, 0xff];
+    nbkpat.apply(&mut image).unwrap();
+    assert_eq!(image, vec![0x00, 0xff, 0xff, 0xff, 0xff]);
+    let nbkpat = read_nbkpat(&[0x00, 0x80, 0x90], false).unwrap();
+    assert_eq!(nbkpat.target, Target::Comdat(0x90));
+    assert!(read_nbkpat(&[0x00, 0x80], false).is_err());
+}
@@ -0,0 +1,227 @@
+use ::objrec::*;
+use ::module::*;
+
+/* a BLKDEF record: a procedure or a block of code */
+#[derive(Clone, PartialEq, Debug)]
+pub struct Blkdef {
+    pub group: usize,
+    pub segment: usize,
+    pub frame: u16,
+    pub name: String,
+    pub offset: u16,
+    pub length: u16,
+    pub procedure: bool,
+    pub far: bool,
+    /* offset of the return address in the stack frame of a procedure */
+    pub return_offset: Option<u16>,
+    pub type_index: usize
+}
+
+/* a scope of the block tree: the BLKDEF, the enclosing block and the
+ * debug symbols, indices into the debug symbols of the module */
+pub struct Block {
+    pub def: Blkdef,
+    pub parent: Option<usize>,
+    pub symbols: Vec<usize>,
+    /* set when its BLKEND is seen */
+    pub closed: bool
+}
+
+fn byte(vec: &[u8], i: usize) -> Result<u8, String> {
+    vec.get(i).cloned().ok_or_else(|| "record is truncated".to_string())
+}
+
+fn word(vec: &[u8], i: usize) -> Result<u16, String> {
+    match vec.get(i..i+2) {
+        Some(bytes) => Ok(read_u16(bytes)),
+        None => Err("record is truncated".to_string())
+    }
+}
+
+/* base, name, offset, length, procedure information and the type index of
+ * named blocks */
+pub fn read_blkdef(vec: &[u8]) -> Result<Blkdef, String> {
+    let (group, segment, frame, mut i) = read_public_base(vec)?;
+    let frame = frame.unwrap_or(0);
+    let (name, n) = read_name(&vec[i..])?;
+    i += n;
+    let offset = word(vec, i)?;
+    let length = word(vec, i + 2)?;
+    let info = byte(vec, i + 4)?;
+    i += 5;
+    let procedure = info & 0x80 != 0;
+    let return_offset = if procedure {
+        i += 2;
+        Some(word(vec, i - 2)?)
+    } else {
+        None
+    };
+    let type_index = if name.is_empty() {
+        0
+    } else {
+        read_index(vec.get(i..).unwrap_or(&[]))?.0
+    };
+    Ok(Blkdef {
+        group,
+        segment,
+        frame,
+        name,
+        offset,
+        length,
+        procedure,
+        far: info & 0x40 != 0,
+        return_offset,
+        type_index
+    })
+}
+
+/* where the symbols of a DEBSYM record are: a segment base, an external
+ * or a block index */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DebsymBase {
+    Segment {
+        group: usize,
+        segment: usize,
+        frame: Option<u16>
+    },
+    External(usize),
+    Block(usize)
+}
+
+/* a DEBSYM record; the long bit makes the offsets 32 bits */
+#[derive(Clone, PartialEq, Debug)]
+pub struct Debsym {
+    pub based: bool,
+    pub long: bool,
+    pub base: DebsymBase,
+    /* name, offset and type index */
+    pub symbols: Vec<(String, u32, usize)>
+}
+
+pub fn read_debsym(vec: &[u8]) -> Result<Debsym, String> {
+    let info = byte(vec, 0)?;
+    let long = info & 0x40 != 0;
+    let (base, mut i) = match info & 7 {
+        0 => {
+            let (group, segment, frame, n) = read_public_base(&vec[1..])?;
+            (DebsymBase::Segment { group, segment, frame }, 1 + n)
+        },
+        1 => {
+            let (index, n) = read_index(&vec[1..])?;
+            (DebsymBase::External(index), 1 + n)
+        },
+        2 => {
+            let (index, n) = read_index(&vec[1..])?;
+            (DebsymBase::Block(index), 1 + n)
+        },
+        m => return Err(format!("bad frame method {}", m))
+    };
+    let mut symbols = Vec::new();
+    while i < vec.len() {
+        let (name, n) = read_name(&vec[i..])?;
+        i += n;
+        let offset = if long {
+            match vec.get(i..i+4) {
+                Some(o) => read_u16(o) as u32 | (read_u16(&o[2..]) as u32) << 16,
+                None => return Err("record is truncated".to_string())
+            }
+        } else {
+            word(vec, i)? as u32
+        };
+        i += if long { 4 } else { 2 };
+        let (type_index, n) = read_index(vec.get(i..).unwrap_or(&[]))?;
+        i += n;
+        symbols.push((name, offset, type_index));
+    }
+    Ok(Debsym { based: info & 0x80 != 0, long, base, symbols })
+}
+
+impl Block {
+    pub fn contains(&self, segment: usize, offset: u16) -> bool {
+        let start = self.def.offset as u32;
+        self.def.segment == segment && offset as u32 >= start &&
+                (offset as u32) < start + self.def.length as u32
+    }
+}
+
+/* the debug symbols of the blocks around a segment offset, outermost first */
+pub fn visible(module: &Module, segment: usize, offset: u16) -> Vec<(&Block, &Symbol)> {
+    let mut chain = Vec::new();
+    let mut current = module.blocks.iter().rposition(|b| b.contains(segment, offset));
+    while let Some(i) = current {
+        chain.push(&module.blocks[i]);
+        current = module.blocks[i].parent;
+    }
+    chain.reverse();
+    let mut result = Vec::new();
+    for block in chain {
+        for &i in &block.symbols {
+            result.push((block, &module.debug_symbols[i]));
+        }
+    }
+    result
+}
+
+#[test]
+fn blkdef() {
+    let blkdef = read_blkdef(&[0x00, 0x01, 0x04, 0x4d, 0x41, 0x49, 0x4e,
+            0x10, 0x00, 0x20, 0x00, 0xc0, 0x02, 0x00, 0x03]).unwrap();
+    assert_eq!((blkdef.name.as_str(), blkdef.segment), ("MAIN", 1));
+    assert_eq!((blkdef.offset, blkdef.length), (0x10, 0x20));
+    assert!(blkdef.procedure && blkdef.far);
+    assert_eq!((blkdef.return_offset, blkdef.type_index), (Some(2), 3));
+    let blkdef = read_blkdef(&[0x00, 0x01, 0x00, 0x14, 0x00, 0x04, 0x00, 0x00])
+            .unwrap();
+    assert!(blkdef.name.is_empty() && !blkdef.procedure);
+    assert!(read_blkdef(&[0x00, 0x01, 0x00, 0x14, 0x00, 0x04, 0x00, 0x80]).is_err());
+    let blkdef = read_blkdef(&[0x00, 0x81, 0x02, 0x01, 0x46,
+            0x10, 0x00, 0x20, 0x00, 0x00, 0x81, 0x03]).unwrap();
+    assert_eq!((blkdef.segment, blkdef.type_index), (0x102, 0x103));
+}
+
+#[test]
+fn debsym() {
+    let debsym = read_debsym(&[0x40, 0x00, 0x81, 0x02, 0x01, 0x41,
+            0x78, 0x56, 0x34, 0x12, 0x81, 0x03]).unwrap();
+    assert!(debsym.long);
+    assert_eq!(debsym.base, DebsymBase::Segment { group: 0, segment: 0x102, frame: None });
+    assert_eq!(debsym.symbols, vec![("A".to_string(), 0x12345678, 0x103)]);
+    assert!(read_debsym(&[0x40, 0x00, 0x01, 0x01, 0x41, 0x78, 0x56, 0x00]).is_err());
+    assert!(read_debsym(&[0x02]).is_err());
+    let records = vec![
+        ObjectRecord { rtype: RecordType::DEBSYM, data: vec![0x02, 0x01, 0x01, 0x41, 0xfc] }
+    ];
+    let module = read_module(records.into_iter());
+    assert!(module.debug_symbols.is_empty());
+    assert_eq!(module.errors.len(), 1);
+}
+
+#[test]
+fn block_tree() {
+    let records = vec![
+        ObjectRecord { rtype: RecordType::BLKDEF, data: vec![0x00, 0x01, 0x01, 0x46,
+                0x00, 0x00, 0x40, 0x00, 0x80, 0x02, 0x00, 0x00] },
+        ObjectRecord { rtype: RecordType::DEBSYM,
+            data: vec![0x02, 0x01, 0x01, 0x41, 0xfc, 0xff, 0x00] },
+        ObjectRecord { rtype: RecordType::BLKDEF,
+            data: vec![0x00, 0x01, 0x00, 0x10, 0x00, 0x08, 0x00, 0x00] },
+        ObjectRecord { rtype: RecordType::DEBSYM,
+            data: vec![0x02, 0x02, 0x01, 0x42, 0xfa, 0xff, 0x00] },
+        ObjectRecord { rtype: RecordType::BLKEND, data: vec![] },
+        ObjectRecord { rtype: RecordType::BLKEND, data: vec![] },
+        ObjectRecord { rtype: RecordType::BLKDEF, data: vec![0x00, 0x01, 0x01, 0x47,
+                0x40, 0x00, 0x10, 0x00, 0x00, 0x00] },
+        ObjectRecord { rtype: RecordType::BLKEND, data: vec![] }
+    ];
+    let module = read_module(records.into_iter());
+    assert_eq!(module.blocks.len(), 3);
+    assert_eq!(module.blocks[1].parent, Some(0));
+    assert_eq!(module.blocks[2].parent, None);
+    assert!(module.blocks.iter().all(|b| b.closed));
+    let names = |offset| -> Vec<&str> {
+        visible(&module, 1, offset).iter().map(|&(_, s)| s.name.as_str()).collect()
+    };
+    assert_eq!(names(0x12), vec!["A", "B"]);
+    assert_eq!(names(0x20), vec!["A"]);
+    assert!(names(0x44).is_empty());
+}
@@ -0,0 +1,433 @@
+use std::fmt;
+
+use ::objrec::*;
+
+/* a member of a structure list (E2H) or an enumeration list (E4H) */
+#[derive(Clone, PartialEq, Debug)]
+pub struct Member {
+    pub name: String,
+    /* type index for structures, value for enumerations */
+    pub value: u16
+}
+
+/* a type of a typedef comment (E3H): the type identifier and what it refers
+ * to, a type index or the index of a member list */
+#[derive(Clone, PartialEq, Debug)]
+pub struct Typedef {
+    pub index: u16,
+    pub size: u16,
+    pub tid: u8,
+    pub reference: Option<u16>,
+    pub extra: Vec<u8>
+}
+
+/* a local symbol (E6H) */
+#[derive(Clone, PartialEq, Debug)]
+pub struct Local {
+    pub name: String,
+    pub type_index: u16,
+    pub group: u8,
+    pub segment: u8,
+    pub offset: u16
+}
+
+/* the Turbo Debugger comment classes E0H-EFH */
+#[derive(Clone, PartialEq, Debug)]
+pub enum BorlandComment {
+    /* external or public index and its type */
+    ExternTypes(Vec<(usize, u16)>),
+    PublicTypes(Vec<(usize, u16)>),
+    StructMembers(Vec<Member>),
+    Typedef(Typedef),
+    EnumMembers(Vec<Member>),
+    /* scope offsets, 32 bits in the large forms ECH and EDH */
+    BeginScope(u32),
+    Locals(Vec<Local>),
+    EndScope(u32),
+    Source {
+        index: u8,
+        name: String,
+        time: u32
+    },
+    /* line numbers and their offsets in a source file */
+    SourceLines {
+        index: u8,
+        lines: Vec<(u16, u16)>
+    },
+    /* an empty name ends the list */
+    Dependency {
+        time: u32,
+        name: String
+    },
+    Compiler {
+        language: u8,
+        model: u8
+    },
+    /* EEH and EFH are kept as bytes */
+    Other(u8, Vec<u8>)
+}
+
+/* a lexical scope with its locals and nested scopes */
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Scope {
+    pub start: u32,
+    pub end: Option<u32>,
+    pub locals: Vec<Local>,
+    pub scopes: Vec<Scope>
+}
+
+/* the Borland debug information of a module, linked together */
+#[derive(Default)]
+pub struct BorlandDebug {
+    pub sources: Vec<(u8, String, u32)>,
+    /* source index, line and offset */
+    pub lines: Vec<(u8, u16, u16)>,
+    pub dependencies: Vec<(String, u32)>,
+    pub compiler: Option<(u8, u8)>,
+    pub types: Vec<Typedef>,
+    /* member lists are numbered from 1 in the order they appear */
+    pub struct_members: Vec<Vec<Member>>,
+    pub enum_members: Vec<Vec<Member>>,
+    pub extern_types: Vec<(usize, u16)>,
+    pub public_types: Vec<(usize, u16)>,
+    /* the module level scope, holding the locals outside of any scope */
+    pub root: Scope,
+    /* the path of child positions to the innermost open scope */
+    open: Vec<usize>
+}
+
+pub fn tid_to_string(tid: u8) -> &'static str {
+    match tid {
+        0x00 => "void",
+        0x01 => "long string",
+        0x02 => "dynamic string",
+        0x03 => "Pascal string",
+        0x04 => "signed char",
+        0x05 => "int",
+        0x06 => "long",
+        0x08 => "unsigned char",
+        0x09 => "unsigned int",
+        0x0a => "unsigned long",
+        0x0c => "Pascal char",
+        0x0d => "float",
+        0x0e => "Pascal real",
+        0x0f => "double",
+        0x10 => "long double",
+        0x15 => "near pointer",
+        0x16 => "far pointer",
+        0x17 => "segment pointer",
+        0x18 => "near 386 pointer",
+        0x19 => "far 386 pointer",
+        0x1a => "C array",
+        0x1c => "Pascal array",
+        0x1e => "struct",
+        0x1f => "union",
+        0x22 => "enum",
+        0x23 => "function",
+        0x24 => "label",
+        0x25 => "set",
+        0x28 => "boolean",
+        _ => "<unknown type identifier>"
+    }
+}
+
+pub fn language_to_string(language: u8) -> &'static str {
+    match language {
+        0 => "unspecified",
+        1 => "C",
+        2 => "Pascal",
+        3 => "Basic",
+        4 => "assembler",
+        5 => "C++",
+        _ => "<bad value>"
+    }
+}
+
+pub fn model_to_string(model: u8) -> &'static str {
+    match model {
+        0 => "tiny",
+        1 => "small",
+        2 => "medium",
+        3 => "compact",
+        4 => "large",
+        5 => "huge",
+        _ => "<bad value>"
+    }
+}
+
+impl fmt::Display for Typedef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}, size {}", tid_to_string(self.tid), self.size)?;
+        match self.reference {
+            Some(r) if self.refers_to_members() => write!(f, ", members {}", r),
+            Some(r) => write!(f, ", of type {}", r),
+            None => Ok(())
+        }
+    }
+}
+
+impl Typedef {
+    /* structures, unions and enumerations refer to a member list */
+    pub fn refers_to_members(&self) -> bool {
+        self.tid == 0x1e || self.tid == 0x1f || self.tid == 0x22
+    }
+}
+
+/* a reader of the fields of one comment */
+struct Fields<'a> {
+    vec: &'a [u8],
+    i: usize
+}
+
+impl <'a> Fields<'a> {
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8], String> {
+        let bytes = self.vec.get(self.i..self.i+n)
+                .ok_or_else(|| "comment is truncated".to_string())?;
+        self.i += n;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        Ok(read_u16(self.bytes(2)?))
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(read_offset(self.bytes(4)?, true))
+    }
+
+    fn index(&mut self) -> Result<usize, String> {
+        let (index, n) = read_index(&self.vec[self.i.min(self.vec.len())..])
+                .map_err(|_| "comment is truncated".to_string())?;
+        self.i += n;
+        Ok(index)
+    }
+
+    fn name(&mut self) -> Result<String, String> {
+        let (name, n) = read_name(&self.vec[self.i.min(self.vec.len())..])?;
+        self.i += n;
+        Ok(name)
+    }
+
+    fn done(&self) -> bool {
+        self.i >= self.vec.len()
+    }
+}
+
+/* index and type pairs */
+fn read_types(fields: &mut Fields) -> Result<Vec<(usize, u16)>, String> {
+    let mut result = Vec::new();
+    while !fields.done() {
+        let index = fields.index()?;
+        result.push((index, fields.u16()?));
+    }
+    Ok(result)
+}
+
+/* flags, name and a type index or value; flag 40H marks the last member */
+fn read_members(fields: &mut Fields) -> Result<Vec<Member>, String> {
+    let mut result = Vec::new();
+    while !fields.done() {
+        let flags = fields.u8()?;
+        let name = fields.name()?;
+        result.push(Member { name, value: fields.u16()? });
+        if flags & 0x40 != 0 {
+            break;
+        }
+    }
+    Ok(result)
+}
+
+fn read_typedef(fields: &mut Fields) -> Result<Typedef, String> {
+    let index = fields.u16()?;
+    let size = fields.u16()?;
+    let tid = fields.u8()?;
+    let refers = matches!(tid, 0x15 ..= 0x1a | 0x1c | 0x1e | 0x1f | 0x22 | 0x23);
+    let reference = if refers && fields.vec.len() >= fields.i + 2 {
+        Some(fields.u16()?)
+    } else {
+        None
+    };
+    let extra = fields.vec[fields.i.min(fields.vec.len())..].to_vec();
+    Ok(Typedef { index, size, tid, reference, extra })
+}
+
+fn read_lines(fields: &mut Fields) -> Result<Vec<(u16, u16)>, String> {
+    let mut result = Vec::new();
+    while !fields.done() {
+        let line = fields.u16()?;
+        result.push((line, fields.u16()?));
+    }
+    Ok(result)
+}
+
+fn read_locals(fields: &mut Fields) -> Result<Vec<Local>, String> {
+    let mut result = Vec::new();
+    while !fields.done() {
+        result.push(Local {
+            name: fields.name()?,
+            type_index: fields.u16()?,
+            group: fields.u8()?,
+            segment: fields.u8()?,
+            offset: fields.u16()?
+        });
+    }
+    Ok(result)
+}
+
+/* the text of a Borland comment, after the class byte */
+pub fn read_borland(class: u8, vec: &[u8]) -> Result<BorlandComment, String> {
+    let mut fields = Fields { vec, i: 0 };
+    let f = &mut fields;
+    Ok(match class {
+        0xe0 => BorlandComment::ExternTypes(read_types(f)?),
+        0xe1 => BorlandComment::PublicTypes(read_types(f)?),
+        0xe2 => BorlandComment::StructMembers(read_members(f)?),
+        0xe3 => BorlandComment::Typedef(read_typedef(f)?),
+        0xe4 => BorlandComment::EnumMembers(read_members(f)?),
+        0xe5 => BorlandComment::BeginScope(f.u16()? as u32),
+        0xe6 => BorlandComment::Locals(read_locals(f)?),
+        0xe7 => BorlandComment::EndScope(f.u16()? as u32),
+        0xe8 => BorlandComment::Source {
+            index: f.u8()?,
+            name: f.name()?,
+            time: if f.vec.len() >= f.i + 4 { f.u32()? } else { 0 }
+        },
+        0xe9 => {
+            let time = f.u32()?;
+            let name = if f.done() { String::new() } else { f.name()? };
+            BorlandComment::Dependency { time, name }
+        },
+        0xea => BorlandComment::Compiler { language: f.u8()?, model: f.u8()? },
+        0xeb => BorlandComment::SourceLines { index: f.u8()?, lines: read_lines(f)? },
+        0xec => BorlandComment::BeginScope(f.u32()?),
+        0xed => BorlandComment::EndScope(f.u32()?),
+        _ => BorlandComment::Other(class, vec.to_vec())
+    })
+}
+
+impl BorlandDebug {
+
+    fn innermost(&mut self) -> &mut Scope {
+        let mut scope = &mut self.root;
+        for &i in &self.open {
+            scope = &mut scope.scopes[i];
+        }
+        scope
+    }
+
+    pub fn add(&mut self, comment: BorlandComment) {
+        match comment {
+            BorlandComment::ExternTypes(types) => self.extern_types.extend(types),
+            BorlandComment::PublicTypes(types) => self.public_types.extend(types),
+            BorlandComment::StructMembers(members) => self.struct_members.push(members),
+            BorlandComment::EnumMembers(members) => self.enum_members.push(members),
+            BorlandComment::Typedef(typedef) => self.types.push(typedef),
+            BorlandComment::BeginScope(start) => {
+                let scope = self.innermost();
+                scope.scopes.push(Scope { start, ..Scope::default() });
+                let i = scope.scopes.len() - 1;
+                self.open.push(i);
+            },
+            BorlandComment::EndScope(end) => {
+                if !self.open.is_empty() {
+                    self.innermost().end = Some(end);
+                    self.open.pop();
+                }
+            },
+            BorlandComment::Locals(locals) => self.innermost().locals.extend(locals),
+            BorlandComment::Source { index, name, time } =>
+                self.sources.push((index, name, time)),
+            BorlandComment::SourceLines { index, lines } =>
+                self.lines.extend(lines.into_iter().map(|(line, offset)| (index, line, offset))),
+            BorlandComment::Dependency { time, name } => {
+                if !name.is_empty() {
+                    self.dependencies.push((name, time));
+                }
+            },
+            BorlandComment::Compiler { language, model } =>
+                self.compiler = Some((language, model)),
+            BorlandComment::Other(_, _) => {}
+        }
+    }
+
+    pub fn typedef(&self, index: u16) -> Option<&Typedef> {
+        self.types.iter().find(|t| t.index == index)
+    }
+
+    /* the members a structure, union or enumeration type refers to */
+    pub fn members(&self, typedef: &Typedef) -> Option<&[Member]> {
+        let list = typedef.reference? as usize;
+        let lists = if typedef.tid == 0x22 { &self.enum_members } else { &self.struct_members };
+        if !typedef.refers_to_members() || list == 0 {
+            return None;
+        }
+        lists.get(list - 1).map(|m| m.as_slice())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.types.is_empty() && self.sources.is_empty() && self.lines.is_empty() &&
+                self.compiler.is_none() &&
+                self.root == Scope::default()
+    }
+}
+
+#[test]
+fn borland_comments() {
+    assert_eq!(read_borland(0xe6, &[0x01, 0x49, 0x05, 0x00, 0x00, 0x01, 0xfe, 0xff])
+            .unwrap(), BorlandComment::Locals(vec![Local {
+                name: "I".to_string(),
+                type_index: 5,
+                group: 0,
+                segment: 1,
+                offset: 0xfffe
+            }]));
+    let typedef = match read_borland(0xe3, &[0x40, 0x00, 0x04, 0x00, 0x1e, 0x01, 0x00])
+            .unwrap() {
+        BorlandComment::Typedef(typedef) => typedef,
+        _ => panic!("not a typedef")
+    };
+    assert_eq!((typedef.index, typedef.size, typedef.reference), (0x40, 4, Some(1)));
+    assert_eq!(typedef.to_string(), "struct, size 4, members 1");
+    assert_eq!(read_borland(0xea, &[0x01, 0x04]).unwrap(),
+            BorlandComment::Compiler { language: 1, model: 4 });
+    assert!(read_borland(0xe5, &[0x01]).is_err());
+    assert_eq!(read_borland(0xe0, &[0x81, 0x02, 0x40, 0x00, 0x01, 0x41, 0x00]).unwrap(),
+            BorlandComment::ExternTypes(vec![(0x102, 0x40), (1, 0x41)]));
+    assert!(read_borland(0xe1, &[0x81]).is_err());
+    assert_eq!(read_borland(0xeb, &[0x01, 0x0a, 0x00, 0x10, 0x00]).unwrap(),
+            BorlandComment::SourceLines { index: 1, lines: vec![(10, 0x10)] });
+    assert!(read_borland(0xeb, &[0x01, 0x0a, 0x00]).is_err());
+    assert_eq!(read_borland(0xec, &[0x00, 0x00, 0x01, 0x00]).unwrap(),
+            BorlandComment::BeginScope(0x10000));
+    assert_eq!(read_borland(0xef, &[0x01]).unwrap(), BorlandComment::Other(0xef, vec![0x01]));
+}
+
+#[test]
+fn scopes() {
+    let mut debug = BorlandDebug::default();
+    let local = |name: &str| Local {
+        name: name.to_string(), type_index: 5, group: 0, segment: 1, offset: 0
+    };
+    debug.add(BorlandComment::Locals(vec![local("G")]));
+    debug.add(BorlandComment::BeginScope(0x10));
+    debug.add(BorlandComment::Locals(vec![local("A")]));
+    debug.add(BorlandComment::BeginScope(0x14));
+    debug.add(BorlandComment::Locals(vec![local("B")]));
+    debug.add(BorlandComment::EndScope(0x18));
+    debug.add(BorlandComment::EndScope(0x20));
+    debug.add(BorlandComment::StructMembers(vec![
+            Member { name: "X".to_string(), value: 5 }]));
+    debug.add(BorlandComment::Typedef(Typedef {
+        index: 0x40, size: 2, tid: 0x1e, reference: Some(1), extra: Vec::new()
+    }));
+    assert_eq!(debug.root.locals[0].name, "G");
+    let scope = &debug.root.scopes[0];
+    assert_eq!((scope.start, scope.end), (0x10, Some(0x20)));
+    assert_eq!(scope.scopes[0].locals[0].name, "B");
+    let typedef = debug.typedef(0x40).unwrap();
+    assert_eq!(debug.members(typedef).unwrap()[0].name, "X");
+}
@@ -0,0 +1,580 @@
+use std::fmt;
+
+use ::objrec::*;
+use ::module::*;
+use ::fixup::*;
+
+/* a segment and offset as stored in a symbol, at position `at` of $$SYMBOLS */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Address {
+    pub offset: u32,
+    pub segment: u16,
+    pub at: usize
+}
+
+/* a CodeView 4 symbol record */
+#[derive(Clone, PartialEq, Debug)]
+pub enum CvSymbol {
+    Compile {
+        machine: u8,
+        version: String
+    },
+    Register {
+        type_index: u16,
+        register: u16,
+        name: String
+    },
+    Constant {
+        type_index: u16,
+        value: u32,
+        name: String
+    },
+    Udt {
+        type_index: u16,
+        name: String
+    },
+    End,
+    ObjName {
+        signature: u32,
+        name: String
+    },
+    BpRelative {
+        offset: i32,
+        type_index: u16,
+        name: String
+    },
+    Data {
+        global: bool,
+        address: Address,
+        type_index: u16,
+        name: String
+    },
+    Public {
+        address: Address,
+        type_index: u16,
+        name: String
+    },
+    Procedure {
+        global: bool,
+        address: Address,
+        length: u32,
+        debug_start: u32,
+        debug_end: u32,
+        type_index: u16,
+        name: String
+    },
+    Block {
+        address: Address,
+        length: u32,
+        name: String
+    },
+    Label {
+        address: Address,
+        name: String
+    },
+    Other(u16, Vec<u8>)
+}
+
+/* a member of a field list */
+#[derive(Clone, PartialEq, Debug)]
+pub enum Field {
+    Member {
+        type_index: u16,
+        offset: u32,
+        name: String
+    },
+    Enumerate {
+        value: u32,
+        name: String
+    },
+    Other(u16)
+}
+
+/* a CodeView 4 type record */
+#[derive(Clone, PartialEq, Debug)]
+pub enum CvType {
+    Modifier {
+        attribute: u16,
+        type_index: u16
+    },
+    Pointer {
+        attribute: u16,
+        type_index: u16
+    },
+    Array {
+        element: u16,
+        index: u16,
+        size: u32,
+        name: String
+    },
+    /* LF_CLASS, LF_STRUCTURE and LF_UNION */
+    Structure {
+        kind: &'static str,
+        count: u16,
+        fields: u16,
+        size: u32,
+        name: String
+    },
+    Enum {
+        count: u16,
+        underlying: u16,
+        fields: u16,
+        name: String
+    },
+    Procedure {
+        ret: u16,
+        convention: u8,
+        params: u16,
+        list: u16
+    },
+    ArgList(Vec<u16>),
+    FieldList(Vec<Field>),
+    Other(u16, Vec<u8>)
+}
+
+/* the name of a type index: primitive types are below 1000H */
+pub fn type_name(index: u16) -> String {
+    if index >= 0x1000 {
+        return format!("T{:04x}", index);
+    }
+    let base = match index & 0xff {
+        0x00 => "notype",
+        0x03 => "void",
+        0x10 => "char",
+        0x11 => "short",
+        0x12 => "long",
+        0x20 => "uchar",
+        0x21 => "ushort",
+        0x22 => "ulong",
+        0x30 => "bool",
+        0x40 => "float",
+        0x41 => "double",
+        0x42 => "long double",
+        0x70 => "rchar",
+        0x72 => "int",
+        0x73 => "uint",
+        0x74 => "int32",
+        0x75 => "uint32",
+        _ => return format!("T{:04x}", index)
+    };
+    match (index >> 8) & 7 {
+        0 => base.to_string(),
+        1 => format!("{} near*", base),
+        2 => format!("{} far*", base),
+        3 => format!("{} huge*", base),
+        4 => format!("{} near32*", base),
+        5 => format!("{} far32*", base),
+        _ => format!("T{:04x}", index)
+    }
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Field::Member { type_index, offset, ref name } =>
+                write!(f, "{}: {} at {}", name, type_name(type_index), offset),
+            Field::Enumerate { value, ref name } => write!(f, "{} = {}", name, value),
+            Field::Other(leaf) => write!(f, "<leaf {:04x}>", leaf)
+        }
+    }
+}
+
+impl fmt::Display for CvType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CvType::Modifier { attribute, type_index } => {
+                let modifiers = [(1, "const "), (2, "volatile "), (4, "unaligned ")];
+                for &(bit, modifier) in &modifiers {
+                    if attribute & bit != 0 {
+                        write!(f, "{}", modifier)?;
+                    }
+                }
+                write!(f, "{}", type_name(type_index))
+            },
+            CvType::Pointer { attribute, type_index } => {
+                let mode = match attribute & 0x1f {
+                    0 => "near",
+                    1 => "far",
+                    2 => "huge",
+                    10 => "near32",
+                    11 => "far32",
+                    _ => "based"
+                };
+                write!(f, "{} {}*", type_name(type_index), mode)
+            },
+            CvType::Array { element, index, size, ref name } =>
+                write!(f, "{}[{} bytes] {} (index {})", type_name(element), size,
+                        name, type_name(index)),
+            CvType::Structure { kind, count, fields, size, ref name } =>
+                write!(f, "{} {} ({} bytes, {} members in {})", kind, name, size,
+                        count, type_name(fields)),
+            CvType::Enum { count, underlying, fields, ref name } =>
+                write!(f, "enum {}: {} ({} values in {})", name,
+                        type_name(underlying), count, type_name(fields)),
+            CvType::Procedure { ret, convention, params, list } =>
+                write!(f, "procedure({} in {}): {}, convention {}", params,
+                        type_name(list), type_name(ret), convention),
+            CvType::ArgList(ref args) => {
+                let names: Vec<String> = args.iter().map(|&a| type_name(a)).collect();
+                write!(f, "({})", names.join(", "))
+            },
+            CvType::FieldList(ref fields) => {
+                let fields: Vec<String> = fields.iter().map(|m| m.to_string()).collect();
+                write!(f, "{{ {} }}", fields.join("; "))
+            },
+            CvType::Other(leaf, _) => write!(f, "<leaf {:04x}>", leaf)
+        }
+    }
+}
+
+/* a bounds checked reader of the fields of one record */
+struct Fields<'a> {
+    vec: &'a [u8],
+    i: usize
+}
+
+impl <'a> Fields<'a> {
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8], String> {
+        let bytes = self.vec.get(self.i..self.i+n)
+                .ok_or_else(|| "record is truncated".to_string())?;
+        self.i += n;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        Ok(read_u16(self.bytes(2)?))
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(read_offset(self.bytes(4)?, true))
+    }
+
+    /* 16 or 32 bit offset */
+    fn offset(&mut self, is32: bool) -> Result<u32, String> {
+        if is32 { self.u32() } else { self.u16().map(|n| n as u32) }
+    }
+
+    /* numeric leaf: a value below 8000H or a leaf and its value */
+    fn numeric(&mut self) -> Result<u32, String> {
+        let leaf = self.u16()?;
+        match leaf {
+            0 ..= 0x7fff => Ok(leaf as u32),
+            0x8000 => Ok(self.u8()? as i8 as u32),
+            0x8001 | 0x8002 => Ok(self.u16()? as u32),
+            0x8003 | 0x8004 => self.u32(),
+            n => Err(format!("unknown numeric leaf {:04x}", n))
+        }
+    }
+
+    fn name(&mut self) -> Result<String, String> {
+        let (name, n) = read_name(&self.vec[self.i.min(self.vec.len())..])?;
+        self.i += n;
+        Ok(name)
+    }
+
+    fn address(&mut self, is32: bool, base: usize) -> Result<Address, String> {
+        let at = base + self.i;
+        let offset = self.offset(is32)?;
+        Ok(Address { offset, segment: self.u16()?, at })
+    }
+}
+
+fn read_symbol(kind: u16, fields: &mut Fields, base: usize) -> Result<CvSymbol, String> {
+    let is32 = kind & 0x0f00 == 0x0200;
+    Ok(match kind {
+        0x0001 => {
+            let machine = fields.u8()?;
+            fields.bytes(3)?;
+            CvSymbol::Compile { machine, version: fields.name()? }
+        },
+        0x0002 => CvSymbol::Register {
+            type_index: fields.u16()?,
+            register: fields.u16()?,
+            name: fields.name()?
+        },
+        0x0003 => CvSymbol::Constant {
+            type_index: fields.u16()?,
+            value: fields.numeric()?,
+            name: fields.name()?
+        },
+        0x0004 => CvSymbol::Udt { type_index: fields.u16()?, name: fields.name()? },
+        0x0006 => CvSymbol::End,
+        0x0009 => CvSymbol::ObjName { signature: fields.u32()?, name: fields.name()? },
+        0x0100 | 0x0200 => CvSymbol::BpRelative {
+            offset: if is32 { fields.u32()? as i32 } else { fields.u16()? as i16 as i32 },
+            type_index: fields.u16()?,
+            name: fields.name()?
+        },
+        0x0101 | 0x0102 | 0x0201 | 0x0202 => CvSymbol::Data {
+            global: kind & 0xff == 0x02,
+            address: fields.address(is32, base)?,
+            type_index: fields.u16()?,
+            name: fields.name()?
+        },
+        0x0103 | 0x0203 => CvSymbol::Public {
+            address: fields.address(is32, base)?,
+            type_index: fields.u16()?,
+            name: fields.name()?
+        },
+        0x0104 | 0x0105 | 0x0204 | 0x0205 => {
+            /* parent, end and next pointers */
+            fields.bytes(12)?;
+            let length = fields.offset(is32)?;
+            let debug_start = fields.offset(is32)?;
+            let debug_end = fields.offset(is32)?;
+            let address = fields.address(is32, base)?;
+            let type_index = fields.u16()?;
+            fields.u8()?;
+            CvSymbol::Procedure {
+                global: kind & 0xff == 0x05,
+                address,
+                length,
+                debug_start,
+                debug_end,
+                type_index,
+                name: fields.name()?
+            }
+        },
+        0x0107 | 0x0207 => {
+            fields.bytes(8)?;
+            let length = fields.offset(is32)?;
+            CvSymbol::Block {
+                length,
+                address: fields.address(is32, base)?,
+                name: fields.name()?
+            }
+        },
+        0x0109 | 0x0209 => {
+            let address = fields.address(is32, base)?;
+            fields.u8()?;
+            CvSymbol::Label { address, name: fields.name()? }
+        },
+        _ => CvSymbol::Other(kind, fields.vec.to_vec())
+    })
+}
+
+fn check_signature(vec: &[u8]) -> Result<(), String> {
+    match vec.get(0..4).map(|s| read_offset(s, true)) {
+        Some(1) => Ok(()),
+        Some(n) => Err(format!("unsupported CodeView signature {}", n)),
+        None => Err("CodeView signature is missing".to_string())
+    }
+}
+
+/* the records of a CodeView 4 $$SYMBOLS segment with their offsets */
+pub fn read_symbols(vec: &[u8]) -> Result<Vec<(usize, CvSymbol)>, String> {
+    check_signature(vec)?;
+    let mut result = Vec::new();
+    let mut i = 4;
+    while i + 2 <= vec.len() {
+        let length = read_u16(&vec[i..]) as usize;
+        if length == 0 {
+            break;
+        }
+        let record = vec.get(i+2..i+2+length)
+                .ok_or_else(|| format!("symbol at offset {} is truncated", i))?;
+        if length < 2 {
+            return Err(format!("symbol at offset {} is truncated", i));
+        }
+        let mut fields = Fields { vec: &record[2..], i: 0 };
+        let symbol = read_symbol(read_u16(record), &mut fields, i + 4)
+                .map_err(|reason| format!("symbol at offset {}: {}", i, reason))?;
+        result.push((i, symbol));
+        i += 2 + length;
+    }
+    Ok(result)
+}
+
+fn read_field_list(fields: &mut Fields) -> Result<Vec<Field>, String> {
+    let mut result = Vec::new();
+    while fields.i < fields.vec.len() {
+        /* pad bytes F0H-FFH align the next member */
+        if fields.vec[fields.i] >= 0xf0 {
+            fields.i += (fields.vec[fields.i] & 0x0f).max(1) as usize;
+            continue;
+        }
+        let leaf = fields.u16()?;
+        match leaf {
+            0x0406 => {
+                let type_index = fields.u16()?;
+                fields.u16()?;
+                let offset = fields.numeric()?;
+                result.push(Field::Member { type_index, offset, name: fields.name()? });
+            },
+            0x0403 => {
+                fields.u16()?;
+                let value = fields.numeric()?;
+                result.push(Field::Enumerate { value, name: fields.name()? });
+            },
+            n => {
+                result.push(Field::Other(n));
+                break;
+            }
+        }
+    }
+    Ok(result)
+}
+
+fn read_type(leaf: u16, fields: &mut Fields) -> Result<CvType, String> {
+    Ok(match leaf {
+        0x0001 => CvType::Modifier { attribute: fields.u16()?, type_index: fields.u16()? },
+        0x0002 => CvType::Pointer { attribute: fields.u16()?, type_index: fields.u16()? },
+        0x0003 => CvType::Array {
+            element: fields.u16()?,
+            index: fields.u16()?,
+            size: fields.numeric()?,
+            name: fields.name()?
+        },
+        0x0004 | 0x0005 => {
+            let count = fields.u16()?;
+            let field_list = fields.u16()?;
+            /* property, derivation list and virtual function table shape */
+            fields.bytes(6)?;
+            CvType::Structure {
+                kind: if leaf == 0x0004 { "class" } else { "struct" },
+                count,
+                fields: field_list,
+                size: fields.numeric()?,
+                name: fields.name()?
+            }
+        },
+        0x0006 => {
+            let count = fields.u16()?;
+            let field_list = fields.u16()?;
+            fields.u16()?;
+            CvType::Structure {
+                kind: "union",
+                count,
+                fields: field_list,
+                size: fields.numeric()?,
+                name: fields.name()?
+            }
+        },
+        0x0007 => {
+            let count = fields.u16()?;
+            let underlying = fields.u16()?;
+            let field_list = fields.u16()?;
+            fields.u16()?;
+            CvType::Enum { count, underlying, fields: field_list, name: fields.name()? }
+        },
+        0x0008 => {
+            let ret = fields.u16()?;
+            let convention = fields.u8()?;
+            fields.u8()?;
+            CvType::Procedure {
+                ret,
+                convention,
+                params: fields.u16()?,
+                list: fields.u16()?
+            }
+        },
+        0x0201 => {
+            let count = fields.u16()?;
+            let mut args = Vec::new();
+            for _ in 0..count {
+                args.push(fields.u16()?);
+            }
+            CvType::ArgList(args)
+        },
+        0x0204 => CvType::FieldList(read_field_list(fields)?),
+        _ => CvType::Other(leaf, fields.vec.to_vec())
+    })
+}
+
+/* the records of a CodeView 4 $$TYPES segment, indices start at 1000H */
+pub fn read_types(vec: &[u8]) -> Result<Vec<(u16, CvType)>, String> {
+    check_signature(vec)?;
+    let mut result = Vec::new();
+    let mut i = 4;
+    let mut index = 0x1000u16;
+    while i + 2 <= vec.len() {
+        let length = read_u16(&vec[i..]) as usize;
+        if length == 0 {
+            break;
+        }
+        let record = vec.get(i+2..i+2+length)
+                .ok_or_else(|| format!("type at offset {} is truncated", i))?;
+        if length < 2 {
+            return Err(format!("type at offset {} is truncated", i));
+        }
+        let mut fields = Fields { vec: &record[2..], i: 0 };
+        let t = read_type(read_u16(record), &mut fields)
+                .map_err(|reason| format!("type {:04x}: {}", index, reason))?;
+        result.push((index, t));
+        index = index.wrapping_add(1);
+        i += 2 + length;
+    }
+    Ok(result)
+}
+
+/* where an address of the $$SYMBOLS segment points with its fixup applied:
+ * the target segment index, 0 when it is not a segment, a name and offset */
+pub fn resolve_address(module: &Module, segment: usize, address: &Address)
+        -> (usize, String, u32) {
+    let fixup = module.fixups.iter()
+            .find(|f| f.segment == segment && f.offset == address.at as u32);
+    let fixup = match fixup {
+        Some(fixup) => fixup,
+        None => return (0, format!("{:04x}", address.segment), address.offset)
+    };
+    let offset = address.offset.wrapping_add(fixup.displacement);
+    match fixup.target {
+        Reference::Segment(i) => (i, module.segment_name(i).to_string(), offset),
+        Reference::External(i) => {
+            let name = module.external(i).map_or("?", |e| e.name.as_str());
+            (0, name.to_string(), offset)
+        },
+        Reference::Group(i) => (0, format!("group {}", i), offset),
+        Reference::Frame(frame) => (0, format!("{:04x}", frame), offset),
+        Reference::Location | Reference::Target => (0, "?".to_string(), offset)
+    }
+}
+
+#[test]
+fn symbols() {
+    let vec = [0x01, 0x00, 0x00, 0x00,
+            /* S_BPREL16 -4, short, "I" */
+            0x08, 0x00, 0x00, 0x01, 0xfc, 0xff, 0x11, 0x00, 0x01, 0x49,
+            /* S_GPROC16 "F" at 0010:0000, 20 bytes */
+            0x1d, 0x00, 0x05, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x14, 0x00, 0x03, 0x00, 0x12, 0x00,
+            0x10, 0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x01, 0x46,
+            0x02, 0x00, 0x06, 0x00];
+    let symbols = read_symbols(&vec).unwrap();
+    assert_eq!(symbols[0], (4, CvSymbol::BpRelative {
+        offset: -4,
+        type_index: 0x11,
+        name: "I".to_string()
+    }));
+    assert_eq!(symbols[1], (14, CvSymbol::Procedure {
+        global: true,
+        address: Address { offset: 0x10, segment: 0, at: 36 },
+        length: 20,
+        debug_start: 3,
+        debug_end: 0x12,
+        type_index: 0x1000,
+        name: "F".to_string()
+    }));
+    assert_eq!(symbols[2], (45, CvSymbol::End));
+    assert!(read_symbols(&vec[..20]).is_err());
+    assert!(read_symbols(&[0x00, 0x00, 0x00, 0x00]).is_err());
+}
+
+#[test]
+fn types() {
+    let vec = [0x01, 0x00, 0x00, 0x00,
+            /* LF_POINTER far to short */
+            0x06, 0x00, 0x02, 0x00, 0x01, 0x00, 0x11, 0x00,
+            /* LF_FIELDLIST with one member */
+            0x0c, 0x00, 0x04, 0x02, 0x06, 0x04, 0x20, 0x00, 0x00, 0x00,
+            0x02, 0x00, 0x01, 0x58];
+    let types = read_types(&vec).unwrap();
+    assert_eq!(types[0].1.to_string(), "short far*");
+    assert_eq!(types[1].0, 0x1001);
+    assert_eq!(types[1].1.to_string(), "{ X: uchar at 2 }");
+    assert_eq!(type_name(0x0211), "short far*");
+}
@@ -0,0 +1,302 @@
+use std::fmt;
+
+use ::objrec::*;
+
+/* what the linker does when a COMDAT name is defined more than once */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Selection {
+    NoMatch,
+    PickAny,
+    SameSize,
+    ExactMatch,
+    Other(u8)
+}
+
+/* where the data of a COMDAT goes */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Allocation {
+    Explicit {
+        group: usize,
+        segment: usize,
+        frame: u16
+    },
+    FarCode,
+    FarData,
+    Code32,
+    Data32,
+    Other(u8)
+}
+
+/* an initialized communal data record, with its LINSYM line numbers */
+pub struct Comdat {
+    pub is32: bool,
+    pub continuation: bool,
+    pub iterated: bool,
+    pub local: bool,
+    pub code_segment: bool,
+    pub selection: Selection,
+    pub allocation: Allocation,
+    pub align: u8,
+    pub offset: u32,
+    pub type_index: usize,
+    pub name_index: usize,
+    pub data: Vec<u8>,
+    pub lines: Vec<(u16, u32)>
+}
+
+/* a LINSYM record: line numbers for the COMDAT of a name */
+pub struct Linsym {
+    pub continuation: bool,
+    pub name_index: usize,
+    pub lines: Vec<(u16, u32)>
+}
+
+impl fmt::Display for Selection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Selection::NoMatch => write!(f, "no match"),
+            Selection::PickAny => write!(f, "pick any"),
+            Selection::SameSize => write!(f, "same size"),
+            Selection::ExactMatch => write!(f, "exact match"),
+            Selection::Other(n) => write!(f, "<bad value {:x}>", n)
+        }
+    }
+}
+
+impl fmt::Display for Allocation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Allocation::Explicit { group, segment, frame } => {
+                write!(f, "explicit, group index: {}, segment index: {}",
+                        group, segment)?;
+                if segment == 0 {
+                    write!(f, ", frame number: {}", frame)?;
+                }
+                Ok(())
+            },
+            Allocation::FarCode => write!(f, "far code"),
+            Allocation::FarData => write!(f, "far data"),
+            Allocation::Code32 => write!(f, "32-bit code"),
+            Allocation::Data32 => write!(f, "32-bit data"),
+            Allocation::Other(n) => write!(f, "<bad value {:x}>", n)
+        }
+    }
+}
+
+pub fn align_to_string(align: u8) -> &'static str {
+    match align {
+        0 => "from segment",
+        1 => "byte",
+        2 => "word",
+        3 => "paragraph",
+        4 => "256-byte page",
+        5 => "double word",
+        6 => "4K page",
+        _ => "<bad value>"
+    }
+}
+
+impl Comdat {
+    /* the data with iterated blocks expanded */
+    pub fn contents(&self) -> Result<Vec<u8>, String> {
+        if self.iterated {
+            expand_iterated(&self.data, self.is32)
+        } else {
+            Ok(self.data.clone())
+        }
+    }
+
+    /* size of the contents, from the enumerated data offset */
+    pub fn size(&self) -> Result<u32, String> {
+        let length = self.contents()?.len() as u32;
+        self.offset.checked_add(length).ok_or_else(|| "size is too large".to_string())
+    }
+}
+
+pub fn read_comdat(vec: &[u8], is32: bool) -> Result<Comdat, String> {
+    let offset_size = if is32 { 4 } else { 2 };
+    if vec.len() < 3 + offset_size + 1 {
+        return Err("record is truncated".to_string());
+    }
+    let flags = vec[0];
+    let attributes = vec[1];
+    let selection = match attributes & 0xf0 {
+        0x00 => Selection::NoMatch,
+        0x10 => Selection::PickAny,
+        0x20 => Selection::SameSize,
+        0x30 => Selection::ExactMatch,
+        n => Selection::Other(n >> 4)
+    };
+    let align = vec[2];
+    let offset = read_offset(&vec[3..], is32);
+    let mut i = 3 + offset_size;
+    let (type_index, n) = read_index(&vec[i..])?;
+    i += n;
+    let allocation = match attributes & 0x0f {
+        0x00 => {
+            let (group, n) = read_index(&vec[i.min(vec.len())..])
+                    .map_err(|_| "public base is truncated".to_string())?;
+            i += n;
+            let (segment, n) = read_index(&vec[i.min(vec.len())..])
+                    .map_err(|_| "public base is truncated".to_string())?;
+            i += n;
+            let mut frame = 0;
+            if segment == 0 {
+                if vec.len() < i + 2 {
+                    return Err("public base is truncated".to_string());
+                }
+                frame = read_u16(&vec[i..]);
+                i += 2;
+            }
+            Allocation::Explicit { group, segment, frame }
+        },
+        0x01 => Allocation::FarCode,
+        0x02 => Allocation::FarData,
+        0x03 => Allocation::Code32,
+        0x04 => Allocation::Data32,
+        n => Allocation::Other(n)
+    };
+    let (name_index, n) = read_index(&vec[i.min(vec.len())..])
+            .map_err(|_| "public name index is missing".to_string())?;
+    i += n;
+    Ok(Comdat {
+        is32,
+        continuation: flags & 0x01 != 0,
+        iterated: flags & 0x02 != 0,
+        local: flags & 0x04 != 0,
+        code_segment: flags & 0x08 != 0,
+        selection,
+        allocation,
+        align,
+        offset,
+        type_index,
+        name_index,
+        data: vec[i..].to_vec(),
+        lines: Vec::new()
+    })
+}
+
+pub fn read_linsym(vec: &[u8], is32: bool) -> Result<Linsym, String> {
+    if vec.len() < 2 {
+        return Err("record is truncated".to_string());
+    }
+    let (name_index, mut i) = read_index(&vec[1..])?;
+    i += 1;
+    let entry_size = if is32 { 6 } else { 4 };
+    let mut lines = Vec::new();
+    while i + entry_size <= vec.len() {
+        lines.push((read_u16(&vec[i..]), read_offset(&vec[i+2..], is32)));
+        i += entry_size;
+    }
+    if i != vec.len() {
+        return Err("line number entry is truncated".to_string());
+    }
+    Ok(Linsym {
+        continuation: vec[0] & 0x01 != 0,
+        name_index,
+        lines
+    })
+}
+
+/* the linker rule: which of the COMDATs defining the same name to keep */
+pub fn select<'a>(comdats: &[&'a Comdat]) -> Result<&'a Comdat, String> {
+    let first = match comdats.first() {
+        Some(first) => *first,
+        None => return Err("no definition".to_string())
+    };
+    if comdats.len() == 1 {
+        return Ok(first);
+    }
+    match first.selection {
+        Selection::NoMatch =>
+            Err(format!("{} definitions, duplicates are not allowed", comdats.len())),
+        Selection::PickAny => Ok(first),
+        Selection::SameSize => {
+            let size = first.size()?;
+            for comdat in &comdats[1..] {
+                if comdat.size()? != size {
+                    return Err("definitions differ in size".to_string());
+                }
+            }
+            Ok(first)
+        },
+        Selection::ExactMatch => {
+            let contents = first.contents()?;
+            for comdat in &comdats[1..] {
+                if comdat.offset != first.offset || comdat.contents()? != contents {
+                    return Err("definitions differ in contents".to_string());
+                }
+            }
+            Ok(first)
+        },
+        Selection::Other(n) => Err(format!("bad selection criteria {:x}", n))
+    }
+}
+
+#[cfg(test)]
+fn test_comdat(selection: u8, data: &[u8]) -> Comdat {
+    let mut vec = vec![0x00, selection | 0x02, 0x00, 0x00, 0x00, 0x00, 0x05];
+    vec.extend_from_slice(data);
+    read_comdat(&vec, false).unwrap()
+}
+
+#[test]
+fn comdat() {
+    let comdat = read_comdat(&[0x05, 0x20, 0x02, 0x10, 0x00, 0x03,
+            0x01, 0x00, 0x34, 0x12, 0x07, 0xaa, 0xbb], false).unwrap();
+    assert!(comdat.continuation && comdat.local && !comdat.iterated);
+    assert_eq!(comdat.selection, Selection::SameSize);
+    assert_eq!(comdat.allocation,
+            Allocation::Explicit { group: 1, segment: 0, frame: 0x1234 });
+    assert_eq!((comdat.align, comdat.offset, comdat.type_index), (2, 0x10, 3));
+    assert_eq!(comdat.name_index, 7);
+    assert_eq!(comdat.data, vec![0xaa, 0xbb]);
+
+    let comdat = read_comdat(&[0x02, 0x14, 0x00, 0x00, 0x00, 0x01, 0x00,
+            0x00, 0x09, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0xcc, 0xdd],
+            true).unwrap();
+    assert_eq!(comdat.allocation, Allocation::Data32);
+    assert_eq!((comdat.offset, comdat.name_index), (0x10000, 9));
+    assert_eq!(comdat.contents().unwrap(), vec![0xcc, 0xdd, 0xcc, 0xdd, 0xcc, 0xdd]);
+    assert!(read_comdat(&[0x00, 0x00, 0x00, 0x00, 0x00, 0x00], false).is_err());
+
+    /* two byte type and name indices */
+    let comdat = read_comdat(&[0x00, 0x11, 0x00, 0x00, 0x00, 0x81, 0x00,
+            0x80, 0x90, 0xee], false).unwrap();
+    assert_eq!((comdat.type_index, comdat.name_index), (0x100, 0x90));
+    assert_eq!(comdat.data, vec![0xee]);
+}
+
+#[test]
+fn linsym() {
+    let linsym = read_linsym(&[0x00, 0x05, 0x0a, 0x00, 0x00, 0x00,
+            0x0b, 0x00, 0x06, 0x00], false).unwrap();
+    assert_eq!(linsym.name_index, 5);
+    assert_eq!(linsym.lines, vec![(10, 0), (11, 6)]);
+    assert!(read_linsym(&[0x00, 0x05, 0x0a, 0x00, 0x00], false).is_err());
+    let linsym = read_linsym(&[0x00, 0x81, 0x05, 0x0a, 0x00, 0x00, 0x00], false).unwrap();
+    assert_eq!((linsym.name_index, linsym.lines.len()), (0x105, 1));
+}
+
+#[test]
+fn selection() {
+    let a = test_comdat(0x10, &[1, 2, 3]);
+    let b = test_comdat(0x10, &[1, 2]);
+    assert!(select(&[&a, &b]).is_ok());
+
+    let a = test_comdat(0x00, &[1, 2, 3]);
+    assert!(select(&[&a]).is_ok());
+    assert!(select(&[&a, &a]).is_err());
+
+    let a = test_comdat(0x20, &[1, 2, 3]);
+    let b = test_comdat(0x20, &[4, 5, 6]);
+    let c = test_comdat(0x20, &[4, 5]);
+    assert!(select(&[&a, &b]).is_ok());
+    assert!(select(&[&a, &c]).is_err());
+
+    let a = test_comdat(0x30, &[1, 2, 3]);
+    let b = test_comdat(0x30, &[1, 2, 3]);
+    let c = test_comdat(0x30, &[4, 5, 6]);
+    assert!(select(&[&a, &b]).is_ok());
+    assert!(select(&[&a, &c]).is_err());
+}
@@ -0,0 +1,297 @@
+use std::fmt;
+
+use ::objrec::*;
+
+/* an IMPDEF entry: the DLL entry is an ordinal or a name */
+#[derive(Clone, PartialEq, Debug)]
+pub enum Entry {
+    Ordinal(u16),
+    /* an empty name means the internal name */
+    Name(String)
+}
+
+/* an IMPDEF comment: a name imported from a DLL */
+#[derive(Clone, PartialEq, Debug)]
+pub struct Import {
+    pub internal: String,
+    pub module: String,
+    pub entry: Entry
+}
+
+/* an EXPDEF comment: a name exported by the DLL being built */
+#[derive(Clone, PartialEq, Debug)]
+pub struct Export {
+    pub exported: String,
+    /* empty when it is the exported name */
+    pub internal: String,
+    pub ordinal: Option<u16>,
+    pub resident: bool,
+    pub no_data: bool,
+    pub parameters: u8
+}
+
+/* a COMENT record: attribute flags, class and the decoded text */
+pub struct Coment {
+    pub no_purge: bool,
+    pub no_list: bool,
+    pub class: u8,
+    pub comment: Comment
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Comment {
+    Translator(String),
+    Copyright(String),
+    LibrarySpecifier(String),
+    DosVersion(u16),
+    MemoryModel {
+        processor: Option<&'static str>,
+        model: Option<&'static str>,
+        optimized: bool,
+        text: String
+    },
+    DosSeg,
+    DefaultLibrary(String),
+    /* class A0H subtypes */
+    Impdef(Import),
+    Expdef(Export),
+    Incdef {
+        extdef_delta: u16,
+        linnum_delta: u16
+    },
+    ProtectedLibrary,
+    Lnkdir {
+        flags: u8,
+        pseudocode_version: u8,
+        codeview_version: u8
+    },
+    BigEndian,
+    PreCompile,
+    Extension(u8, Vec<u8>),
+    /* class A1H: CodeView or another debug style and its version */
+    DebugStyle {
+        version: u8,
+        style: String
+    },
+    LinkPass(u8),
+    LibraryModule(String),
+    Exestr(String),
+    Incerr,
+    Nopad(Vec<usize>),
+    /* pairs of external indices: the weak or lazy one and its default */
+    WeakExterns(Vec<(usize, usize)>),
+    LazyExterns(Vec<(usize, usize)>),
+    Other(Vec<u8>)
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Entry::Ordinal(n) => write!(f, "ordinal {}", n),
+            Entry::Name(ref name) if name.is_empty() => write!(f, "internal name"),
+            Entry::Name(ref name) => write!(f, "name {}", name)
+        }
+    }
+}
+
+fn text(vec: &[u8]) -> String {
+    String::from_utf8_lossy(vec).to_string()
+}
+
+fn byte(vec: &[u8], i: usize) -> Result<u8, String> {
+    vec.get(i).cloned().ok_or_else(|| "record is truncated".to_string())
+}
+
+fn word(vec: &[u8], i: usize) -> Result<u16, String> {
+    match vec.get(i..i+2) {
+        Some(bytes) => Ok(read_u16(bytes)),
+        None => Err("record is truncated".to_string())
+    }
+}
+
+/* letters of the memory model comment: processor, model and optimization */
+fn read_memory_model(vec: &[u8]) -> Comment {
+    let mut processor = None;
+    let mut model = None;
+    let mut optimized = false;
+    for &c in vec {
+        match c {
+            b'0' => processor = Some("8086"),
+            b'1' => processor = Some("80186"),
+            b'2' => processor = Some("80286"),
+            b'3' => processor = Some("80386"),
+            b's' => model = Some("small"),
+            b'c' => model = Some("compact"),
+            b'm' => model = Some("medium"),
+            b'l' => model = Some("large"),
+            b'h' => model = Some("huge"),
+            b'O' => optimized = true,
+            _ => {}
+        }
+    }
+    Comment::MemoryModel { processor, model, optimized, text: text(vec) }
+}
+
+fn read_impdef(vec: &[u8]) -> Result<Comment, String> {
+    let by_ordinal = byte(vec, 0)? != 0;
+    let (internal, n) = read_name(&vec[1..])?;
+    let mut i = 1 + n;
+    let (module, n) = read_name(&vec[i..])?;
+    i += n;
+    let entry = if by_ordinal {
+        Entry::Ordinal(word(vec, i)?)
+    } else {
+        Entry::Name(read_name(&vec[i..])?.0)
+    };
+    Ok(Comment::Impdef(Import { internal, module, entry }))
+}
+
+fn read_expdef(vec: &[u8]) -> Result<Comment, String> {
+    let flags = byte(vec, 0)?;
+    let (exported, n) = read_name(&vec[1..])?;
+    let mut i = 1 + n;
+    let (internal, n) = read_name(&vec[i..])?;
+    i += n;
+    let ordinal = if flags & 0x80 != 0 { Some(word(vec, i)?) } else { None };
+    Ok(Comment::Expdef(Export {
+        exported,
+        internal,
+        ordinal,
+        resident: flags & 0x40 != 0,
+        no_data: flags & 0x20 != 0,
+        parameters: flags & 0x1f
+    }))
+}
+
+fn read_extension(vec: &[u8]) -> Result<Comment, String> {
+    let subtype = byte(vec, 0)?;
+    let p = &vec[1..];
+    match subtype {
+        0x01 => read_impdef(p),
+        0x02 => read_expdef(p),
+        0x03 => Ok(Comment::Incdef {
+            extdef_delta: word(p, 0)?,
+            linnum_delta: word(p, 2)?
+        }),
+        0x04 => Ok(Comment::ProtectedLibrary),
+        0x05 => Ok(Comment::Lnkdir {
+            flags: byte(p, 0)?,
+            pseudocode_version: byte(p, 1)?,
+            codeview_version: byte(p, 2)?
+        }),
+        0x06 => Ok(Comment::BigEndian),
+        0x07 => Ok(Comment::PreCompile),
+        n => Ok(Comment::Extension(n, p.to_vec()))
+    }
+}
+
+fn read_indices(vec: &[u8]) -> Result<Vec<usize>, String> {
+    let mut result = Vec::new();
+    let mut i = 0;
+    while i < vec.len() {
+        let (index, n) = read_index(&vec[i..])?;
+        result.push(index);
+        i += n;
+    }
+    Ok(result)
+}
+
+fn read_pairs(vec: &[u8]) -> Result<Vec<(usize, usize)>, String> {
+    let indices = read_indices(vec)?;
+    if !indices.len().is_multiple_of(2) {
+        return Err("external index pair is truncated".to_string());
+    }
+    Ok(indices.chunks(2).map(|p| (p[0], p[1])).collect())
+}
+
+/* translator names may be preceded by a length or other control bytes */
+fn read_translator(vec: &[u8]) -> String {
+    let start = vec.iter().position(|&b| b >= 0x20).unwrap_or(vec.len());
+    text(&vec[start..])
+}
+
+pub fn read_coment(vec: &[u8]) -> Result<Coment, String> {
+    let flags = byte(vec, 0)?;
+    let class = byte(vec, 1)?;
+    let p = &vec[2..];
+    let comment = match class {
+        0x00 | 0x80 => Comment::Translator(read_translator(p)),
+        0x01 => Comment::Copyright(read_translator(p)),
+        0x81 => Comment::LibrarySpecifier(text(p)),
+        0x9c => Comment::DosVersion(word(p, 0)?),
+        0x9d => read_memory_model(p),
+        0x9e => Comment::DosSeg,
+        0x9f => Comment::DefaultLibrary(text(p)),
+        0xa0 => read_extension(p)?,
+        0xa1 => Comment::DebugStyle { version: byte(p, 0)?, style: text(&p[1..]) },
+        0xa2 => Comment::LinkPass(byte(p, 0)?),
+        0xa3 => Comment::LibraryModule(read_name(p)?.0),
+        0xa4 => Comment::Exestr(text(p)),
+        0xa6 => Comment::Incerr,
+        0xa7 => Comment::Nopad(read_indices(p)?),
+        0xa8 => Comment::WeakExterns(read_pairs(p)?),
+        0xa9 => Comment::LazyExterns(read_pairs(p)?),
+        _ => Comment::Other(p.to_vec())
+    };
+    Ok(Coment {
+        no_purge: flags & 0x80 != 0,
+        no_list: flags & 0x40 != 0,
+        class,
+        comment
+    })
+}
+
+#[test]
+fn impdef() {
+    let coment = read_coment(&[0x00, 0xa0, 0x01, 0x01, 0x03, 0x46, 0x4f, 0x4f,
+            0x03, 0x4c, 0x49, 0x42, 0x07, 0x00]).unwrap();
+    assert_eq!(coment.comment, Comment::Impdef(Import {
+        internal: "FOO".to_string(),
+        module: "LIB".to_string(),
+        entry: Entry::Ordinal(7)
+    }));
+    let coment = read_coment(&[0x00, 0xa0, 0x01, 0x00, 0x01, 0x41,
+            0x01, 0x42, 0x00]).unwrap();
+    assert_eq!(coment.comment, Comment::Impdef(Import {
+        internal: "A".to_string(),
+        module: "B".to_string(),
+        entry: Entry::Name(String::new())
+    }));
+    assert!(read_coment(&[0x00, 0xa0, 0x01, 0x01, 0x01, 0x41, 0x01, 0x42]).is_err());
+}
+
+#[test]
+fn expdef() {
+    let coment = read_coment(&[0x00, 0xa0, 0x02, 0xc2, 0x01, 0x41, 0x00,
+            0x05, 0x00]).unwrap();
+    assert_eq!(coment.comment, Comment::Expdef(Export {
+        exported: "A".to_string(),
+        internal: String::new(),
+        ordinal: Some(5),
+        resident: true,
+        no_data: false,
+        parameters: 2
+    }));
+}
+
+#[test]
+fn coment_classes() {
+    let coment = read_coment(&[0x80, 0x9d, 0x33, 0x4f, 0x6c]).unwrap();
+    assert!(coment.no_purge && !coment.no_list);
+    assert_eq!(coment.comment, Comment::MemoryModel {
+        processor: Some("80386"),
+        model: Some("large"),
+        optimized: true,
+        text: "3Ol".to_string()
+    });
+    let coment = read_coment(&[0x00, 0xa8, 0x02, 0x01, 0x03, 0x01]).unwrap();
+    assert_eq!(coment.comment, Comment::WeakExterns(vec![(2, 1), (3, 1)]));
+    assert!(read_coment(&[0x00, 0xa9, 0x02]).is_err());
+    let coment = read_coment(&[0x00, 0xa9, 0x81, 0x02, 0x01]).unwrap();
+    assert_eq!(coment.comment, Comment::LazyExterns(vec![(0x102, 1)]));
+    assert!(read_coment(&[0x00, 0xa9, 0x01, 0x81]).is_err());
+    let coment = read_coment(&[0x00, 0xa7, 0x01, 0x80, 0x90]).unwrap();
+    assert_eq!(coment.comment, Comment::Nopad(vec![1, 0x90]));
+    let coment = read_coment(&[0x00, 0x00, 0x05, 0x54, 0x43, 0x38, 0x36]).unwrap();
+    assert_eq!(coment.comment, Comment::Translator("TC86".to_string()));
+}
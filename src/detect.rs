@@ -0,0 +1,210 @@
+use std::fmt;
+
+use ::objrec::*;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Relocatable,
+    Absolute,
+    IntelLibrary,
+    MicrosoftLibrary,
+    Omf51,
+    Omf85,
+    Omf96,
+    Unknown
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub enum Confidence {
+    Low,
+    Medium,
+    High
+}
+
+/* the format of a file, how sure we are and why */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Detection {
+    pub format: Format,
+    pub confidence: Confidence,
+    pub explanation: String
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Format::Relocatable => write!(f, "OMF-86 relocatable module"),
+            Format::Absolute => write!(f, "OMF-86 absolute module"),
+            Format::IntelLibrary => write!(f, "Intel library"),
+            Format::MicrosoftLibrary => write!(f, "Microsoft library"),
+            Format::Omf51 => write!(f, "OMF-51 module"),
+            Format::Omf85 => write!(f, "OMF-85 module"),
+            Format::Omf96 => write!(f, "OMF-96 module"),
+            Format::Unknown => write!(f, "not an OMF file")
+        }
+    }
+}
+
+impl fmt::Display for Detection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let confidence = match self.confidence {
+            Confidence::Low => "low",
+            Confidence::Medium => "medium",
+            Confidence::High => "high"
+        };
+        write!(f, "{} ({} confidence: {})", self.format, confidence, self.explanation)
+    }
+}
+
+fn detection(format: Format, confidence: Confidence, explanation: String) -> Detection {
+    Detection { format, confidence, explanation }
+}
+
+/* the types and contents of the records at the start of a file, up to the
+ * first one that is truncated or has a bad checksum, with the reason */
+fn records(vec: &[u8]) -> (Vec<(u8, &[u8])>, Option<String>) {
+    let mut result = Vec::new();
+    let mut i = 0;
+    while i < vec.len() {
+        if vec.len() < i + 3 {
+            return (result, Some(format!("record at offset {} is truncated", i)));
+        }
+        let length = read_u16(&vec[i+1..]) as usize;
+        if length == 0 || vec.len() < i + 3 + length {
+            return (result, Some(format!("record at offset {} is truncated", i)));
+        }
+        let record = &vec[i..i+3+length];
+        if !checksum_ok(record) {
+            return (result, Some(format!("bad checksum at offset {}", i)));
+        }
+        result.push((vec[i], &record[3..record.len()-1]));
+        i += 3 + length;
+    }
+    (result, None)
+}
+
+fn detect_omf86(vec: &[u8]) -> Detection {
+    let (format, header) = match vec[0] {
+        0x80 => (Format::Relocatable, "THEADR"),
+        0x82 => (Format::Absolute, "LHEADR"),
+        0x6e => (Format::Absolute, "RHEADR"),
+        _ => (Format::IntelLibrary, "LIBHED")
+    };
+    let (records, problem) = records(vec);
+    let mut explanation = format!("starts with {}", header);
+    let confidence = if let Some(&(t, _)) = records.iter().find(|r| record_type(r.0).is_none()) {
+        explanation += &format!(", but has unknown record type {:02X}H", t);
+        Confidence::Low
+    } else if let Some(problem) = problem {
+        explanation += &format!(", {} record(s) read before a {}", records.len(), problem);
+        Confidence::Medium
+    } else {
+        explanation += &format!(", {} well formed record(s)", records.len());
+        Confidence::High
+    };
+    detection(format, confidence, explanation)
+}
+
+fn detect_microsoft_library(vec: &[u8]) -> Detection {
+    if vec.len() < 10 {
+        return detection(Format::MicrosoftLibrary, Confidence::Low,
+                "0xF0 page header is truncated".to_string());
+    }
+    let page_size = read_u16(&vec[1..]) as usize + 3;
+    if !page_size.is_power_of_two() || page_size < 16 {
+        return detection(Format::MicrosoftLibrary, Confidence::Low,
+                format!("0xF0 page header, but page size {} is not a power of two",
+                        page_size));
+    }
+    match vec.get(page_size) {
+        Some(&0x80) => detection(Format::MicrosoftLibrary, Confidence::High,
+                format!("0xF0 page header, page size {}, first member starts with THEADR",
+                        page_size)),
+        _ => detection(Format::MicrosoftLibrary, Confidence::Medium,
+                format!("0xF0 page header, page size {}, no THEADR at the first page",
+                        page_size))
+    }
+}
+
+/* the 8-bit formats share their module header type; OMF-51 translators have
+ * ids FDH to FFH, OMF-85 has relocation records and an empty end of file
+ * record, and the OMF-96 tools write record types neither of them has */
+fn detect_omf8(vec: &[u8]) -> Detection {
+    let (records, problem) = records(vec);
+    if let Some(problem) = problem {
+        return detection(Format::Unknown, Confidence::Medium,
+                format!("starts with 02H, but {}", problem));
+    }
+    let translator = match read_name(records[0].1) {
+        Ok((_, n)) => records[0].1.get(n).cloned(),
+        Err(_) => None
+    };
+    let omf51 = [0x02, 0x04, 0x06, 0x08, 0x0e, 0x10, 0x12, 0x16, 0x18];
+    let omf85 = [0x02, 0x04, 0x06, 0x08, 0x0e, 0x10, 0x12, 0x16, 0x18,
+            0x20, 0x22, 0x24, 0x2e];
+    if let Some(&(t, _)) = records.iter().find(|r| !omf85.contains(&r.0)) {
+        return detection(Format::Omf96, Confidence::Low,
+                format!("module header 02H and record type {:02X}H of neither OMF-51 \
+                        nor OMF-85", t));
+    }
+    let empty_eof = records.iter().any(|r| r.0 == 0x0e && r.1.is_empty());
+    match translator {
+        Some(0xfd ..= 0xff) if records.iter().all(|r| omf51.contains(&r.0)) && !empty_eof =>
+            detection(Format::Omf51, Confidence::High,
+                "module header with an OMF-51 translator id, only OMF-51 record types"
+                        .to_string()),
+        Some(0xfd ..= 0xff) => detection(Format::Omf51, Confidence::Medium,
+                "module header with an OMF-51 translator id".to_string()),
+        _ if empty_eof => detection(Format::Omf85, Confidence::High,
+                "module header and an empty end of file record".to_string()),
+        _ => detection(Format::Omf85, Confidence::Medium,
+                "module header with only OMF-85 record types".to_string())
+    }
+}
+
+pub fn detect(vec: &[u8]) -> Detection {
+    match vec.first() {
+        None => detection(Format::Unknown, Confidence::High, "file is empty".to_string()),
+        Some(&0x80) | Some(&0x82) | Some(&0x6e) | Some(&0xa4) => detect_omf86(vec),
+        Some(&0xf0) => detect_microsoft_library(vec),
+        Some(&0x02) => detect_omf8(vec),
+        Some(&b) => detection(Format::Unknown, Confidence::High,
+                format!("first byte {:02X}H is no module header", b))
+    }
+}
+
+#[cfg(test)]
+fn record(t: u8, data: &[u8]) -> Vec<u8> {
+    let mut vec = vec![t, (data.len() + 1) as u8, 0];
+    vec.extend_from_slice(data);
+    let sum = vec.iter().fold(0u8, |s, &b| s.wrapping_add(b));
+    vec.push(sum.wrapping_neg());
+    vec
+}
+
+#[test]
+fn detection_of_formats() {
+    let mut obj = record(0x80, &[0x01, 0x41]);
+    obj.extend(record(0x8a, &[0x00]));
+    assert_eq!(detect(&obj).format, Format::Relocatable);
+    assert_eq!(detect(&obj).confidence, Confidence::High);
+    obj.push(0x8a);
+    assert_eq!(detect(&obj).confidence, Confidence::Medium);
+
+    let mut omf51 = record(0x02, &[0x01, 0x41, 0xfd, 0x00]);
+    omf51.extend(record(0x0e, &[0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]));
+    assert_eq!(detect(&omf51).format, Format::Omf51);
+    let mut omf85 = record(0x02, &[0x01, 0x41, 0x03, 0x00]);
+    omf85.extend(record(0x0e, &[]));
+    assert_eq!((detect(&omf85).format, detect(&omf85).confidence),
+            (Format::Omf85, Confidence::High));
+    let mut omf96 = record(0x02, &[0x01, 0x41, 0x00, 0x00]);
+    omf96.extend(record(0x2a, &[]));
+    assert_eq!(detect(&omf96).format, Format::Omf96);
+
+    let mut library = vec![0xf0, 0x0d, 0x00];
+    library.resize(16, 0);
+    library.extend(record(0x80, &[0x01, 0x41]));
+    assert_eq!((detect(&library).format, detect(&library).confidence),
+            (Format::MicrosoftLibrary, Confidence::High));
+    assert_eq!(detect(b"MZ\x90\x00").format, Format::Unknown);
+}
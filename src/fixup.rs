@@ -0,0 +1,160 @@
+use ::objrec::*;
+
+/* how a frame or a target is given: a method and its index */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Reference {
+    Segment(usize),
+    Group(usize),
+    External(usize),
+    Frame(u16),
+    /* frame methods 4 and 5: the frame of the location or of the target */
+    Location,
+    Target
+}
+
+/* a fixup with its threads resolved, at an offset of a segment */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Fixup {
+    pub segment: usize,
+    pub offset: u32,
+    pub segment_relative: bool,
+    /* 0 low byte, 1 offset, 2 base, 3 pointer, 4 high byte, 5 loader
+     * resolved offset, 9 32-bit offset, 11 48-bit pointer */
+    pub location: u8,
+    pub frame: Reference,
+    pub target: Reference,
+    pub displacement: u32
+}
+
+/* the frame and target threads that fixups can refer to */
+#[derive(Default)]
+pub struct Threads {
+    frames: [Option<Reference>; 4],
+    targets: [Option<Reference>; 4]
+}
+
+fn byte(vec: &[u8], i: usize) -> Result<u8, String> {
+    vec.get(i).cloned().ok_or_else(|| "fixup is truncated".to_string())
+}
+
+fn reference(method: u8, vec: &[u8], i: &mut usize) -> Result<Reference, String> {
+    let rest = vec.get(*i..).unwrap_or(&[]);
+    let (reference, n) = match method & 3 {
+        0 => read_index(rest).map(|(index, n)| (Reference::Segment(index), n))?,
+        1 => read_index(rest).map(|(index, n)| (Reference::Group(index), n))?,
+        2 => read_index(rest).map(|(index, n)| (Reference::External(index), n))?,
+        _ => (Reference::Frame(read_u16(rest.get(..2).ok_or("fixup is truncated")?)), 2)
+    };
+    *i += n;
+    Ok(reference)
+}
+
+fn frame(method: u8, vec: &[u8], i: &mut usize) -> Result<Reference, String> {
+    match method {
+        0 ..= 3 => reference(method, vec, i),
+        4 => Ok(Reference::Location),
+        5 => Ok(Reference::Target),
+        n => Err(format!("bad frame method {}", n))
+    }
+}
+
+/* the FIXDAT byte, frame and target datum and the target displacement of
+ * a fixup, MODEND or REGINT; the displacement has 4 bytes in the 32-bit
+ * records; returns them and the number of bytes read */
+pub fn read_fixdat(vec: &[u8], threads: &Threads, is32: bool)
+        -> Result<(Reference, Reference, u32, usize), String> {
+    let fixdat = byte(vec, 0)?;
+    let mut i = 1;
+    let frame = if fixdat & 0x80 != 0 {
+        threads.frames[((fixdat >> 4) & 3) as usize].ok_or("undefined frame thread")?
+    } else {
+        frame((fixdat >> 4) & 7, vec, &mut i)?
+    };
+    let target = if fixdat & 0x08 != 0 {
+        threads.targets[(fixdat & 3) as usize].ok_or("undefined target thread")?
+    } else {
+        reference(fixdat & 3, vec, &mut i)?
+    };
+    /* the displacement is there unless P is set */
+    let displacement = if fixdat & 0x04 == 0 {
+        let size = if is32 { 4 } else { 2 };
+        let d = read_offset(vec.get(i..i+size).ok_or("fixup is truncated")?, is32);
+        i += size;
+        d
+    } else {
+        0
+    };
+    Ok((frame, target, displacement, i))
+}
+
+/* the fixups of a FIXUPP record; their offsets are relative to the data
+ * record they follow, which starts at data_offset of segment */
+pub fn read_fixupp(vec: &[u8], threads: &mut Threads, segment: usize, data_offset: u32,
+        is32: bool) -> Result<Vec<Fixup>, String> {
+    let mut result = Vec::new();
+    let mut i = 0;
+    while i < vec.len() {
+        let b = vec[i];
+        if b & 0x80 == 0 {
+            /* thread field */
+            let method = (b >> 2) & 7;
+            let number = (b & 3) as usize;
+            i += 1;
+            if b & 0x40 != 0 {
+                threads.frames[number] = Some(frame(method, vec, &mut i)?);
+            } else {
+                threads.targets[number] = Some(reference(method, vec, &mut i)?);
+            }
+            continue;
+        }
+        let locat = (b as u16) << 8 | byte(vec, i + 1)? as u16;
+        let (frame, target, displacement, n) = read_fixdat(&vec[i+2..], threads, is32)?;
+        i += 2 + n;
+        result.push(Fixup {
+            segment,
+            offset: data_offset + (locat & 0x3ff) as u32,
+            segment_relative: locat & 0x4000 != 0,
+            location: ((locat >> 10) & 0xf) as u8,
+            frame,
+            target,
+            displacement
+        });
+    }
+    Ok(result)
+}
+
+#[test]
+fn fixups() {
+    let mut threads = Threads::default();
+    /* target thread 1 is segment 2, then an offset fixup at 4 using it and a
+     * base fixup at 6 to external 1 with frame of the target */
+    let fixups = read_fixupp(&[0x01, 0x02,
+            0xc4, 0x04, 0x59, 0x10, 0x00,
+            0xc8, 0x06, 0x56, 0x01], &mut threads, 3, 0x100, false).unwrap();
+    assert_eq!(fixups[0], Fixup {
+        segment: 3,
+        offset: 0x104,
+        segment_relative: true,
+        location: 1,
+        frame: Reference::Target,
+        target: Reference::Segment(2),
+        displacement: 0x10
+    });
+    assert_eq!((fixups[1].location, fixups[1].target), (2, Reference::External(1)));
+    assert_eq!(fixups[1].displacement, 0);
+    assert!(read_fixupp(&[0xc4, 0x00, 0x5a], &mut threads, 1, 0, false).is_err());
+    assert!(read_fixupp(&[0xc4, 0x00, 0x9a], &mut Threads::default(), 1, 0, false).is_err());
+}
+
+#[test]
+fn fixup_indices() {
+    let mut threads = Threads::default();
+    /* an offset fixup at 0 to external 102H in the frame of the target */
+    let fixups = read_fixupp(&[0xc4, 0x00, 0x56, 0x81, 0x02], &mut threads, 1, 0, false).unwrap();
+    assert_eq!((fixups[0].frame, fixups[0].target), (Reference::Target, Reference::External(0x102)));
+    assert!(read_fixupp(&[0xc4, 0x00, 0x56, 0x81], &mut threads, 1, 0, false).is_err());
+    /* FIXUPP32 displacements have 4 bytes */
+    let fixups = read_fixupp(&[0xc4, 0x00, 0x50, 0x01, 0x78, 0x56, 0x34, 0x12],
+            &mut threads, 1, 0, true).unwrap();
+    assert_eq!(fixups[0].displacement, 0x12345678);
+}
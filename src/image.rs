@@ -0,0 +1,105 @@
+use ::module::*;
+use ::comdat::*;
+use ::bakpat::*;
+
+/* the largest image built from the lengths and offsets of a file */
+const MAX_IMAGE: usize = 0x1000000;
+
+/* the contents of a segment: the data records in order, then the
+ * back-patches; returns the image and the data and patches that did not
+ * apply */
+pub fn segment_image(module: &Module, segment: usize) -> (Vec<u8>, Vec<String>) {
+    let mut errors = Vec::new();
+    let mut image = match module.segments.get(segment.wrapping_sub(1)) {
+        Some(s) if s.length as usize > MAX_IMAGE => {
+            errors.push(format!("segment length {} is too large", s.length));
+            Vec::new()
+        },
+        Some(s) => vec![0u8; s.length as usize],
+        None => Vec::new()
+    };
+    for data in module.data.iter().filter(|d| d.segment == segment) {
+        let end = data.offset as usize + data.data.len();
+        if end > MAX_IMAGE {
+            errors.push(format!("data at offset {} is too far", data.offset));
+            continue;
+        }
+        if end > image.len() {
+            image.resize(end, 0);
+        }
+        image[data.offset as usize..end].copy_from_slice(&data.data);
+    }
+    for bakpat in module.bakpats.iter().filter(|b| b.target == Target::Segment(segment)) {
+        if let Err(reason) = bakpat.apply(&mut image) {
+            errors.push(reason);
+        }
+    }
+    (image, errors)
+}
+
+/* the contents of a COMDAT, with the NBKPAT records of its name applied */
+pub fn comdat_image(module: &Module, comdat: &Comdat) -> Result<Vec<u8>, String> {
+    let contents = comdat.contents()?;
+    /* continuations follow the data, so only the first record can leave a
+     * gap, and not one larger than the data */
+    if comdat.offset as usize > contents.len() {
+        return Err(format!("data offset {} is past the {} bytes of data",
+                comdat.offset, contents.len()));
+    }
+    let mut image = vec![0u8; comdat.offset as usize];
+    image.extend(contents);
+    for bakpat in &module.bakpats {
+        if let Target::Comdat(name_index) = bakpat.target {
+            if module.name(name_index) == module.name(comdat.name_index) {
+                bakpat.apply(&mut image)?;
+            }
+        }
+    }
+    Ok(image)
+}
+
+#[cfg(test)]
+use ::objrec::*;
+
+#[test]
+fn patched_segment() {
+    let records = vec![
+        ObjectRecord { rtype: RecordType::LNAMES,
+            data: vec![0x04, 0x43, 0x4f, 0x44, 0x45] },
+        ObjectRecord { rtype: RecordType::SEGDEF,
+            data: vec![0x28, 0x08, 0x00, 0x01, 0x01, 0x01] },
+        ObjectRecord { rtype: RecordType::LEDATA,
+            data: vec![0x01, 0x00, 0x00, 0xe8, 0x00, 0x00] },
+        ObjectRecord { rtype: RecordType::BAKPAT,
+            data: vec![0x01, 0x01, 0x01, 0x00, 0x05, 0x00] },
+        ObjectRecord { rtype: RecordType::LEDATA,
+            data: vec![0x01, 0x03, 0x00, 0x90, 0xc3] },
+        ObjectRecord { rtype: RecordType::COMDAT,
+            data: vec![0x00, 0x11, 0x00, 0x00, 0x00, 0x00, 0x01, 0x10, 0x00] },
+        ObjectRecord { rtype: RecordType::NBKPAT,
+            data: vec![0x00, 0x01, 0x01, 0x00, 0x02, 0x00] }
+    ];
+    let module = read_module(records.into_iter());
+    let (image, errors) = segment_image(&module, 1);
+    assert_eq!(image, vec![0xe8, 0x05, 0x00, 0x90, 0xc3, 0x00, 0x00, 0x00]);
+    assert!(errors.is_empty());
+    assert_eq!(comdat_image(&module, &module.comdats[0]).unwrap(), vec![0x10, 0x02]);
+}
+
+#[test]
+fn image_bounds() {
+    let records = vec![
+        ObjectRecord { rtype: RecordType::SEGDEF32,
+            data: vec![0x28, 0x00, 0x00, 0x00, 0x10, 0x01, 0x01, 0x01] },
+        ObjectRecord { rtype: RecordType::LEDATA32,
+            data: vec![0x01, 0x00, 0x00, 0x00, 0x10, 0xaa] },
+        ObjectRecord { rtype: RecordType::COMDAT32,
+            data: vec![0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x70, 0x00, 0x00, 0x01, 0x10] }
+    ];
+    let module = read_module(records.into_iter());
+    let (image, errors) = segment_image(&module, 1);
+    assert!(image.is_empty());
+    assert_eq!(errors.len(), 2);
+    assert!(comdat_image(&module, &module.comdats[0]).is_err());
+    assert!(module.comdats[0].size().is_ok());
+}
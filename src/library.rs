@@ -0,0 +1,255 @@
+use ::objrec::*;
+use ::module::*;
+use ::link::*;
+
+/* a Microsoft library: a header record, object modules aligned to the
+ * page size, an end record and the dictionary */
+pub struct Library {
+    pub page_size: usize,
+    pub dictionary_offset: u32,
+    pub dictionary_blocks: u16,
+    pub flags: u8,
+    pub members: Vec<Member>
+}
+
+/* a module of a library and where it starts */
+pub struct Member {
+    pub offset: usize,
+    pub module: Module
+}
+
+/* the records of one module, up to and including MODEND; returns them and
+ * the number of bytes read, records of unknown types are left out */
+pub fn read_records(vec: &[u8]) -> Result<(Vec<ObjectRecord>, usize), String> {
+    let mut records = Vec::new();
+    let mut i = 0;
+    loop {
+        if vec.len() < i + 3 {
+            return Err(format!("record at offset {} is truncated", i));
+        }
+        let t = vec[i];
+        let length = read_u16(&vec[i+1..]) as usize;
+        if length == 0 || vec.len() < i + 3 + length {
+            return Err(format!("record at offset {} is truncated", i));
+        }
+        let record = &vec[i..i+3+length];
+        if !checksum_ok(record) {
+            return Err(format!("bad checksum at offset {}", i));
+        }
+        i += 3 + length;
+        if let Some(rtype) = record_type(t) {
+            records.push(ObjectRecord { rtype, data: record[3..record.len()-1].to_vec() });
+        }
+        if t == 0x8a || t == 0x8b {
+            return Ok((records, i));
+        }
+    }
+}
+
+pub fn read_library(vec: &[u8]) -> Result<Library, String> {
+    if vec.len() < 10 || vec[0] != 0xf0 {
+        return Err("not a library".to_string());
+    }
+    let page_size = read_u16(&vec[1..]) as usize + 3;
+    let mut library = Library {
+        page_size,
+        dictionary_offset: read_offset(&vec[3..], true),
+        dictionary_blocks: read_u16(&vec[7..]),
+        flags: vec[9],
+        members: Vec::new()
+    };
+    let mut offset = page_size;
+    while offset < vec.len() && vec[offset] != 0xf1 {
+        let (records, length) = read_records(&vec[offset..])
+            .map_err(|reason| format!("member at offset {}: {}", offset, reason))?;
+        library.members.push(Member { offset, module: read_module(records.into_iter()) });
+        offset = (offset + length).div_ceil(page_size) * page_size;
+    }
+    Ok(library)
+}
+
+impl Library {
+    /* the member whose public names define a name */
+    pub fn find(&self, name: &str) -> Option<&Member> {
+        self.members.iter().find(|m| m.module.publics.iter()
+                .any(|p| !p.local && p.name == name))
+    }
+}
+
+/* the name a library is searched by: no directory or extension, upper case */
+pub fn library_name(path: &str) -> String {
+    let file = path.rsplit(['/', '\\', ':']).next().unwrap_or(path);
+    let upper = file.to_uppercase();
+    match upper.strip_suffix(".LIB") {
+        Some(stem) => stem.to_string(),
+        None => upper
+    }
+}
+
+/* the order the linker searches libraries in: the ones given, then the
+ * default libraries of the modules as they are met, each only once */
+pub fn search_order(given: &[String], modules: &[Module]) -> Vec<String> {
+    let mut result: Vec<String> = Vec::new();
+    let defaults = modules.iter().flat_map(|m| m.default_libraries.iter());
+    for path in given.iter().chain(defaults) {
+        let name = library_name(path);
+        if !name.is_empty() && !result.contains(&name) {
+            result.push(name);
+        }
+    }
+    result
+}
+
+/* the externals the linked modules leave undefined, each with the library
+ * number and member that defines it; members are linked as they are
+ * pulled in, so their own externals are resolved and searched for too */
+pub fn pull_members<'a>(linker: &mut Linker<'a>, libraries: &[&'a Library])
+        -> Vec<(&'a str, Option<(usize, &'a Member)>)> {
+    let mut pending = linker.undefined();
+    let mut result = Vec::new();
+    let mut seen: Vec<&str> = Vec::new();
+    let mut i = 0;
+    while i < pending.len() {
+        let (module, external) = pending[i];
+        i += 1;
+        /* a member pulled in since may define it */
+        if seen.contains(&external.name.as_str()) ||
+                linker.resolve_before_search(module, external).definition
+                        != Definition::Undefined {
+            continue;
+        }
+        seen.push(&external.name);
+        let found = libraries.iter().enumerate()
+                .filter(|_| !external.local)
+                .filter_map(|(l, lib)| lib.find(&external.name).map(|m| (l, m)))
+                .next();
+        match (found, external.default) {
+            (Some((_, member)), _) => {
+                let m = linker.add(&member.module);
+                for external in &member.module.externals {
+                    if linker.resolve_before_search(m, external).definition
+                            == Definition::Undefined {
+                        pending.push((m, external));
+                    }
+                }
+            },
+            /* no library defines a lazy external, its default is searched for */
+            (None, Some((Weakness::Lazy, index))) => {
+                if let Some(d) = linker.module(module).external(index) {
+                    pending.push((module, d));
+                }
+            },
+            _ => {}
+        }
+        result.push((external.name.as_str(), found));
+    }
+    result
+}
+
+#[cfg(test)]
+fn record(t: u8, data: &[u8]) -> Vec<u8> {
+    let length = data.len() + 1;
+    let mut vec = vec![t, length as u8, (length >> 8) as u8];
+    vec.extend_from_slice(data);
+    vec.push(0);
+    vec
+}
+
+#[test]
+fn library() {
+    let mut lib = record(0xf0, &[0x00; 12]);
+    lib.extend(record(0x80, &[0x01, 0x41]));
+    lib.extend(record(0x90, &[0x00, 0x01, 0x03, 0x46, 0x4f, 0x4f, 0x00, 0x00, 0x00]));
+    lib.extend(record(0x8a, &[0x00]));
+    lib.resize(48, 0);
+    lib.extend(record(0x80, &[0x01, 0x42]));
+    lib.extend(record(0xf4, &[0x00]));
+    lib.extend(record(0x8a, &[0x00]));
+    lib.resize(64, 0);
+    lib.extend(record(0xf1, &[0x00]));
+    let library = read_library(&lib).unwrap();
+    assert_eq!(library.page_size, 16);
+    assert_eq!(library.members.len(), 2);
+    assert_eq!(library.members[1].offset, 48);
+    assert_eq!(library.find("FOO").unwrap().module.name, "A");
+    assert!(library.find("BAR").is_none());
+    assert!(read_library(&lib[..56]).is_err());
+}
+
+#[test]
+fn library_search_order() {
+    assert_eq!(library_name("C:\\MSC\\LIB\\slibce.lib"), "SLIBCE");
+    assert_eq!(library_name("lib/os2"), "OS2");
+    let mut a = Module::new();
+    a.default_libraries = vec!["SLIBCE".to_string(), "OLDNAMES".to_string()];
+    let mut b = Module::new();
+    b.default_libraries = vec!["os2.lib".to_string(), "slibce".to_string()];
+    assert_eq!(search_order(&["mylib.lib".to_string()], &[a, b]),
+            vec!["MYLIB", "SLIBCE", "OLDNAMES", "OS2"]);
+}
+
+#[test]
+fn pulled_members() {
+    let mut lib = record(0xf0, &[0x00; 12]);
+    let members: [&[u8]; 2] = [b"\x01A\x03FOO", b"\x01B\x03BAZ"];
+    for member in &members {
+        lib.extend(record(0x80, &member[..2]));
+        let mut pubdef = vec![0x00, 0x01];
+        pubdef.extend_from_slice(&member[2..]);
+        pubdef.extend_from_slice(&[0x00, 0x00, 0x00]);
+        lib.extend(record(0x90, &pubdef));
+        if member[1] == b'A' {
+            lib.extend(record(0x8c, b"\x03BAZ\x00"));
+        }
+        lib.extend(record(0x8a, &[0x00]));
+        let length = lib.len().div_ceil(16) * 16;
+        lib.resize(length, 0);
+    }
+    lib.extend(record(0xf1, &[0x00]));
+    let library = read_library(&lib).unwrap();
+    let main = vec![
+        ObjectRecord { rtype: RecordType::EXTDEF, data: b"\x03FOO\x00\x03BAR\x00".to_vec() }
+    ];
+    let modules = vec![read_module(main.into_iter())];
+    let mut linker = Linker::new(&modules);
+    let pulled: Vec<(&str, Option<(usize, &str)>)> = pull_members(&mut linker, &[&library])
+            .into_iter()
+            .map(|(name, found)| (name, found.map(|(l, m)| (l, m.module.name.as_str()))))
+            .collect();
+    assert_eq!(pulled, vec![("FOO", Some((0, "A"))), ("BAR", None), ("BAZ", Some((0, "B")))]);
+    assert_eq!(linker.define(0, "BAZ", false), Definition::Public(2));
+}
+
+#[test]
+fn lazy_externals() {
+    let mut lib = record(0xf0, &[0x00; 12]);
+    for member in [b"\x01A\x03FOO", b"\x01B\x03DEF"] {
+        lib.extend(record(0x80, &member[..2]));
+        let mut pubdef = vec![0x00, 0x01];
+        pubdef.extend_from_slice(&member[2..]);
+        pubdef.extend_from_slice(&[0x00, 0x00, 0x00]);
+        lib.extend(record(0x90, &pubdef));
+        lib.extend(record(0x8a, &[0x00]));
+        let length = lib.len().div_ceil(16) * 16;
+        lib.resize(length, 0);
+    }
+    lib.extend(record(0xf1, &[0x00]));
+    let library = read_library(&lib).unwrap();
+    /* FOO and BAR are lazy with the default DEF, a library defines FOO */
+    let main = vec![
+        ObjectRecord { rtype: RecordType::EXTDEF,
+            data: b"\x03FOO\x00\x03BAR\x00\x03DEF\x00".to_vec() },
+        ObjectRecord { rtype: RecordType::COMENT, data: vec![0x80, 0xa9, 0x01, 0x03, 0x02, 0x03] }
+    ];
+    let modules = vec![read_module(main.into_iter())];
+    let mut linker = Linker::new(&modules);
+    let pulled: Vec<(&str, Option<&str>)> = pull_members(&mut linker, &[&library])
+            .into_iter()
+            .map(|(name, found)| (name, found.map(|(_, m)| m.module.name.as_str())))
+            .collect();
+    assert_eq!(pulled, vec![("FOO", Some("A")), ("BAR", None), ("DEF", Some("B"))]);
+    let resolutions = linker.resolutions();
+    assert_eq!((resolutions[0].definition, resolutions[0].default), (Definition::Public(1), None));
+    assert_eq!(resolutions[1].definition, Definition::Public(2));
+    assert_eq!(resolutions[1].default, Some((Weakness::Lazy, "DEF")));
+}
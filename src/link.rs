@@ -0,0 +1,221 @@
+use std::collections::HashMap;
+
+use ::module::*;
+
+/* what an external resolves to, module numbers index the linked modules */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Definition {
+    Public(usize),
+    Comdat(usize),
+    /* a communal nothing else defines is allocated by the linker */
+    Communal,
+    Undefined
+}
+
+pub struct Resolution<'a> {
+    pub module: usize,
+    pub external: &'a External,
+    pub definition: Definition,
+    /* the substitute name when resolved through an ALIAS */
+    pub alias: Option<&'a str>,
+    /* the default external when resolved through a WKEXT or LZEXT */
+    pub default: Option<(Weakness, &'a str)>
+}
+
+/* the public names and aliases of a set of modules */
+pub struct Linker<'a> {
+    modules: Vec<&'a Module>,
+    publics: HashMap<&'a str, Vec<usize>>,
+    comdats: HashMap<&'a str, usize>,
+    aliases: HashMap<&'a str, &'a str>
+}
+
+impl <'a> Linker<'a> {
+
+    pub fn new(modules: &'a [Module]) -> Linker<'a> {
+        let mut linker = Linker {
+            modules: Vec::new(),
+            publics: HashMap::new(),
+            comdats: HashMap::new(),
+            aliases: HashMap::new()
+        };
+        for module in modules {
+            linker.add(module);
+        }
+        linker
+    }
+
+    /* links one more module, such as a library member; returns its number */
+    pub fn add(&mut self, module: &'a Module) -> usize {
+        let i = self.modules.len();
+        for public in module.publics.iter().filter(|p| !p.local) {
+            self.publics.entry(public.name.as_str()).or_default().push(i);
+        }
+        for comdat in module.comdats.iter().filter(|c| !c.local) {
+            self.comdats.entry(module.name(comdat.name_index)).or_insert(i);
+        }
+        for (alias, substitute) in &module.aliases {
+            self.aliases.entry(alias.as_str()).or_insert(substitute.as_str());
+        }
+        self.modules.push(module);
+        i
+    }
+
+    /* the definition of a name as seen from a module, local names are
+     * only defined in their own module */
+    pub fn define(&self, module: usize, name: &str, local: bool) -> Definition {
+        if local {
+            let m = self.modules[module];
+            if m.publics.iter().any(|p| p.local && p.name == name) {
+                return Definition::Public(module);
+            }
+            if m.comdats.iter().any(|c| c.local && m.name(c.name_index) == name) {
+                return Definition::Comdat(module);
+            }
+            return Definition::Undefined;
+        }
+        if let Some(modules) = self.publics.get(name) {
+            return Definition::Public(modules[0]);
+        }
+        if let Some(&m) = self.comdats.get(name) {
+            return Definition::Comdat(m);
+        }
+        Definition::Undefined
+    }
+
+    pub fn module(&self, module: usize) -> &'a Module {
+        self.modules[module]
+    }
+
+    pub fn resolve(&self, module: usize, external: &'a External) -> Resolution<'a> {
+        self.resolve_default(module, external, true)
+    }
+
+    /* the resolution while the libraries are searched: a lazy external
+     * only falls back to its default when no library defines it */
+    pub fn resolve_before_search(&self, module: usize, external: &'a External)
+            -> Resolution<'a> {
+        self.resolve_default(module, external, false)
+    }
+
+    fn resolve_default(&self, module: usize, external: &'a External, lazy: bool)
+            -> Resolution<'a> {
+        let mut definition = self.define(module, &external.name, external.local);
+        let mut alias = None;
+        if definition == Definition::Undefined && !external.local {
+            if let Some(&substitute) = self.aliases.get(external.name.as_str()) {
+                definition = self.define(module, substitute, false);
+                alias = Some(substitute);
+            }
+        }
+        let mut default = None;
+        if definition == Definition::Undefined {
+            if let Some((weakness, index)) = external.default
+                    .filter(|&(weakness, _)| lazy || weakness != Weakness::Lazy) {
+                if let Some(d) = self.modules[module].external(index) {
+                    definition = self.define(module, &d.name, d.local);
+                    default = Some((weakness, d.name.as_str()));
+                }
+            }
+        }
+        if definition == Definition::Undefined {
+            if let ExternalKind::Communal(_) = external.kind {
+                definition = Definition::Communal;
+            }
+        }
+        Resolution { module, external, definition, alias, default }
+    }
+
+    /* the externals of every module nothing defines before the libraries
+     * are searched */
+    pub fn undefined(&self) -> Vec<(usize, &'a External)> {
+        let mut result = Vec::new();
+        for (i, module) in self.modules.iter().enumerate() {
+            for external in &module.externals {
+                if self.resolve_before_search(i, external).definition == Definition::Undefined {
+                    result.push((i, external));
+                }
+            }
+        }
+        result
+    }
+
+    /* the resolution of every external of every module */
+    pub fn resolutions(&self) -> Vec<Resolution<'a>> {
+        let mut result = Vec::new();
+        for (i, module) in self.modules.iter().enumerate() {
+            for external in &module.externals {
+                result.push(self.resolve(i, external));
+            }
+        }
+        result
+    }
+
+    /* public names defined by more than one module */
+    pub fn duplicates(&self) -> Vec<(&'a str, &[usize])> {
+        let mut result: Vec<(&str, &[usize])> = self.publics.iter()
+                .filter(|&(_, modules)| modules.len() > 1)
+                .map(|(&name, modules)| (name, modules.as_slice()))
+                .collect();
+        result.sort();
+        result
+    }
+
+}
+
+#[cfg(test)]
+use ::objrec::*;
+
+#[cfg(test)]
+fn test_modules() -> Vec<Module> {
+    let main = vec![
+        ObjectRecord { rtype: RecordType::LNAMES, data: vec![0x01, 0x46] },
+        ObjectRecord { rtype: RecordType::EXTDEF,
+            data: vec![0x01, 0x41, 0x00, 0x01, 0x42, 0x00, 0x01, 0x43, 0x00] },
+        ObjectRecord { rtype: RecordType::LEXTDEF, data: vec![0x01, 0x4c, 0x00] },
+        ObjectRecord { rtype: RecordType::CEXTDEF, data: vec![0x01, 0x00] },
+        ObjectRecord { rtype: RecordType::COMDEF,
+            data: vec![0x01, 0x44, 0x00, 0x62, 0x02] },
+        ObjectRecord { rtype: RecordType::ALIAS,
+            data: vec![0x01, 0x43, 0x01, 0x45] },
+        ObjectRecord { rtype: RecordType::LPUBDEF,
+            data: vec![0x00, 0x01, 0x01, 0x4c, 0x00, 0x00, 0x00] },
+        ObjectRecord { rtype: RecordType::EXTDEF,
+            data: vec![0x01, 0x57, 0x00, 0x01, 0x5a, 0x00] },
+        ObjectRecord { rtype: RecordType::COMENT, data: vec![0x80, 0xa8, 0x07, 0x01] },
+        ObjectRecord { rtype: RecordType::COMENT, data: vec![0x80, 0xa9, 0x08, 0x02] }
+    ];
+    let other = vec![
+        ObjectRecord { rtype: RecordType::LNAMES, data: vec![0x01, 0x46] },
+        ObjectRecord { rtype: RecordType::PUBDEF,
+            data: vec![0x00, 0x01, 0x01, 0x41, 0x00, 0x00, 0x00,
+                    0x01, 0x45, 0x02, 0x00, 0x00] },
+        ObjectRecord { rtype: RecordType::LPUBDEF,
+            data: vec![0x00, 0x01, 0x01, 0x42, 0x04, 0x00, 0x00] },
+        ObjectRecord { rtype: RecordType::COMDAT,
+            data: vec![0x00, 0x11, 0x00, 0x00, 0x00, 0x00, 0x01, 0x90] }
+    ];
+    vec![read_module(main.into_iter()), read_module(other.into_iter())]
+}
+
+#[test]
+fn resolve() {
+    let modules = test_modules();
+    let linker = Linker::new(&modules);
+    let resolutions = linker.resolutions();
+    let found: Vec<(&str, Definition, Option<&str>)> = resolutions.iter()
+            .map(|r| (r.external.name.as_str(), r.definition, r.alias))
+            .collect();
+    assert_eq!(found, vec![
+            ("A", Definition::Public(1), None),
+            ("B", Definition::Undefined, None),
+            ("C", Definition::Public(1), Some("E")),
+            ("L", Definition::Public(0), None),
+            ("F", Definition::Comdat(1), None),
+            ("D", Definition::Communal, None),
+            ("W", Definition::Public(1), None),
+            ("Z", Definition::Undefined, None)]);
+    assert_eq!(resolutions[6].default, Some((Weakness::Weak, "A")));
+    assert_eq!(resolutions[7].default, Some((Weakness::Lazy, "B")));
+    assert!(linker.duplicates().is_empty());
+}
@@ -8,44 +8,965 @@ pub mod objrec;
 
 pub mod typdef;
 
-fn main() {
-    /* get arguments */
-    let args: Vec<String> = env::args().collect();
-    println!("args: {:?}", args);
-    if args.len() != 2 {
-        println!("usage {} <filename>", &args[0]);
-        std::process::exit(1);
-    }
-    let path = Path::new(&args[1]);
+pub mod module;
+
+pub mod addr2line;
+
+pub mod typetable;
+
+pub mod comdat;
+
+pub mod link;
+
+pub mod bakpat;
+
+pub mod image;
+
+pub mod absrec;
+
+pub mod coment;
+
+pub mod library;
+
+pub mod fixup;
+
+pub mod codeview;
+
+pub mod borland;
+
+pub mod block;
+
+pub mod regint;
+
+pub mod omf51;
+
+pub mod print51;
+
+pub mod omf85;
+
+pub mod print85;
+
+pub mod omf96;
+
+pub mod print96;
+
+pub mod detect;
+
+fn usage(program: &str) -> ! {
+    println!("usage {} <filename>", program);
+    println!("      {} addr2line <filename> <segment> <offset>", program);
+    println!("      {} line2addr <filename> <line> [<source>]", program);
+    println!("      {} types <filename>", program);
+    println!("      {} symbols <filename>", program);
+    println!("      {} comdats <filename>...", program);
+    println!("      {} link <filename>...", program);
+    println!("      {} image <filename> <segment or COMDAT>", program);
+    println!("      {} summary <filename>", program);
+    println!("      {} imports <filename>...", program);
+    println!("      {} exports <filename>...", program);
+    println!("      {} libraries <filename>...", program);
+    println!("      {} codeview <filename>", program);
+    println!("      {} borland <filename>", program);
+    println!("      {} scopes <filename>", program);
+    println!("      {} locals <filename> <segment> <offset>", program);
+    println!("      {} omf51 <filename>", program);
+    println!("      {} omf85 <filename>", program);
+    println!("      {} hex <filename> <hex filename>", program);
+    println!("      {} omf96 <filename>", program);
+    println!("      {} detect <filename>...", program);
+    std::process::exit(1);
+}
+
+fn open_file(filename: &str) -> File {
+    let path = Path::new(filename);
     let display = path.display();
 
-    /* open the file */
-    let file = match File::open(&path) {
+    match File::open(path) {
         Err(_) => panic!("couldn't open {:?}", display),
         Ok(file) => file,
-    };
+    }
+}
+
+fn open_obj_reader(filename: &str) -> objrec::ObjReader {
+    objrec::make_obj_reader(open_file(filename)).unwrap()
+}
 
-    let obj_reader = objrec::make_obj_reader(file).unwrap();
+/* the module of an object file; the record the reader stopped at, if any,
+ * is one of its errors */
+fn read_obj_module(filename: &str) -> module::Module {
+    let mut reader = open_obj_reader(filename);
+    let mut module = module::read_module(reader.by_ref());
+    if let Some(reason) = reader.error() {
+        module.errors.push(reason.to_string());
+    }
+    module
+}
+
+/* numbers are decimal, or hexadecimal with a 0x prefix or an H suffix */
+fn parse_number(s: &str) -> Option<u16> {
+    let lower = s.to_lowercase();
+    if let Some(hex) = lower.strip_prefix("0x") {
+        u16::from_str_radix(hex, 16).ok()
+    } else if let Some(hex) = lower.strip_suffix('h') {
+        u16::from_str_radix(hex, 16).ok()
+    } else {
+        lower.parse().ok()
+    }
+}
+
+fn dump(program: &str, filename: &str) {
+    let contents = match std::fs::read(filename) {
+        Ok(contents) => contents,
+        Err(_) => panic!("couldn't open {:?}", filename)
+    };
+    let detection = detect::detect(&contents);
+    println!("{}", detection);
+    let args = [filename.to_string()];
+    match detection.format {
+        detect::Format::Omf51 => return omf51(program, &args),
+        detect::Format::Omf85 => return omf85(program, &args),
+        detect::Format::Omf96 => return omf96(program, &args),
+        detect::Format::Relocatable |
+        detect::Format::Absolute if detection.confidence > detect::Confidence::Low => {},
+        detect::Format::Relocatable |
+        detect::Format::Absolute => {
+            println!("skipped: not sure enough that this is an object module");
+            return;
+        },
+        detect::Format::MicrosoftLibrary |
+        detect::Format::IntelLibrary => {
+            println!("skipped: libraries are listed with {} libraries", program);
+            return;
+        },
+        detect::Format::Unknown => {
+            println!("skipped: no records to dump");
+            return;
+        }
+    }
+    let mut obj_reader = open_obj_reader(filename);
+    let mut module = module::Module::new();
 
     /* read */
-    for orec in obj_reader {
+    for orec in obj_reader.by_ref() {
+        /* LNAMES and LLNAMES continue the name table, which starts at 1 */
+        let first_name = module.names.len() + 1;
+        module.add(&orec);
         match orec.rtype {
-            objrec::RecordType::REGINT => recprint::regint(orec),
+            objrec::RecordType::REGINT => recprint::regint(orec, &module),
             objrec::RecordType::BLKDEF => recprint::blkdef(orec),
             objrec::RecordType::BLKEND => recprint::blkend(orec),
             objrec::RecordType::DEBSYM => recprint::debsym(orec),
             objrec::RecordType::THEADR => recprint::theadr(orec),
-            objrec::RecordType::COMENT => recprint::coment(orec),
-            objrec::RecordType::MODEND => recprint::modend(orec),
+            objrec::RecordType::COMENT => recprint::coment(orec, &module),
+            objrec::RecordType::MODEND |
+            objrec::RecordType::MODEND32 => recprint::modend(orec, &module),
             objrec::RecordType::EXTDEF => recprint::extdef(orec),
             objrec::RecordType::TYPDEF => recprint::typdef(orec),
-            objrec::RecordType::PUBDEF => recprint::pubdef(orec),
+            objrec::RecordType::PUBDEF |
+            objrec::RecordType::PUBDEF32 => recprint::pubdef(orec),
             objrec::RecordType::LINNUM => recprint::linnum(orec),
-            objrec::RecordType::LNAMES => recprint::lnames(orec),
-            objrec::RecordType::SEGDEF => recprint::segdef(orec),
+            objrec::RecordType::LNAMES => recprint::lnames(orec, first_name),
+            objrec::RecordType::SEGDEF |
+            objrec::RecordType::SEGDEF32 => recprint::segdef(orec),
             objrec::RecordType::GRPDEF => recprint::grpdef(orec),
-            objrec::RecordType::FIXUPP => recprint::fixupp(orec),
-            objrec::RecordType::LEDATA => recprint::ledata(orec)
+            objrec::RecordType::FIXUPP |
+            objrec::RecordType::FIXUPP32 => recprint::fixupp(orec, &module),
+            objrec::RecordType::LEDATA |
+            objrec::RecordType::LEDATA32 => recprint::ledata(orec),
+            objrec::RecordType::LIDATA |
+            objrec::RecordType::LIDATA32 => recprint::lidata(orec),
+            objrec::RecordType::COMDEF => recprint::comdef(orec),
+            objrec::RecordType::LCOMDEF => recprint::lcomdef(orec),
+            objrec::RecordType::COMDAT |
+            objrec::RecordType::COMDAT32 => recprint::comdat(orec, &module),
+            objrec::RecordType::LINSYM |
+            objrec::RecordType::LINSYM32 => recprint::linsym(orec, &module),
+            objrec::RecordType::LEXTDEF => recprint::lextdef(orec),
+            objrec::RecordType::LPUBDEF |
+            objrec::RecordType::LPUBDEF32 => recprint::lpubdef(orec),
+            objrec::RecordType::CEXTDEF => recprint::cextdef(orec, &module),
+            objrec::RecordType::ALIAS => recprint::alias(orec),
+            objrec::RecordType::BAKPAT |
+            objrec::RecordType::BAKPAT32 => recprint::bakpat(orec),
+            objrec::RecordType::NBKPAT |
+            objrec::RecordType::NBKPAT32 => recprint::nbkpat(orec, &module),
+            objrec::RecordType::LLNAMES => recprint::llnames(orec, first_name),
+            objrec::RecordType::VERNUM => recprint::vernum(orec),
+            objrec::RecordType::VENDEXT => recprint::vendext(orec),
+            objrec::RecordType::RHEADR => recprint::rheadr(orec),
+            objrec::RecordType::OVLDEF => recprint::ovldef(orec),
+            objrec::RecordType::ENDREC => recprint::endrec(orec),
+            objrec::RecordType::LHEADR => recprint::lheadr(orec),
+            objrec::RecordType::PEDATA => recprint::pedata(orec),
+            objrec::RecordType::PIDATA => recprint::pidata(orec),
+            objrec::RecordType::LOCSYM => recprint::locsym(orec),
+            objrec::RecordType::LIBHED => recprint::libhed(orec)
+        }
+    }
+    if let Some(reason) = obj_reader.error() {
+        println!("stopped: {}", reason);
+    }
+}
+
+fn addr2line(program: &str, args: &[String]) {
+    if args.len() != 3 {
+        usage(program);
+    }
+    let offset = match parse_number(&args[2]) {
+        Some(offset) => offset,
+        None => usage(program)
+    };
+    let module = read_obj_module(&args[0]);
+    match addr2line::addr2line(&module, &args[1], offset) {
+        Some(location) => println!("{}:{}", location.source, location.line),
+        None => println!("??:0")
+    }
+}
+
+fn line2addr(program: &str, args: &[String]) {
+    if args.len() != 2 && args.len() != 3 {
+        usage(program);
+    }
+    let line = match parse_number(&args[1]) {
+        Some(line) => line,
+        None => usage(program)
+    };
+    let source = args.get(2).map(|s| s.as_str());
+    let module = read_obj_module(&args[0]);
+    for range in addr2line::line2addr(&module, source, line) {
+        println!("{}:{} {}:{:04x}-{:04x}", range.source, line,
+                range.segment, range.start, range.end);
+    }
+}
+
+fn print_symbol_types(title: &str, module: &module::Module,
+        symbols: &[(&str, usize)]) {
+    if symbols.is_empty() {
+        return;
+    }
+    println!("{}:", title);
+    for &(name, type_index) in symbols {
+        if type_index > 0 {
+            println!("{}: {}", name, module.types.declaration(type_index));
+        }
+    }
+    println!();
+}
+
+fn types(program: &str, args: &[String]) {
+    if args.len() != 1 {
+        usage(program);
+    }
+    let module = read_obj_module(&args[0]);
+    println!("Types:");
+    for index in 1..=module.types.types.len() {
+        let (ref name, ref typdef) = module.types.types[index - 1];
+        let declaration = match *typdef {
+            Ok(_) => module.types.declaration(index),
+            Err(ref reason) => format!("undecodable TYPDEF: {}", reason)
+        };
+        if name.is_empty() {
+            println!("T{}: {}", index, declaration);
+        } else {
+            println!("T{} {}: {}", index, name, declaration);
+        }
+    }
+    println!();
+    let publics: Vec<(&str, usize)> = module.publics.iter()
+            .map(|p| (p.name.as_str(), p.type_index)).collect();
+    let externals: Vec<(&str, usize)> = module.externals.iter()
+            .map(|e| (e.name.as_str(), e.type_index)).collect();
+    let debug_symbols: Vec<(&str, usize)> = module.debug_symbols.iter()
+            .map(|d| (d.name.as_str(), d.type_index)).collect();
+    print_symbol_types("Publics", &module, &publics);
+    print_symbol_types("Externals", &module, &externals);
+    print_symbol_types("Debug symbols", &module, &debug_symbols);
+}
+
+fn symbols(program: &str, args: &[String]) {
+    if args.len() != 1 {
+        usage(program);
+    }
+    let module = read_obj_module(&args[0]);
+    println!("Publics:");
+    for public in &module.publics {
+        let scope = if public.local { "local " } else { "" };
+        print!("{}: {}public, {}:{:04x}", public.name, scope,
+                module.segment_name(public.segment), public.offset);
+        if public.type_index > 0 {
+            print!(", type: {}", module.types.declaration(public.type_index));
+        }
+        println!();
+    }
+    println!();
+    println!("Externals:");
+    for (i, external) in module.externals.iter().enumerate() {
+        let scope = if external.local { "local " } else { "" };
+        print!("{}: {}: ", i + 1, external.name);
+        match external.kind {
+            module::ExternalKind::Extern => print!("{}external", scope),
+            module::ExternalKind::Communal(communal) =>
+                print!("{}communal, {}, size: {}", scope, communal, communal.size()),
+            module::ExternalKind::Comdat => print!("COMDAT external")
+        }
+        if external.type_index > 0 {
+            print!(", type: {}", module.types.declaration(external.type_index));
+        }
+        if let Some((weakness, index)) = external.default {
+            let name = module.external(index).map_or("?", |d| d.name.as_str());
+            match weakness {
+                module::Weakness::Weak => print!(", weak, default: {}", name),
+                module::Weakness::Lazy => print!(", lazy, default: {}", name)
+            }
+        }
+        println!();
+    }
+    println!();
+    if !module.aliases.is_empty() {
+        println!("Aliases:");
+        for (alias, substitute) in &module.aliases {
+            println!("{}: {}", alias, substitute);
+        }
+        println!();
+    }
+}
+
+/* resolves the externals of several modules against each other */
+fn link(program: &str, args: &[String]) {
+    if args.is_empty() {
+        usage(program);
+    }
+    let modules: Vec<module::Module> = args.iter()
+            .map(|filename| read_obj_module(filename))
+            .collect();
+    let linker = link::Linker::new(&modules);
+    for resolution in linker.resolutions() {
+        print!("{}: {}: ", args[resolution.module], resolution.external.name);
+        match resolution.definition {
+            link::Definition::Public(m) => print!("public in {}", args[m]),
+            link::Definition::Comdat(m) => print!("COMDAT in {}", args[m]),
+            link::Definition::Communal => print!("communal"),
+            link::Definition::Undefined => print!("undefined")
+        }
+        if let Some(substitute) = resolution.alias {
+            print!(" (alias of {})", substitute);
+        }
+        if let Some((_, default)) = resolution.default {
+            print!(" (default {})", default);
+        }
+        println!();
+    }
+    for (name, defining) in linker.duplicates() {
+        let files: Vec<&str> = defining.iter().map(|&m| args[m].as_str()).collect();
+        println!("{}: defined in {}", name, files.join(", "));
+    }
+}
+
+fn summary(program: &str, args: &[String]) {
+    if args.len() != 1 {
+        usage(program);
+    }
+    let module = read_obj_module(&args[0]);
+    println!("Module: {}", module.name);
+    let kind = match module.header {
+        Some(objrec::RecordType::LHEADR) => "linked (L-module)",
+        Some(objrec::RecordType::RHEADR) => "R-module",
+        _ => "translator (T-module)"
+    };
+    if module.physical_data.is_empty() {
+        println!("Kind: {}", kind);
+    } else {
+        println!("Kind: {}, located", kind);
+    }
+    if let Some(ref version) = module.version {
+        println!("OMF version: {}", version);
+    }
+    for &(vendor, ref extension) in &module.vendor_extensions {
+        println!("Vendor extension: vendor {}, {} bytes", vendor, extension.len());
+    }
+    for register in regint::entry_state(&module) {
+        println!("Initial {}: {}", register.id,
+                regint::value_to_string(&module, &register.value));
+    }
+    println!("Names: {}", module.names.len());
+    println!("Segments: {}", module.segments.len());
+    for i in 1..=module.segments.len() {
+        let segment = &module.segments[i - 1];
+        println!("  {}: {}, class {}, length {}", i, module.segment_name(i),
+                module.name(segment.class_index), segment.length);
+    }
+    println!("Types: {}", module.types.types.len());
+    println!("Publics: {}", module.publics.len());
+    println!("Externals: {}", module.externals.len());
+    println!("COMDATs: {}", module.comdats.len());
+    if !module.overlays.is_empty() {
+        println!("Overlays: {}", module.overlays.len());
+    }
+    if !module.physical_data.is_empty() {
+        println!("Physical data records: {}", module.physical_data.len());
+    }
+    println!("Line numbers: {}", module.lines.len());
+    if !module.sources.is_empty() {
+        println!("Sources: {}", module.sources.join(", "));
+    }
+    for reason in &module.errors {
+        println!("bad record: {}", reason);
+    }
+}
+
+/* the DLL imports of IMPDEF comments, in module definition file syntax */
+fn imports(program: &str, args: &[String]) {
+    if args.is_empty() {
+        usage(program);
+    }
+    for filename in args {
+        let module = read_obj_module(filename);
+        for import in &module.imports {
+            print!("{}: {} = {}.", filename, import.internal, import.module);
+            match import.entry {
+                coment::Entry::Ordinal(ordinal) => println!("{}", ordinal),
+                coment::Entry::Name(ref name) if name.is_empty() =>
+                    println!("{}", import.internal),
+                coment::Entry::Name(ref name) => println!("{}", name)
+            }
+        }
+    }
+}
+
+/* the DLL exports of EXPDEF comments, in module definition file syntax */
+fn exports(program: &str, args: &[String]) {
+    if args.is_empty() {
+        usage(program);
+    }
+    for filename in args {
+        let module = read_obj_module(filename);
+        for export in &module.exports {
+            print!("{}: {}", filename, export.exported);
+            if !export.internal.is_empty() {
+                print!(" = {}", export.internal);
+            }
+            if let Some(ordinal) = export.ordinal {
+                print!(" @{}", ordinal);
+            }
+            if export.resident {
+                print!(" RESIDENTNAME");
+            }
+            if export.no_data {
+                print!(" NODATA");
+            }
+            if export.parameters > 0 {
+                print!(" {}", export.parameters);
+            }
+            println!();
+        }
+    }
+}
+
+/* the default libraries of a set of objects, and the library members that
+ * define their undefined externals when libraries are given too */
+fn libraries(program: &str, args: &[String]) {
+    if args.is_empty() {
+        usage(program);
+    }
+    let mut objects = Vec::new();
+    let mut modules = Vec::new();
+    let mut libraries = Vec::new();
+    for filename in args {
+        let contents = match std::fs::read(filename) {
+            Ok(contents) => contents,
+            Err(_) => panic!("couldn't open {:?}", filename)
+        };
+        let detection = detect::detect(&contents);
+        match detection.format {
+            detect::Format::MicrosoftLibrary => match library::read_library(&contents) {
+                Ok(lib) => libraries.push((filename.clone(), lib)),
+                Err(reason) => panic!("{}: {}", filename, reason)
+            },
+            detect::Format::Relocatable |
+            detect::Format::Absolute if detection.confidence > detect::Confidence::Low => {
+                objects.push(filename.as_str());
+                modules.push(read_obj_module(filename));
+            },
+            _ => println!("{}: skipped, {}", filename, detection)
+        }
+    }
+    for (filename, module) in objects.iter().zip(&modules) {
+        println!("{}: {}", filename, module.default_libraries.join(", "));
+    }
+
+    let given: Vec<String> = libraries.iter().map(|l| l.0.clone()).collect();
+    let order = library::search_order(&given, &modules);
+    println!();
+    println!("Search order:");
+    let mut searched = Vec::new();
+    for name in &order {
+        match libraries.iter().find(|l| &library::library_name(&l.0) == name) {
+            Some(lib) => {
+                println!("{}: {}", name, lib.0);
+                searched.push(lib);
+            },
+            None => println!("{}: not given", name)
+        }
+    }
+
+    let mut linker = link::Linker::new(&modules);
+    let searched_libraries: Vec<&library::Library> = searched.iter().map(|l| &l.1).collect();
+    println!();
+    println!("Undefined externals:");
+    for (name, found) in library::pull_members(&mut linker, &searched_libraries) {
+        match found {
+            Some((l, member)) =>
+                println!("{}: {} in {}", name, member.module.name, searched[l].0),
+            None => println!("{}: undefined", name)
+        }
+    }
+}
+
+/* the CodeView types and symbols of the $$TYPES and $$SYMBOLS segments */
+fn codeview(program: &str, args: &[String]) {
+    if args.len() != 1 {
+        usage(program);
+    }
+    let module = read_obj_module(&args[0]);
+    match module.debug_style {
+        Some((version, ref style)) => println!("Debug style: {} version {}", style, version),
+        None => println!("Debug style: not given")
+    }
+
+    let types = module.find_segment("$$TYPES");
+    if types > 0 {
+        println!();
+        println!("Types:");
+        match codeview::read_types(&image::segment_image(&module, types).0) {
+            Ok(types) => {
+                for (index, t) in types {
+                    println!("{}: {}", codeview::type_name(index), t);
+                }
+            },
+            Err(reason) => println!("undecodable $$TYPES: {}", reason)
+        }
+    }
+
+    let symbols = module.find_segment("$$SYMBOLS");
+    if symbols == 0 {
+        return;
+    }
+    println!();
+    println!("Symbols:");
+    let decoded = match codeview::read_symbols(&image::segment_image(&module, symbols).0) {
+        Ok(decoded) => decoded,
+        Err(reason) => {
+            println!("undecodable $$SYMBOLS: {}", reason);
+            return;
+        }
+    };
+    let address = |a: &codeview::Address| {
+        let (_, name, offset) = codeview::resolve_address(&module, symbols, a);
+        format!("{}:{:04x}", name, offset)
+    };
+    let mut depth = 0;
+    for (_, symbol) in decoded {
+        if symbol == codeview::CvSymbol::End {
+            depth = std::cmp::max(depth, 1) - 1;
+        }
+        print!("{:indent$}", "", indent = 2 * depth);
+        match symbol {
+            codeview::CvSymbol::Compile { machine, ref version } =>
+                println!("compiler: {}, machine {:02x}", version, machine),
+            codeview::CvSymbol::Register { type_index, register, ref name } =>
+                println!("{}: register {}, {}", name, register,
+                        codeview::type_name(type_index)),
+            codeview::CvSymbol::Constant { type_index, value, ref name } =>
+                println!("{}: constant {}, {}", name, value,
+                        codeview::type_name(type_index)),
+            codeview::CvSymbol::Udt { type_index, ref name } =>
+                println!("{}: typedef {}", name, codeview::type_name(type_index)),
+            codeview::CvSymbol::End => println!("end"),
+            codeview::CvSymbol::ObjName { ref name, .. } => println!("object: {}", name),
+            codeview::CvSymbol::BpRelative { offset, type_index, ref name } =>
+                println!("{}: [bp{:+}], {}", name, offset,
+                        codeview::type_name(type_index)),
+            codeview::CvSymbol::Data { global, address: ref a, type_index, ref name } =>
+                println!("{}: {} data {}, {}", name,
+                        if global { "global" } else { "local" }, address(a),
+                        codeview::type_name(type_index)),
+            codeview::CvSymbol::Public { address: ref a, type_index, ref name } =>
+                println!("{}: public {}, {}", name, address(a),
+                        codeview::type_name(type_index)),
+            codeview::CvSymbol::Procedure { global, address: ref a, length,
+                    type_index, ref name, .. } => {
+                println!("{}: {} procedure {}, length {}, {}", name,
+                        if global { "global" } else { "local" }, address(a), length,
+                        codeview::type_name(type_index));
+                let (segment, _, start) = codeview::resolve_address(&module, symbols, a);
+                for line in module.lines.iter().filter(|l| l.segment == segment &&
+                        segment > 0 && l.offset as u32 >= start &&
+                        (l.offset as u32) < start + length) {
+                    println!("{:indent$}line {}: {:04x}", "", line.line, line.offset,
+                            indent = 2 * depth + 2);
+                }
+                depth += 1;
+            },
+            codeview::CvSymbol::Block { address: ref a, length, ref name } => {
+                println!("block {} {}, length {}", name, address(a), length);
+                depth += 1;
+            },
+            codeview::CvSymbol::Label { address: ref a, ref name } =>
+                println!("{}: label {}", name, address(a)),
+            codeview::CvSymbol::Other(kind, _) => println!("<symbol {:04x}>", kind)
         }
     }
 }
+
+fn borland_type(debug: &borland::BorlandDebug, index: u16) -> String {
+    match debug.typedef(index) {
+        Some(typedef) => typedef.to_string(),
+        None => format!("type {}", index)
+    }
+}
+
+fn print_scope(debug: &borland::BorlandDebug, scope: &borland::Scope, depth: usize) {
+    for local in &scope.locals {
+        println!("{:indent$}{}: {}, segment {}, offset {:04x}", "", local.name,
+                borland_type(debug, local.type_index), local.segment, local.offset,
+                indent = 2 * depth);
+    }
+    for inner in &scope.scopes {
+        match inner.end {
+            Some(end) => println!("{:indent$}scope {:04x}-{:04x}", "", inner.start, end,
+                    indent = 2 * depth),
+            None => println!("{:indent$}scope {:04x}-", "", inner.start, indent = 2 * depth)
+        }
+        print_scope(debug, inner, depth + 1);
+    }
+}
+
+/* the Turbo Debugger comments of a module as a tree */
+fn borland(program: &str, args: &[String]) {
+    if args.len() != 1 {
+        usage(program);
+    }
+    let module = read_obj_module(&args[0]);
+    let debug = &module.borland;
+    if debug.is_empty() {
+        println!("no Borland debug information");
+        return;
+    }
+    if let Some((language, model)) = debug.compiler {
+        println!("Compiler: {}, {} model", borland::language_to_string(language),
+                borland::model_to_string(model));
+    }
+    for &(index, ref name, time) in &debug.sources {
+        println!("Source {}: {} ({:08x})", index, name, time);
+    }
+    for &(ref name, time) in &debug.dependencies {
+        println!("Dependency: {} ({:08x})", name, time);
+    }
+    for &(index, line, offset) in &debug.lines {
+        println!("Source {} line {}: {:04x}", index, line, offset);
+    }
+    println!();
+    println!("Types:");
+    for typedef in &debug.types {
+        println!("{}: {}", typedef.index, typedef);
+        if let Some(members) = debug.members(typedef) {
+            for member in members {
+                if typedef.tid == 0x22 {
+                    println!("  {} = {}", member.name, member.value);
+                } else {
+                    println!("  {}: {}", member.name, borland_type(debug, member.value));
+                }
+            }
+        }
+    }
+    for &(index, t) in &debug.extern_types {
+        let name = module.external(index).map_or("?", |e| e.name.as_str());
+        println!("{}: {}", name, borland_type(debug, t));
+    }
+    println!();
+    println!("Scopes:");
+    print_scope(debug, &debug.root, 0);
+}
+
+/* a debug symbol: frame offsets for the ones of a procedure block,
+ * segment offsets otherwise */
+fn debug_symbol(module: &module::Module, symbol: &module::Symbol) -> String {
+    let mut s = if symbol.segment == 0 {
+        format!("{}: [bp{:+}]", symbol.name, symbol.offset as u16 as i16)
+    } else {
+        format!("{}: {}:{:04x}", symbol.name, module.segment_name(symbol.segment),
+                symbol.offset)
+    };
+    if symbol.type_index > 0 {
+        s += &format!(", {}", module.types.declaration(symbol.type_index));
+    }
+    s
+}
+
+fn print_blocks(module: &module::Module, parent: Option<usize>, depth: usize) {
+    for (i, block) in module.blocks.iter().enumerate().filter(|b| b.1.parent == parent) {
+        let def = &block.def;
+        print!("{:indent$}", "", indent = 2 * depth);
+        if def.procedure {
+            print!("{} procedure ", if def.far { "far" } else { "near" });
+        } else {
+            print!("block ");
+        }
+        if !def.name.is_empty() {
+            print!("{} ", def.name);
+        }
+        print!("{}:{:04x}, length {}", module.segment_name(def.segment), def.offset,
+                def.length);
+        if let Some(return_offset) = def.return_offset {
+            print!(", return address at [bp+{}]", return_offset);
+        }
+        if def.type_index > 0 {
+            print!(", {}", module.types.declaration(def.type_index));
+        }
+        if !block.closed {
+            print!(", no BLKEND");
+        }
+        println!();
+        for &symbol in &block.symbols {
+            println!("{:indent$}{}", "", debug_symbol(module, &module.debug_symbols[symbol]),
+                    indent = 2 * depth + 2);
+        }
+        print_blocks(module, Some(i), depth + 1);
+    }
+}
+
+/* the BLKDEF scopes of a module with their DEBSYM symbols */
+fn scopes(program: &str, args: &[String]) {
+    if args.len() != 1 {
+        usage(program);
+    }
+    let module = read_obj_module(&args[0]);
+    print_blocks(&module, None, 0);
+}
+
+/* the local variables visible at a segment offset */
+fn locals(program: &str, args: &[String]) {
+    if args.len() != 3 {
+        usage(program);
+    }
+    let offset = match parse_number(&args[2]) {
+        Some(offset) => offset,
+        None => usage(program)
+    };
+    let module = read_obj_module(&args[0]);
+    let segment = module.find_segment(&args[1]);
+    for (block, symbol) in block::visible(&module, segment, offset) {
+        let scope = if block.def.name.is_empty() { "block" } else { &block.def.name };
+        println!("{}: {}", scope, debug_symbol(&module, symbol));
+    }
+}
+
+/* dumps the records of an OMF-51 object file */
+fn omf51(program: &str, args: &[String]) {
+    if args.len() != 1 {
+        usage(program);
+    }
+    for orec in omf51::make_omf51_reader(open_file(&args[0])).unwrap() {
+        match orec.rtype {
+            omf51::RecordType51::MODHDR => print51::modhdr(orec),
+            omf51::RecordType51::MODEND => print51::modend(orec),
+            omf51::RecordType51::CONTENT => print51::content(orec),
+            omf51::RecordType51::FIXUP => print51::fixup(orec),
+            omf51::RecordType51::SEGDEF => print51::segdef(orec),
+            omf51::RecordType51::SCOPDEF => print51::scopdef(orec),
+            omf51::RecordType51::DEBITEM => print51::debitem(orec),
+            omf51::RecordType51::PUBDEF => print51::pubdef(orec),
+            omf51::RecordType51::EXTDEF => print51::extdef(orec)
+        }
+    }
+}
+
+/* dumps the records of an OMF-85 object file */
+fn omf85(program: &str, args: &[String]) {
+    if args.len() != 1 {
+        usage(program);
+    }
+    for orec in omf85::make_omf85_reader(open_file(&args[0])).unwrap() {
+        print_omf85(orec);
+    }
+}
+
+fn print_omf85(orec: omf85::Record85) {
+    match orec.rtype {
+        omf85::RecordType85::MODHDR => print85::modhdr(orec),
+        omf85::RecordType85::MODEND => print85::modend(orec),
+        omf85::RecordType85::CONTENT => print85::content(orec),
+        omf85::RecordType85::LINNUM => print85::linnum(orec),
+        omf85::RecordType85::EOF => print85::eof(orec),
+        omf85::RecordType85::ANCESTOR => print85::ancestor(orec),
+        omf85::RecordType85::LOCALS => print85::locals(orec),
+        omf85::RecordType85::PUBLICS => print85::publics(orec),
+        omf85::RecordType85::EXTNAMES => print85::extnames(orec),
+        omf85::RecordType85::EXTREF |
+        omf85::RecordType85::RELOC |
+        omf85::RecordType85::INTERSEG => print85::references(orec),
+        omf85::RecordType85::COMNAMES => print85::comnames(orec)
+    }
+}
+
+/* dumps the records of an OMF-96 object file, the undecoded ones in hexadecimal */
+fn omf96(program: &str, args: &[String]) {
+    if args.len() != 1 {
+        usage(program);
+    }
+    let mut reader = match omf96::make_omf96_reader(open_file(&args[0])) {
+        Ok(reader) => reader,
+        Err(reason) => panic!("{}: {}", args[0], reason)
+    };
+    for orec in reader.by_ref() {
+        match orec.rtype {
+            omf96::RecordType96::MODHDR => print96::modhdr(orec),
+            omf96::RecordType96::CONTENT => print96::content(orec),
+            _ => print96::other(orec)
+        }
+    }
+    if let Some(reason) = reader.error() {
+        println!("stopped: {}", reason);
+    }
+}
+
+/* the format of each file, to know which reader to use */
+fn detect(program: &str, args: &[String]) {
+    if args.is_empty() {
+        usage(program);
+    }
+    for filename in args {
+        match std::fs::read(filename) {
+            Ok(contents) => println!("{}: {}", filename, detect::detect(&contents)),
+            Err(reason) => println!("{}: couldn't open: {}", filename, reason)
+        }
+    }
+}
+
+/* writes an absolute OMF-85 module as an Intel HEX file */
+fn hex(program: &str, args: &[String]) {
+    if args.len() != 2 {
+        usage(program);
+    }
+    let records: Vec<omf85::Record85> =
+            omf85::make_omf85_reader(open_file(&args[0])).unwrap().collect();
+    match omf85::absolute_image(&records) {
+        Ok((contents, start)) => {
+            if let Err(reason) = std::fs::write(&args[1], omf85::intel_hex(&contents, start)) {
+                println!("couldn't write {}: {}", args[1], reason);
+            }
+        },
+        Err(reason) => println!("not an absolute module: {}", reason)
+    }
+}
+
+fn print_image(image: &[u8]) {
+    for (i, line) in image.chunks(16).enumerate() {
+        print!("{:04x}:", i * 16);
+        for x in line {
+            print!(" {:02x}", x);
+        }
+        println!();
+    }
+}
+
+/* the contents of a segment or COMDAT with back-patches applied */
+fn image(program: &str, args: &[String]) {
+    if args.len() != 2 {
+        usage(program);
+    }
+    let module = read_obj_module(&args[0]);
+    let segment = module.find_segment(&args[1]);
+    if segment > 0 {
+        let (image, errors) = image::segment_image(&module, segment);
+        print_image(&image);
+        for reason in errors {
+            println!("bad back-patch: {}", reason);
+        }
+        return;
+    }
+    match module.comdats.iter().find(|c| module.name(c.name_index) == args[1]) {
+        Some(comdat) => match image::comdat_image(&module, comdat) {
+            Ok(image) => print_image(&image),
+            Err(reason) => println!("bad COMDAT: {}", reason)
+        },
+        None => println!("no segment or COMDAT {}", args[1])
+    }
+}
+
+/* applies the linker selection rules to the COMDATs of several modules */
+fn comdats(program: &str, args: &[String]) {
+    if args.is_empty() {
+        usage(program);
+    }
+    let modules: Vec<module::Module> = args.iter()
+            .map(|filename| read_obj_module(filename))
+            .collect();
+
+    /* names in the order they are first defined */
+    let mut names: Vec<&str> = Vec::new();
+    for module in &modules {
+        for comdat in &module.comdats {
+            let name = module.name(comdat.name_index);
+            if !comdat.local && !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+
+    for name in names {
+        let mut definitions = Vec::new();
+        let mut files = Vec::new();
+        for (module, filename) in modules.iter().zip(args.iter()) {
+            for comdat in &module.comdats {
+                if !comdat.local && module.name(comdat.name_index) == name {
+                    definitions.push(comdat);
+                    files.push(filename);
+                }
+            }
+        }
+        match comdat::select(&definitions) {
+            Ok(chosen) => {
+                let index = definitions.iter()
+                        .position(|&c| std::ptr::eq(c, chosen)).unwrap();
+                println!("{}: {} ({}, {} definition{})", name, files[index],
+                        chosen.selection, definitions.len(),
+                        if definitions.len() == 1 { "" } else { "s" });
+            },
+            Err(reason) => println!("{}: error: {}", name, reason)
+        }
+    }
+}
+
+fn main() {
+    /* get arguments */
+    let args: Vec<String> = env::args().collect();
+    println!("args: {:?}", args);
+    if args.len() < 2 {
+        usage(&args[0]);
+    }
+    match args[1].as_str() {
+        "addr2line" => addr2line(&args[0], &args[2..]),
+        "line2addr" => line2addr(&args[0], &args[2..]),
+        "types" => types(&args[0], &args[2..]),
+        "symbols" => symbols(&args[0], &args[2..]),
+        "comdats" => comdats(&args[0], &args[2..]),
+        "link" => link(&args[0], &args[2..]),
+        "image" => image(&args[0], &args[2..]),
+        "summary" => summary(&args[0], &args[2..]),
+        "imports" => imports(&args[0], &args[2..]),
+        "exports" => exports(&args[0], &args[2..]),
+        "libraries" => libraries(&args[0], &args[2..]),
+        "codeview" => codeview(&args[0], &args[2..]),
+        "borland" => borland(&args[0], &args[2..]),
+        "scopes" => scopes(&args[0], &args[2..]),
+        "locals" => locals(&args[0], &args[2..]),
+        "omf51" => omf51(&args[0], &args[2..]),
+        "omf85" => omf85(&args[0], &args[2..]),
+        "hex" => hex(&args[0], &args[2..]),
+        "omf96" => omf96(&args[0], &args[2..]),
+        "detect" => detect(&args[0], &args[2..]),
+        _ if args.len() == 2 => dump(&args[0], &args[1]),
+        _ => usage(&args[0])
+    }
+}
@@ -0,0 +1,1042 @@
+use std::fmt;
+
+use ::objrec::*;
+use ::typetable::*;
+use ::comdat::*;
+use ::bakpat::*;
+use ::absrec::*;
+use ::coment::*;
+use ::fixup::*;
+use ::borland::*;
+use ::block::*;
+use ::regint::*;
+
+/* a segment as defined by a SEGDEF record */
+pub struct Segment {
+    pub name_index: usize,
+    pub class_index: usize,
+    pub length: u32
+}
+
+/* one line number entry of a LINNUM record */
+pub struct LineNumber {
+    pub source: usize,
+    pub segment: usize,
+    pub line: u16,
+    pub offset: u16
+}
+
+/* the data of an LEDATA record */
+pub struct Data {
+    pub segment: usize,
+    pub offset: u32,
+    pub data: Vec<u8>
+}
+
+/* a name with a type index: public or debug symbol */
+pub struct Symbol {
+    pub name: String,
+    pub local: bool,
+    pub segment: usize,
+    pub offset: u32,
+    pub type_index: usize
+}
+
+/* size of a communal variable from COMDEF or LCOMDEF */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Communal {
+    Near(u32),
+    Far {
+        elements: u32,
+        element_size: u32
+    },
+    /* Borland: the data type is the index of the segment */
+    Segment {
+        segment: u8,
+        length: u32
+    }
+}
+
+impl Communal {
+    /* size in bytes */
+    pub fn size(&self) -> u64 {
+        match *self {
+            Communal::Near(length) => length as u64,
+            Communal::Far { elements, element_size } =>
+                elements as u64 * element_size as u64,
+            Communal::Segment { length, .. } => length as u64
+        }
+    }
+}
+
+impl fmt::Display for Communal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Communal::Near(length) => write!(f, "near, length: {}", length),
+            Communal::Far { elements, element_size } =>
+                write!(f, "far, elements: {}, element size: {}",
+                        elements, element_size),
+            Communal::Segment { segment, length } =>
+                write!(f, "segment: {}, length: {}", segment, length)
+        }
+    }
+}
+
+/* what defines an entry of the external index space */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ExternalKind {
+    /* EXTDEF or LEXTDEF: defined by a public in some module */
+    Extern,
+    /* COMDEF or LCOMDEF: the communal defines itself */
+    Communal(Communal),
+    /* CEXTDEF: defined by a COMDAT */
+    Comdat
+}
+
+/* the comment that gave an external a default resolution */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Weakness {
+    /* WKEXT */
+    Weak,
+    /* LZEXT: the default is only used when no library defines the name */
+    Lazy
+}
+
+/* an entry of the external index space: EXTDEF, LEXTDEF, CEXTDEF names
+ * and communals all share the numbering */
+pub struct External {
+    pub name: String,
+    pub type_index: usize,
+    pub local: bool,
+    pub kind: ExternalKind,
+    /* the external index used when nothing defines the name */
+    pub default: Option<(Weakness, usize)>
+}
+
+/* decodes the names of a COMDEF or LCOMDEF record */
+pub fn read_comdef(vec: &[u8]) -> Result<Vec<(String, usize, Communal)>, String> {
+    let mut result = Vec::new();
+    let mut i = 0;
+    while i < vec.len() {
+        let length = vec[i] as usize;
+        let truncated = format!("communal at offset {} is truncated", i);
+        if i + length + 3 > vec.len() {
+            return Err(truncated);
+        }
+        let name = String::from_utf8_lossy(&vec[i+1..i+1+length]).to_string();
+        i += 1 + length;
+        let (type_index, n) = read_index(&vec[i..])?;
+        i += n;
+        let data_type = *vec.get(i).ok_or(truncated)?;
+        i += 1;
+        let bad_length = || format!("bad length for communal {}", name);
+        let communal = match data_type {
+            0x61 => {
+                let (elements, n) = read_length(&vec[i..]).ok_or_else(bad_length)?;
+                i += n;
+                let (element_size, n) = read_length(&vec[i..]).ok_or_else(bad_length)?;
+                i += n;
+                Communal::Far { elements, element_size }
+            },
+            0x62 => {
+                let (length, n) = read_length(&vec[i..]).ok_or_else(bad_length)?;
+                i += n;
+                Communal::Near(length)
+            },
+            0x01 ..= 0x5f => {
+                let (length, n) = read_length(&vec[i..]).ok_or_else(bad_length)?;
+                i += n;
+                Communal::Segment { segment: data_type, length }
+            },
+            t => return Err(format!("bad data type {:02X}H for communal {}", t, name))
+        };
+        result.push((name, type_index, communal));
+    }
+    Ok(result)
+}
+
+/* the fields of a SEGDEF or SEGDEF32 record; the frame number and offset
+ * are there for absolute segments only */
+pub struct Segdef {
+    pub acbp: u8,
+    pub frame: Option<(u16, u8)>,
+    pub length: u32,
+    pub name_index: usize,
+    pub class_index: usize,
+    pub overlay_index: usize
+}
+
+pub fn read_segdef(vec: &[u8], is32: bool) -> Result<Segdef, String> {
+    let acbp = *vec.first().ok_or_else(|| "record is empty".to_string())?;
+    let (frame, mut i) = if acbp >> 5 == 0 {
+        match vec.get(1..4) {
+            Some(f) => (Some((read_u16(f), f[2])), 4),
+            None => return Err("frame number and offset are truncated".to_string())
+        }
+    } else {
+        (None, 1)
+    };
+    let size = if is32 { 4 } else { 2 };
+    let length = match vec.get(i..i+size) {
+        Some(l) => read_offset(l, is32),
+        None => return Err("segment length is truncated".to_string())
+    };
+    i += size;
+    let (name_index, n) = read_index(&vec[i..])?;
+    i += n;
+    let (class_index, n) = read_index(&vec[i..])?;
+    i += n;
+    let (overlay_index, _) = read_index(&vec[i..])?;
+    Ok(Segdef { acbp, frame, length, name_index, class_index, overlay_index })
+}
+
+/* the segment index, offset and data of an LEDATA or LIDATA record and
+ * their 32-bit forms; iterated data is expanded */
+pub fn read_data(vec: &[u8], is32: bool, iterated: bool) -> Result<Data, String> {
+    let (segment, i) = read_index(vec)?;
+    let size = if is32 { 4 } else { 2 };
+    let offset = match vec.get(i..i+size) {
+        Some(o) => read_offset(o, is32),
+        None => return Err("data offset is truncated".to_string())
+    };
+    let data = if iterated {
+        expand_iterated(&vec[i+size..], is32)?
+    } else {
+        vec[i+size..].to_vec()
+    };
+    Ok(Data { segment, offset, data })
+}
+
+/* the base group and segment of a LINNUM record and its line number and
+ * offset pairs */
+#[derive(Debug, PartialEq)]
+pub struct Linnum {
+    pub group: usize,
+    pub segment: usize,
+    pub lines: Vec<(u16, u16)>
+}
+
+pub fn read_linnum(vec: &[u8]) -> Result<Linnum, String> {
+    let (group, mut i) = read_index(vec)?;
+    let (segment, n) = read_index(&vec[i..])?;
+    i += n;
+    let mut lines = Vec::new();
+    while i < vec.len() {
+        match vec.get(i..i+4) {
+            Some(p) => lines.push((read_u16(p), read_u16(&p[2..]))),
+            None => return Err("line number entry is truncated".to_string())
+        }
+        i += 4;
+    }
+    Ok(Linnum { group, segment, lines })
+}
+
+/* the names and type indices of an EXTDEF or LEXTDEF record */
+pub fn read_extdef(vec: &[u8]) -> Result<Vec<(String, usize)>, String> {
+    let mut result = Vec::new();
+    let mut i = 0;
+    while i < vec.len() {
+        let (name, n) = read_name(&vec[i..])?;
+        i += n;
+        let (type_index, n) = read_index(&vec[i..])?;
+        i += n;
+        result.push((name, type_index));
+    }
+    Ok(result)
+}
+
+/* the name index and type index pairs of a CEXTDEF record */
+pub fn read_cextdef(vec: &[u8]) -> Result<Vec<(usize, usize)>, String> {
+    let mut result = Vec::new();
+    let mut i = 0;
+    while i < vec.len() {
+        let (name_index, n) = read_index(&vec[i..])?;
+        i += n;
+        let (type_index, n) = read_index(&vec[i..])?;
+        i += n;
+        result.push((name_index, type_index));
+    }
+    Ok(result)
+}
+
+/* the names of PUBDEF, LPUBDEF or LOCSYM: base, then name, offset, type */
+/* the base group and segment of PUBDEF, LPUBDEF or LOCSYM, and the frame
+ * number that follows a base without a segment; returns them and the
+ * number of bytes read */
+pub fn read_public_base(vec: &[u8]) -> Result<(usize, usize, Option<u16>, usize), String> {
+    let (group, mut i) = read_index(vec)?;
+    let (segment, n) = read_index(&vec[i..])?;
+    i += n;
+    if segment != 0 {
+        return Ok((group, segment, None, i));
+    }
+    match vec.get(i..i+2) {
+        Some(frame) => Ok((group, segment, Some(read_u16(frame)), i + 2)),
+        None => Err("base frame is truncated".to_string())
+    }
+}
+
+pub fn read_publics(vec: &[u8], local: bool, is32: bool) -> Result<Vec<Symbol>, String> {
+    let mut result = Vec::new();
+    let (_, segment, _, mut i) = read_public_base(vec)?;
+    let offset_size = if is32 { 4 } else { 2 };
+    while i < vec.len() {
+        let (name, n) = read_name(&vec[i..])?;
+        i += n;
+        let offset = match vec.get(i..i+offset_size) {
+            Some(offset) => read_offset(offset, is32),
+            None => return Err(format!("offset of {} is truncated", name))
+        };
+        i += offset_size;
+        let (type_index, n) = read_index(&vec[i..])?;
+        i += n;
+        result.push(Symbol { name, local, segment, offset, type_index });
+    }
+    Ok(result)
+}
+
+/* pairs of alias name and substitute name of an ALIAS record */
+pub fn read_aliases(vec: &[u8]) -> Result<Vec<(String, String)>, String> {
+    let mut result = Vec::new();
+    let mut i = 0;
+    while i < vec.len() {
+        let (alias, n) = read_name(&vec[i..])?;
+        i += n;
+        let (substitute, n) = read_name(&vec[i..])?;
+        i += n;
+        result.push((alias, substitute));
+    }
+    Ok(result)
+}
+
+/* the tables of an object module, collected record by record */
+pub struct Module {
+    /* THEADR, LHEADR or RHEADR */
+    pub header: Option<RecordType>,
+    pub name: String,
+    pub names: Vec<String>,
+    pub segments: Vec<Segment>,
+    pub sources: Vec<String>,
+    pub lines: Vec<LineNumber>,
+    pub types: TypeTable,
+    pub externals: Vec<External>,
+    pub publics: Vec<Symbol>,
+    pub debug_symbols: Vec<Symbol>,
+    /* BLKDEF scopes in order, which DEBSYM block indices count from 1 */
+    pub blocks: Vec<Block>,
+    pub comdats: Vec<Comdat>,
+    pub aliases: Vec<(String, String)>,
+    pub data: Vec<Data>,
+    pub bakpats: Vec<Bakpat>,
+    pub fixups: Vec<Fixup>,
+    /* REGINT values and the MODEND start address */
+    pub registers: Vec<Register>,
+    pub start: Option<RegisterValue>,
+    pub version: Option<String>,
+    pub vendor_extensions: Vec<(u16, Vec<u8>)>,
+    /* from default library search comments, in order */
+    pub default_libraries: Vec<String>,
+    pub imports: Vec<Import>,
+    pub exports: Vec<Export>,
+    pub overlays: Vec<Ovldef>,
+    pub physical_data: Vec<PhysicalData>,
+    /* the debug information style of a COMENT A1H, "CV" for CodeView */
+    pub debug_style: Option<(u8, String)>,
+    /* Turbo Debugger comments */
+    pub borland: BorlandDebug,
+    /* records that could not be added, with the reason */
+    pub errors: Vec<String>,
+    source: Option<usize>,
+    threads: Threads,
+    open_blocks: Vec<usize>
+}
+
+impl Module {
+
+    pub fn new() -> Module {
+        Module {
+            header: None,
+            name: String::new(),
+            names: Vec::new(),
+            segments: Vec::new(),
+            sources: Vec::new(),
+            lines: Vec::new(),
+            types: TypeTable::new(),
+            externals: Vec::new(),
+            publics: Vec::new(),
+            debug_symbols: Vec::new(),
+            blocks: Vec::new(),
+            comdats: Vec::new(),
+            aliases: Vec::new(),
+            data: Vec::new(),
+            bakpats: Vec::new(),
+            fixups: Vec::new(),
+            registers: Vec::new(),
+            start: None,
+            version: None,
+            vendor_extensions: Vec::new(),
+            default_libraries: Vec::new(),
+            imports: Vec::new(),
+            exports: Vec::new(),
+            overlays: Vec::new(),
+            physical_data: Vec::new(),
+            debug_style: None,
+            borland: BorlandDebug::default(),
+            errors: Vec::new(),
+            source: None,
+            threads: Threads::default(),
+            open_blocks: Vec::new()
+        }
+    }
+
+    pub fn add(&mut self, orec: &ObjectRecord) {
+        match orec.rtype {
+            RecordType::THEADR |
+            RecordType::LHEADR => {
+                let result = self.add_theadr(orec.rtype, &orec.data);
+                self.report(orec.rtype, result);
+            },
+            RecordType::RHEADR => {
+                let result = self.add_rheadr(&orec.data);
+                self.report(orec.rtype, result);
+            },
+            RecordType::OVLDEF => {
+                let result = read_ovldef(&orec.data)
+                        .map(|ovldef| self.overlays.push(ovldef));
+                self.report(orec.rtype, result);
+            },
+            RecordType::PEDATA |
+            RecordType::PIDATA => {
+                let result = read_physical_data(&orec.data, orec.rtype == RecordType::PIDATA)
+                        .map(|data| self.physical_data.push(data));
+                self.report(orec.rtype, result);
+            },
+            RecordType::LOCSYM => {
+                let result = read_publics(&orec.data, true, false)
+                        .map(|symbols| self.debug_symbols.extend(symbols));
+                self.report(orec.rtype, result);
+            },
+            RecordType::COMENT => {
+                let result = self.add_coment(&orec.data);
+                self.report(orec.rtype, result);
+            },
+            RecordType::LNAMES |
+            RecordType::LLNAMES => {
+                let result = self.add_lnames(&orec.data);
+                self.report(orec.rtype, result);
+            },
+            RecordType::VERNUM => self.add_vernum(&orec.data),
+            RecordType::VENDEXT => self.add_vendext(&orec.data),
+            RecordType::SEGDEF |
+            RecordType::SEGDEF32 => {
+                let result = self.add_segdef(&orec.data, orec.rtype == RecordType::SEGDEF32);
+                self.report(orec.rtype, result);
+            },
+            RecordType::LINNUM => {
+                let result = self.add_linnum(&orec.data);
+                self.report(orec.rtype, result);
+            },
+            RecordType::TYPDEF => self.types.add(&orec.data),
+            RecordType::EXTDEF |
+            RecordType::LEXTDEF => {
+                let result = self.add_extdef(&orec.data, orec.rtype == RecordType::LEXTDEF);
+                self.report(orec.rtype, result);
+            },
+            RecordType::CEXTDEF => {
+                let result = self.add_cextdef(&orec.data);
+                self.report(orec.rtype, result);
+            },
+            RecordType::COMDEF |
+            RecordType::LCOMDEF => {
+                let result = self.add_comdef(&orec.data, orec.rtype == RecordType::LCOMDEF);
+                self.report(orec.rtype, result);
+            },
+            RecordType::PUBDEF |
+            RecordType::PUBDEF32 |
+            RecordType::LPUBDEF |
+            RecordType::LPUBDEF32 => {
+                let local = orec.rtype == RecordType::LPUBDEF || orec.rtype == RecordType::LPUBDEF32;
+                let is32 = orec.rtype == RecordType::PUBDEF32 || orec.rtype == RecordType::LPUBDEF32;
+                let result = read_publics(&orec.data, local, is32)
+                        .map(|symbols| self.publics.extend(symbols));
+                self.report(orec.rtype, result);
+            },
+            RecordType::ALIAS => {
+                let result = read_aliases(&orec.data)
+                        .map(|aliases| self.aliases.extend(aliases));
+                self.report(orec.rtype, result);
+            },
+            RecordType::LEDATA |
+            RecordType::LEDATA32 |
+            RecordType::LIDATA |
+            RecordType::LIDATA32 => {
+                let is32 = orec.rtype == RecordType::LEDATA32 || orec.rtype == RecordType::LIDATA32;
+                let iterated = orec.rtype == RecordType::LIDATA || orec.rtype == RecordType::LIDATA32;
+                let result = self.add_data(&orec.data, is32, iterated);
+                self.report(orec.rtype, result);
+            },
+            RecordType::FIXUPP |
+            RecordType::FIXUPP32 => {
+                let result = self.add_fixupp(&orec.data, orec.rtype == RecordType::FIXUPP32);
+                self.report(orec.rtype, result);
+            },
+            RecordType::BAKPAT => self.add_bakpat(orec.rtype,
+                    read_bakpat(&orec.data, false)),
+            RecordType::BAKPAT32 => self.add_bakpat(orec.rtype,
+                    read_bakpat(&orec.data, true)),
+            RecordType::NBKPAT => self.add_bakpat(orec.rtype,
+                    read_nbkpat(&orec.data, false)),
+            RecordType::NBKPAT32 => self.add_bakpat(orec.rtype,
+                    read_nbkpat(&orec.data, true)),
+            RecordType::REGINT => {
+                let result = read_regint(&orec.data)
+                        .map(|registers| self.registers.extend(registers));
+                self.report(orec.rtype, result);
+            },
+            RecordType::MODEND |
+            RecordType::MODEND32 => {
+                let result = read_start(&orec.data, orec.rtype == RecordType::MODEND32)
+                        .map(|start| self.start = start);
+                self.report(orec.rtype, result);
+            },
+            RecordType::BLKDEF => {
+                let result = self.add_blkdef(&orec.data);
+                self.report(orec.rtype, result);
+            },
+            RecordType::BLKEND => {
+                if let Some(i) = self.open_blocks.pop() {
+                    self.blocks[i].closed = true;
+                }
+            },
+            RecordType::DEBSYM => {
+                let result = self.add_debsym(&orec.data);
+                self.report(orec.rtype, result);
+            },
+            RecordType::COMDAT => {
+                let result = self.add_comdat(&orec.data, false);
+                self.report(orec.rtype, result);
+            },
+            RecordType::COMDAT32 => {
+                let result = self.add_comdat(&orec.data, true);
+                self.report(orec.rtype, result);
+            },
+            RecordType::LINSYM |
+            RecordType::LINSYM32 => {
+                let result = self.add_linsym(&orec.data, orec.rtype == RecordType::LINSYM32);
+                self.report(orec.rtype, result);
+            },
+            _ => {}
+        }
+    }
+
+    fn report(&mut self, rtype: RecordType, result: Result<(), String>) {
+        if let Err(reason) = result {
+            self.errors.push(format!("{:?}: {}", rtype, reason));
+        }
+    }
+
+    /* name from the LNAMES table, indices start at 1 */
+    pub fn name(&self, index: usize) -> &str {
+        if index == 0 || index > self.names.len() {
+            ""
+        } else {
+            &self.names[index - 1]
+        }
+    }
+
+    /* segment name of a segment index, indices start at 1 */
+    pub fn segment_name(&self, index: usize) -> &str {
+        if index == 0 || index > self.segments.len() {
+            ""
+        } else {
+            self.name(self.segments[index - 1].name_index)
+        }
+    }
+
+    /* segment index by name, 0 if there is no such segment */
+    pub fn find_segment(&self, name: &str) -> usize {
+        for i in 1..=self.segments.len() {
+            if self.segment_name(i).eq_ignore_ascii_case(name) {
+                return i;
+            }
+        }
+        0
+    }
+
+    /* external of an external index, indices start at 1 */
+    pub fn external(&self, index: usize) -> Option<&External> {
+        if index == 0 || index > self.externals.len() {
+            None
+        } else {
+            Some(&self.externals[index - 1])
+        }
+    }
+
+    /* the source file that the line numbers that follow belong to */
+    fn set_source(&mut self, name: &str) {
+        let index = match self.sources.iter().position(|s| s == name) {
+            Some(index) => index,
+            None => {
+                self.sources.push(name.to_string());
+                self.sources.len() - 1
+            }
+        };
+        self.source = Some(index);
+    }
+
+    fn add_theadr(&mut self, rtype: RecordType, vec: &[u8]) -> Result<(), String> {
+        let (name, _) = read_name(vec)?;
+        self.set_source(&name);
+        self.header = Some(rtype);
+        self.name = name;
+        Ok(())
+    }
+
+    fn add_rheadr(&mut self, vec: &[u8]) -> Result<(), String> {
+        let rheadr = read_rheadr(vec)?;
+        self.header = Some(RecordType::RHEADR);
+        self.name = rheadr.name;
+        Ok(())
+    }
+
+    fn add_coment(&mut self, vec: &[u8]) -> Result<(), String> {
+        if vec.len() > 1 && (0xe0..=0xef).contains(&vec[1]) {
+            if vec[1] == 0xe8 {
+                let (name, _) = read_name(vec.get(3..).unwrap_or(&[]))?;
+                self.set_source(&name);
+            }
+            self.borland.add(read_borland(vec[1], &vec[2..])?);
+            return Ok(());
+        }
+        let (weakness, pairs) = match read_coment(vec)?.comment {
+            Comment::WeakExterns(pairs) => (Weakness::Weak, pairs),
+            Comment::LazyExterns(pairs) => (Weakness::Lazy, pairs),
+            Comment::DebugStyle { version, style } => {
+                self.debug_style = Some((version, style));
+                return Ok(());
+            },
+            Comment::DefaultLibrary(name) => {
+                self.default_libraries.push(name);
+                return Ok(());
+            },
+            Comment::Impdef(import) => {
+                self.imports.push(import);
+                return Ok(());
+            },
+            Comment::Expdef(export) => {
+                self.exports.push(export);
+                return Ok(());
+            },
+            _ => return Ok(())
+        };
+        for (index, default) in pairs {
+            if index == 0 || index > self.externals.len() {
+                return Err(format!("external index {} is undefined", index));
+            }
+            self.externals[index - 1].default = Some((weakness, default));
+        }
+        Ok(())
+    }
+
+    fn add_lnames(&mut self, vec: &[u8]) -> Result<(), String> {
+        let mut i = 0;
+        while i < vec.len() {
+            let (name, n) = read_name(&vec[i..])?;
+            self.names.push(name);
+            i += n;
+        }
+        Ok(())
+    }
+
+    fn add_vernum(&mut self, vec: &[u8]) {
+        if let Some(&length) = vec.first() {
+            let length = (length as usize).min(vec.len() - 1);
+            self.version = Some(String::from_utf8_lossy(&vec[1..1+length]).to_string());
+        }
+    }
+
+    /* vendor number and the vendor specific bytes */
+    fn add_vendext(&mut self, vec: &[u8]) {
+        if vec.len() >= 2 {
+            self.vendor_extensions.push((read_u16(vec), vec[2..].to_vec()));
+        }
+    }
+
+    fn add_segdef(&mut self, vec: &[u8], is32: bool) -> Result<(), String> {
+        let segdef = read_segdef(vec, is32)?;
+        /* a big 32-bit segment of 4 GB is one byte short */
+        let big = (segdef.acbp & 0x02) != 0;
+        self.segments.push(Segment {
+            name_index: segdef.name_index,
+            class_index: segdef.class_index,
+            length: match (big, is32) {
+                (true, false) => 0x10000,
+                (true, true) => 0xffffffff,
+                _ => segdef.length
+            }
+        });
+        Ok(())
+    }
+
+    fn add_linnum(&mut self, vec: &[u8]) -> Result<(), String> {
+        let linnum = read_linnum(vec)?;
+        let segment = linnum.segment;
+        if self.source.is_none() {
+            self.set_source("");
+        }
+        let source = self.source.unwrap();
+        for (line, offset) in linnum.lines {
+            self.lines.push(LineNumber { source, segment, line, offset });
+        }
+        Ok(())
+    }
+
+    fn add_extdef(&mut self, vec: &[u8], local: bool) -> Result<(), String> {
+        for (name, type_index) in read_extdef(vec)? {
+            self.externals.push(External {
+                name,
+                type_index,
+                local,
+                kind: ExternalKind::Extern,
+                default: None
+            });
+        }
+        Ok(())
+    }
+
+    /* COMDAT externals: a name index and a type index */
+    fn add_cextdef(&mut self, vec: &[u8]) -> Result<(), String> {
+        for (name_index, type_index) in read_cextdef(vec)? {
+            let name = self.name(name_index).to_string();
+            self.externals.push(External {
+                name,
+                type_index,
+                local: false,
+                kind: ExternalKind::Comdat,
+                default: None
+            });
+        }
+        Ok(())
+    }
+
+    /* communals take the next external indices, like EXTDEF names */
+    fn add_comdef(&mut self, vec: &[u8], local: bool) -> Result<(), String> {
+        for (name, type_index, communal) in read_comdef(vec)? {
+            self.externals.push(External {
+                name,
+                type_index,
+                local,
+                kind: ExternalKind::Communal(communal),
+                default: None
+            });
+        }
+        Ok(())
+    }
+
+    /* the last COMDAT of a name, continuations and LINSYMs go there */
+    fn comdat_mut(&mut self, name_index: usize) -> Option<&mut Comdat> {
+        self.comdats.iter_mut().rev().find(|c| c.name_index == name_index)
+    }
+
+    /* a continuation goes at its data offset, which must be where the
+     * data of the COMDAT ends */
+    fn add_comdat(&mut self, vec: &[u8], is32: bool) -> Result<(), String> {
+        let comdat = read_comdat(vec, is32)?;
+        if comdat.continuation {
+            if let Some(first) = self.comdat_mut(comdat.name_index) {
+                let end = first.offset + first.data.len() as u32;
+                if comdat.offset != end {
+                    return Err(format!("continuation at offset {:x}, data ends at {:x}",
+                            comdat.offset, end));
+                }
+                first.data.extend_from_slice(&comdat.data);
+                return Ok(());
+            }
+        }
+        self.comdats.push(comdat);
+        Ok(())
+    }
+
+    fn add_linsym(&mut self, vec: &[u8], is32: bool) -> Result<(), String> {
+        let linsym = read_linsym(vec, is32)?;
+        match self.comdat_mut(linsym.name_index) {
+            Some(comdat) => comdat.lines.extend(linsym.lines),
+            None => return Err(format!("no COMDAT of name index {}", linsym.name_index))
+        }
+        Ok(())
+    }
+
+    fn add_data(&mut self, vec: &[u8], is32: bool, iterated: bool) -> Result<(), String> {
+        self.data.push(read_data(vec, is32, iterated)?);
+        Ok(())
+    }
+
+    /* fixups apply to the data record before them */
+    fn add_fixupp(&mut self, vec: &[u8], is32: bool) -> Result<(), String> {
+        let (segment, offset) = match self.data.last() {
+            Some(data) => (data.segment, data.offset),
+            None => (0, 0)
+        };
+        let fixups = read_fixupp(vec, &mut self.threads, segment, offset, is32)?;
+        self.fixups.extend(fixups);
+        Ok(())
+    }
+
+    fn add_bakpat(&mut self, rtype: RecordType, bakpat: Result<Bakpat, String>) {
+        let result = bakpat.map(|bakpat| self.bakpats.push(bakpat));
+        self.report(rtype, result);
+    }
+
+    fn add_blkdef(&mut self, vec: &[u8]) -> Result<(), String> {
+        let def = read_blkdef(vec)?;
+        self.blocks.push(Block {
+            def,
+            parent: self.open_blocks.last().cloned(),
+            symbols: Vec::new(),
+            closed: false
+        });
+        self.open_blocks.push(self.blocks.len() - 1);
+        Ok(())
+    }
+
+    fn add_debsym(&mut self, vec: &[u8]) -> Result<(), String> {
+        let debsym = read_debsym(vec)?;
+        let segment = match debsym.base {
+            DebsymBase::Segment { segment, .. } => segment,
+            _ => 0
+        };
+        /* symbols of a block index, or of the block being defined */
+        let block = match debsym.base {
+            DebsymBase::Block(index) => index.checked_sub(1).filter(|&b| b < self.blocks.len()),
+            _ => self.open_blocks.last().cloned()
+        };
+        for (name, offset, type_index) in debsym.symbols {
+            if let Some(b) = block {
+                self.blocks[b].symbols.push(self.debug_symbols.len());
+            }
+            self.debug_symbols.push(Symbol {
+                name,
+                local: true,
+                segment,
+                offset,
+                type_index
+            });
+        }
+        Ok(())
+    }
+
+}
+
+impl Default for Module {
+    fn default() -> Module {
+        Module::new()
+    }
+}
+
+pub fn read_module<I: Iterator<Item = ObjectRecord>>(records: I) -> Module {
+    let mut module = Module::new();
+    for orec in records {
+        module.add(&orec);
+    }
+    module
+}
+
+#[test]
+fn communals() {
+    let communals = read_comdef(&[
+            0x03, 0x42, 0x55, 0x46, 0x00, 0x62, 0x81, 0x00, 0x02,
+            0x03, 0x54, 0x41, 0x42, 0x04, 0x61, 0x64, 0x04,
+            0x03, 0x42, 0x49, 0x47, 0x00, 0x61, 0x84, 0x00, 0x00, 0x01, 0x80,
+            0x01, 0x43, 0x00, 0x02, 0x10]).unwrap();
+    assert_eq!(communals.len(), 4);
+    assert_eq!((communals[0].0.as_str(), communals[0].1), ("BUF", 0));
+    assert_eq!(communals[0].2, Communal::Near(512));
+    assert_eq!((communals[1].0.as_str(), communals[1].1), ("TAB", 4));
+    assert_eq!(communals[1].2, Communal::Far { elements: 100, element_size: 4 });
+    assert_eq!(communals[2].2.size(), 0x10000 * 128);
+    assert_eq!(communals[3].2, Communal::Segment { segment: 2, length: 16 });
+    assert!(read_comdef(&[0x01, 0x41, 0x00, 0x63, 0x10]).is_err());
+    assert!(read_comdef(&[0x01, 0x41, 0x00, 0x62, 0x81, 0x10]).is_err());
+    let communals = read_comdef(&[0x01, 0x41, 0x81, 0x02, 0x62, 0x10]).unwrap();
+    assert_eq!((communals[0].1, communals[0].2), (0x102, Communal::Near(16)));
+}
+
+#[test]
+fn external_names() {
+    assert_eq!(read_extdef(&[0x01, 0x41, 0x00, 0x01, 0x42, 0x81, 0x02]).unwrap(),
+            vec![("A".to_string(), 0), ("B".to_string(), 0x102)]);
+    assert!(read_extdef(&[0x01, 0x41, 0x81]).is_err());
+    assert!(read_extdef(&[0x05, 0x41]).is_err());
+    let records = vec![
+        ObjectRecord { rtype: RecordType::EXTDEF, data: vec![0x01, 0x41, 0x80] }
+    ];
+    let module = read_module(records.into_iter());
+    assert!(module.externals.is_empty());
+    assert_eq!(module.errors.len(), 1);
+}
+
+#[test]
+fn public_names() {
+    let publics = read_publics(&[0x00, 0x81, 0x02, 0x01, 0x41, 0x10, 0x00, 0x80, 0x90],
+            false, false).unwrap();
+    assert_eq!((publics[0].segment, publics[0].offset, publics[0].type_index),
+            (0x102, 0x10, 0x90));
+    let publics = read_publics(&[0x00, 0x00, 0x00, 0xb8, 0x01, 0x41, 0x10, 0x00, 0x00],
+            true, false).unwrap();
+    assert_eq!((publics[0].segment, publics[0].name.as_str()), (0, "A"));
+    assert!(read_publics(&[0x00, 0x01, 0x01, 0x41, 0x10], false, false).is_err());
+    assert_eq!(read_aliases(&[0x01, 0x41, 0x01, 0x42]).unwrap(),
+            vec![("A".to_string(), "B".to_string())]);
+    assert!(read_aliases(&[0x01, 0x41]).is_err());
+}
+
+#[test]
+fn comdat_externals() {
+    assert_eq!(read_cextdef(&[0x01, 0x00, 0x80, 0x90, 0x81, 0x02]).unwrap(),
+            vec![(1, 0), (0x90, 0x102)]);
+    assert!(read_cextdef(&[0x01]).is_err());
+}
+
+#[test]
+fn communal_indices() {
+    let records = vec![
+        ObjectRecord { rtype: RecordType::EXTDEF,
+            data: vec![0x06, 0x50, 0x52, 0x49, 0x4e, 0x54, 0x46, 0x00] },
+        ObjectRecord { rtype: RecordType::COMDEF,
+            data: vec![0x03, 0x42, 0x55, 0x46, 0x00, 0x62, 0x10] },
+        ObjectRecord { rtype: RecordType::LCOMDEF,
+            data: vec![0x03, 0x54, 0x41, 0x42, 0x00, 0x61, 0x02, 0x02] },
+        ObjectRecord { rtype: RecordType::EXTDEF,
+            data: vec![0x04, 0x45, 0x58, 0x49, 0x54, 0x00] },
+        ObjectRecord { rtype: RecordType::COMDEF,
+            data: vec![0x03, 0x42, 0x41, 0x44, 0x00, 0x63, 0x10] }
+    ];
+    let module = read_module(records.into_iter());
+    assert_eq!(module.errors.len(), 1);
+    assert_eq!(module.external(1).unwrap().name, "PRINTF");
+    assert_eq!(module.external(1).unwrap().kind, ExternalKind::Extern);
+    assert_eq!(module.external(2).unwrap().name, "BUF");
+    assert!(!module.external(2).unwrap().local);
+    assert_eq!(module.external(3).unwrap().name, "TAB");
+    assert!(module.external(3).unwrap().local);
+    assert_eq!(module.external(4).unwrap().name, "EXIT");
+    assert!(module.external(5).is_none());
+}
+
+#[test]
+fn comdat_continuation() {
+    let records = vec![
+        ObjectRecord { rtype: RecordType::LNAMES,
+            data: vec![0x01, 0x46, 0x01, 0x47] },
+        ObjectRecord { rtype: RecordType::COMDAT,
+            data: vec![0x00, 0x11, 0x00, 0x00, 0x00, 0x00, 0x01, 0xaa, 0xbb] },
+        ObjectRecord { rtype: RecordType::COMDAT,
+            data: vec![0x00, 0x11, 0x00, 0x00, 0x00, 0x00, 0x02, 0xdd] },
+        ObjectRecord { rtype: RecordType::COMDAT,
+            data: vec![0x01, 0x11, 0x00, 0x02, 0x00, 0x00, 0x01, 0xcc] },
+        ObjectRecord { rtype: RecordType::LINSYM,
+            data: vec![0x00, 0x01, 0x0a, 0x00, 0x00, 0x00] }
+    ];
+    let module = read_module(records.into_iter());
+    assert_eq!(module.comdats.len(), 2);
+    assert_eq!(module.name(module.comdats[0].name_index), "F");
+    assert_eq!(module.comdats[0].data, vec![0xaa, 0xbb, 0xcc]);
+    assert_eq!(module.comdats[0].lines, vec![(10, 0)]);
+    assert!(module.comdats[1].lines.is_empty());
+    assert!(module.errors.is_empty());
+
+    /* a continuation that leaves a gap is not added */
+    let records = vec![
+        ObjectRecord { rtype: RecordType::COMDAT,
+            data: vec![0x00, 0x11, 0x00, 0x00, 0x00, 0x00, 0x01, 0xaa, 0xbb] },
+        ObjectRecord { rtype: RecordType::COMDAT,
+            data: vec![0x01, 0x11, 0x00, 0x04, 0x00, 0x00, 0x01, 0xcc] }
+    ];
+    let module = read_module(records.into_iter());
+    assert_eq!(module.comdats[0].data, vec![0xaa, 0xbb]);
+    assert_eq!(module.errors.len(), 1);
+}
+
+#[test]
+fn local_names() {
+    let records = vec![
+        ObjectRecord { rtype: RecordType::LNAMES,
+            data: vec![0x00, 0x04, 0x43, 0x4f, 0x44, 0x45] },
+        ObjectRecord { rtype: RecordType::LLNAMES,
+            data: vec![0x01, 0x46] },
+        ObjectRecord { rtype: RecordType::LNAMES,
+            data: vec![0x04, 0x44, 0x41, 0x54, 0x41] },
+        ObjectRecord { rtype: RecordType::VERNUM,
+            data: vec![0x05, 0x31, 0x2e, 0x30, 0x2e, 0x30] },
+        ObjectRecord { rtype: RecordType::VENDEXT,
+            data: vec![0x01, 0x00, 0xaa] }
+    ];
+    let module = read_module(records.into_iter());
+    assert_eq!(module.name(3), "F");
+    assert_eq!(module.name(4), "DATA");
+    assert_eq!(module.version, Some("1.0.0".to_string()));
+    assert_eq!(module.vendor_extensions, vec![(1, vec![0xaa])]);
+}
+
+#[test]
+fn segment_indices() {
+    let segdef = read_segdef(&[0x48, 0x10, 0x00, 0x81, 0x02, 0x03, 0x01], false).unwrap();
+    assert_eq!((segdef.length, segdef.name_index, segdef.class_index, segdef.overlay_index),
+            (0x10, 0x102, 3, 1));
+    let segdef = read_segdef(&[0x00, 0x34, 0x12, 0x05, 0x10, 0x00, 0x01, 0x02, 0x01], false).unwrap();
+    assert_eq!(segdef.frame, Some((0x1234, 5)));
+    assert!(read_segdef(&[0x48, 0x10, 0x00, 0x81], false).is_err());
+    assert_eq!(read_linnum(&[0x00, 0x81, 0x02, 0x07, 0x00, 0x10, 0x00]).unwrap(),
+            Linnum { group: 0, segment: 0x102, lines: vec![(7, 0x10)] });
+    assert!(read_linnum(&[0x00, 0x01, 0x07, 0x00]).is_err());
+    let records = vec![
+        ObjectRecord { rtype: RecordType::SEGDEF, data: vec![0x48, 0x10] },
+        ObjectRecord { rtype: RecordType::LINNUM, data: vec![0x00] }
+    ];
+    let module = read_module(records.into_iter());
+    assert!(module.segments.is_empty());
+    assert_eq!(module.errors.len(), 2);
+}
+
+#[test]
+fn records_32() {
+    let records = vec![
+        ObjectRecord { rtype: RecordType::SEGDEF32,
+            data: vec![0x48, 0x00, 0x00, 0x01, 0x00, 0x01, 0x01, 0x01] },
+        ObjectRecord { rtype: RecordType::PUBDEF32,
+            data: vec![0x00, 0x01, 0x01, 0x41, 0x00, 0x00, 0x01, 0x00, 0x00] },
+        ObjectRecord { rtype: RecordType::LEDATA32,
+            data: vec![0x01, 0x00, 0x00, 0x01, 0x00, 0xaa] },
+        ObjectRecord { rtype: RecordType::LIDATA,
+            data: vec![0x01, 0x10, 0x00, 0x03, 0x00, 0x00, 0x00, 0x01, 0xbb] },
+        ObjectRecord { rtype: RecordType::LIDATA32,
+            data: vec![0x01, 0x00, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0xcc] }
+    ];
+    let module = read_module(records.into_iter());
+    assert!(module.errors.is_empty());
+    assert_eq!(module.segments[0].length, 0x10000);
+    assert_eq!((module.publics[0].offset, module.publics[0].local), (0x10000, false));
+    assert_eq!((module.data[0].offset, &module.data[0].data[..]), (0x10000, &[0xaa][..]));
+    assert_eq!((module.data[1].offset, &module.data[1].data[..]), (0x10, &[0xbb, 0xbb, 0xbb][..]));
+    assert_eq!(&module.data[2].data[..], &[0xcc, 0xcc][..]);
+    assert_eq!(record_type(0x8b), Some(RecordType::MODEND32));
+    assert_eq!(record_type(0x9d), Some(RecordType::FIXUPP32));
+}
+
+#[test]
+fn reported_errors() {
+    let records = vec![
+        ObjectRecord { rtype: RecordType::THEADR, data: vec![0x05, 0x41] },
+        ObjectRecord { rtype: RecordType::LNAMES, data: vec![0x01, 0x41, 0x03] },
+        ObjectRecord { rtype: RecordType::COMENT, data: vec![0x80, 0xa8, 0x07, 0x01] },
+        ObjectRecord { rtype: RecordType::FIXUPP, data: vec![0xc4, 0x00, 0x9a] },
+        ObjectRecord { rtype: RecordType::LINSYM, data: vec![0x00, 0x01, 0x01, 0x00, 0x00, 0x00] },
+        ObjectRecord { rtype: RecordType::MODEND, data: vec![0xc1] }
+    ];
+    let module = read_module(records.into_iter());
+    assert_eq!(module.names, vec!["A".to_string()]);
+    let types: Vec<&str> = module.errors.iter()
+            .map(|e| e.split(':').next().unwrap())
+            .collect();
+    assert_eq!(types, vec!["THEADR", "LNAMES", "COMENT", "FIXUPP", "LINSYM", "MODEND"]);
+}
@@ -3,7 +3,9 @@ use std::io::prelude::*;
 use std::io::Read;
 use std::io::SeekFrom;
 
-#[derive(Debug)]
+use ::detect::*;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum RecordType {
     REGINT,
     BLKDEF,
@@ -12,15 +14,48 @@ pub enum RecordType {
     THEADR,
     COMENT,
     MODEND,
+    MODEND32,
     EXTDEF,
     TYPDEF,
     PUBDEF,
+    PUBDEF32,
     LINNUM,
     LNAMES,
     SEGDEF,
+    SEGDEF32,
     GRPDEF,
     FIXUPP,
-    LEDATA
+    FIXUPP32,
+    LEDATA,
+    LEDATA32,
+    LIDATA,
+    LIDATA32,
+    COMDEF,
+    LCOMDEF,
+    COMDAT,
+    COMDAT32,
+    LINSYM,
+    LINSYM32,
+    LEXTDEF,
+    LPUBDEF,
+    LPUBDEF32,
+    CEXTDEF,
+    ALIAS,
+    BAKPAT,
+    BAKPAT32,
+    NBKPAT,
+    NBKPAT32,
+    LLNAMES,
+    VERNUM,
+    VENDEXT,
+    RHEADR,
+    OVLDEF,
+    ENDREC,
+    LHEADR,
+    PEDATA,
+    PIDATA,
+    LOCSYM,
+    LIBHED
 }
 
 pub struct ObjectRecord {
@@ -28,86 +63,401 @@ pub struct ObjectRecord {
     pub data: Vec<u8>
 }
 
-fn const_to_type(num: u8) -> RecordType {
-    match num {
+pub fn read_u16(vec: &[u8]) -> u16 {
+    (vec[0] as u16) + 0x100 * (vec[1] as u16)
+}
+
+/* a name preceded by its length; returns it and the number of bytes read */
+pub fn read_name(vec: &[u8]) -> Result<(String, usize), String> {
+    let length = match vec.first() {
+        Some(&length) => length as usize,
+        None => return Err("name is missing".to_string())
+    };
+    match vec.get(1..1+length) {
+        Some(name) => Ok((String::from_utf8_lossy(name).to_string(), 1 + length)),
+        None => Err("name is truncated".to_string())
+    }
+}
+
+/* an index: one byte, or two when the high bit of the first is set;
+ * returns it and the number of bytes read */
+pub fn read_index(vec: &[u8]) -> Result<(usize, usize), String> {
+    match vec.first() {
+        Some(&high) if high & 0x80 != 0 => match vec.get(1) {
+            Some(&low) => Ok((((high & 0x7f) as usize) << 8 | low as usize, 2)),
+            None => Err("index is truncated".to_string())
+        },
+        Some(&index) => Ok((index as usize, 1)),
+        None => Err("index is missing".to_string())
+    }
+}
+
+/* a COMDEF style length: 0-80H in one byte, or 81H, 84H or 88H followed
+ * by 2, 3 or 4 bytes; returns the value and the number of bytes read */
+pub fn read_length(vec: &[u8]) -> Option<(u32, usize)> {
+    let size = match *vec.first()? {
+        b @ 0 ..= 0x80 => return Some((b as u32, 1)),
+        0x81 => 2,
+        0x84 => 3,
+        0x88 => 4,
+        _ => return None
+    };
+    let bytes = vec.get(1..1+size)?;
+    let value = bytes.iter().rev().fold(0u32, |v, &b| (v << 8) | b as u32);
+    Some((value, 1 + size))
+}
+
+/* a 2 byte or, in the 32-bit record forms, 4 byte number */
+pub fn read_offset(vec: &[u8], is32: bool) -> u32 {
+    if is32 {
+        (read_u16(vec) as u32) + 0x10000 * (read_u16(&vec[2..]) as u32)
+    } else {
+        read_u16(vec) as u32
+    }
+}
+
+/* expands iterated data blocks: repeat count, block count, and either
+ * nested blocks or a count byte followed by the content */
+pub fn expand_iterated(vec: &[u8], is32: bool) -> Result<Vec<u8>, String> {
+    let mut result = Vec::new();
+    let mut i = 0;
+    while i < vec.len() {
+        i += expand_block(&vec[i..], is32, &mut result, 0)?;
+    }
+    Ok(result)
+}
+
+fn expand_block(vec: &[u8], is32: bool, result: &mut Vec<u8>, depth: usize)
+        -> Result<usize, String> {
+    let count_size = if is32 { 4 } else { 2 };
+    if depth > 16 {
+        return Err("iterated data nested too deep".to_string());
+    }
+    if vec.len() < count_size + 2 {
+        return Err("iterated data block is truncated".to_string());
+    }
+    let repeat = read_offset(vec, is32) as usize;
+    let blocks = read_u16(&vec[count_size..]) as usize;
+    let mut i = count_size + 2;
+    let mut block = Vec::new();
+    if blocks == 0 {
+        let length = *vec.get(i).ok_or("iterated data content is truncated")? as usize;
+        let content = vec.get(i+1..i+1+length).ok_or("iterated data content is truncated")?;
+        block.extend_from_slice(content);
+        i += 1 + length;
+    } else {
+        for _ in 0..blocks {
+            i += expand_block(&vec[i..], is32, &mut block, depth + 1)?;
+        }
+    }
+    if repeat * block.len() > 0x1000000 {
+        return Err("iterated data is too large".to_string());
+    }
+    for _ in 0..repeat {
+        result.extend_from_slice(&block);
+    }
+    Ok(i)
+}
+
+/* the record type of a type byte, None for the ones we do not know */
+pub fn record_type(num: u8) -> Option<RecordType> {
+    Some(match num {
+        0x6e => RecordType::RHEADR,
         0x70 => RecordType::REGINT,
+        0x76 => RecordType::OVLDEF,
+        0x78 => RecordType::ENDREC,
         0x7a => RecordType::BLKDEF,
         0x7c => RecordType::BLKEND,
         0x7e => RecordType::DEBSYM,
         0x80 => RecordType::THEADR,
+        0x82 => RecordType::LHEADR,
+        0x84 => RecordType::PEDATA,
+        0x86 => RecordType::PIDATA,
         0x88 => RecordType::COMENT,
         0x8a => RecordType::MODEND,
+        0x8b => RecordType::MODEND32,
         0x8c => RecordType::EXTDEF,
         0x8e => RecordType::TYPDEF,
         0x90 => RecordType::PUBDEF,
+        0x91 => RecordType::PUBDEF32,
+        0x92 => RecordType::LOCSYM,
         0x94 => RecordType::LINNUM,
         0x96 => RecordType::LNAMES,
         0x98 => RecordType::SEGDEF,
+        0x99 => RecordType::SEGDEF32,
         0x9a => RecordType::GRPDEF,
         0x9c => RecordType::FIXUPP,
+        0x9d => RecordType::FIXUPP32,
         0xa0 => RecordType::LEDATA,
-        n => panic!("Bad record type: {:02X}H", n)
+        0xa1 => RecordType::LEDATA32,
+        0xa2 => RecordType::LIDATA,
+        0xa3 => RecordType::LIDATA32,
+        0xa4 => RecordType::LIBHED,
+        0xb0 => RecordType::COMDEF,
+        0xb2 => RecordType::BAKPAT,
+        0xb3 => RecordType::BAKPAT32,
+        0xb4 | 0xb5 => RecordType::LEXTDEF,
+        0xb6 => RecordType::LPUBDEF,
+        0xb7 => RecordType::LPUBDEF32,
+        0xb8 => RecordType::LCOMDEF,
+        0xbc => RecordType::CEXTDEF,
+        0xc2 => RecordType::COMDAT,
+        0xc3 => RecordType::COMDAT32,
+        0xc4 => RecordType::LINSYM,
+        0xc5 => RecordType::LINSYM32,
+        0xc6 => RecordType::ALIAS,
+        0xc8 => RecordType::NBKPAT,
+        0xc9 => RecordType::NBKPAT32,
+        0xca => RecordType::LLNAMES,
+        0xcc => RecordType::VERNUM,
+        0xce => RecordType::VENDEXT,
+        _ => return None
+    })
+}
+
+/* stops at the end, or at a truncated record, a record of an unknown type
+ * or a bad checksum, and then tells why with error() */
+pub struct ObjReader<R: Read = File> {
+    file: R,
+    error: Option<String>
+}
+
+impl<R: Read> ObjReader<R> {
+    /* why the reader stopped before the end, if it did */
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
     }
+
+    fn record(&mut self) -> Result<Option<ObjectRecord>, String> {
+        let (t, data, checksum_ok) = match read_raw_record(&mut self.file)? {
+            Some(record) => record,
+            None => return Ok(None)
+        };
+        let rtype = record_type(t).ok_or_else(|| format!("bad record type {:02X}H", t))?;
+        if !checksum_ok {
+            return Err(format!("bad checksum in record type {:?}", rtype));
+        }
+        Ok(Some(ObjectRecord { rtype, data }))
+    }
+}
+
+/* whether the bytes of a whole record sum to 0 modulo 256; translators
+ * and librarians may also leave the checksum byte at 0 */
+pub fn checksum_ok(record: &[u8]) -> bool {
+    let sum = record.iter().fold(0u8, |s, &b| s.wrapping_add(b));
+    sum == 0 || record.last() == Some(&0)
 }
 
-pub struct ObjReader {
-    file: File
+/* reads the type, length, contents and checksum of a record; returns the
+ * type, the contents and whether the checksum is right, or nothing at the
+ * end of the file */
+pub fn read_raw_record<R: Read>(file: &mut R) -> Result<Option<(u8, Vec<u8>, bool)>, String> {
+    let mut header = [0u8; 3];
+    match file.read(&mut header[..1]) {
+        Ok(0) => return Ok(None),
+        Ok(_) => {},
+        Err(e) => return Err(e.to_string())
+    }
+    let rtype = header[0];
+    let truncated = |_| format!("record of type {:02X}H is truncated", rtype);
+    file.read_exact(&mut header[1..]).map_err(truncated)?;
+    let record_length = read_u16(&header[1..]) as usize;
+    if record_length == 0 {
+        return Err(format!("record of type {:02X}H has no checksum", rtype));
+    }
+
+    /* the contents and the checksum byte */
+    let mut vec = vec![0u8; record_length];
+    file.read_exact(vec.as_mut_slice()).map_err(truncated)?;
+    let mut record = header.to_vec();
+    record.extend_from_slice(&vec);
+    vec.pop();
+
+    Ok(Some((rtype, vec, checksum_ok(&record))))
 }
 
-impl Iterator for ObjReader {
+impl<R: Read> Iterator for ObjReader<R> {
     type Item = ObjectRecord;
 
     fn next(&mut self) -> Option<ObjectRecord> {
-
-        let mut buf = [0u8; 1];
-        if self.file.read(&mut buf).unwrap() == 0 {
+        if self.error.is_some() {
             return None;
         }
-        let rtype = const_to_type(buf[0]);
-        let mut sum: u32 = buf[0] as u32;
+        match self.record() {
+            Ok(record) => record,
+            Err(reason) => {
+                self.error = Some(reason);
+                None
+            }
+        }
+    }
+}
 
-        if self.file.read(&mut buf).unwrap() == 0 {
-            return None;
+/* a record of a SliceReader, borrowing its contents */
+pub struct RecordRef<'a> {
+    pub rtype: RecordType,
+    pub data: &'a [u8]
+}
+
+impl<'a> RecordRef<'a> {
+    pub fn to_record(&self) -> ObjectRecord {
+        ObjectRecord { rtype: self.rtype, data: self.data.to_vec() }
+    }
+}
+
+/* reads the records of an object module that is already in memory without
+ * copying them; stops at the end, or at a truncated record, a record of an
+ * unknown type or a bad checksum, and then tells why with error() */
+pub struct SliceReader<'a> {
+    vec: &'a [u8],
+    offset: usize,
+    error: Option<String>
+}
+
+pub fn make_slice_reader(vec: &[u8]) -> SliceReader<'_> {
+    SliceReader { vec, offset: 0, error: None }
+}
+
+impl<'a> SliceReader<'a> {
+    /* where the next record starts */
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /* why the reader stopped before the end, if it did */
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    fn record(&self) -> Result<Option<RecordRef<'a>>, String> {
+        let vec = &self.vec[self.offset..];
+        if vec.is_empty() {
+            return Ok(None);
         }
-        let record_length = buf[0] as usize;
-        sum += buf[0] as u32;
-    
-        if self.file.read(&mut buf).unwrap() == 0 {
-            return None;
+        let truncated = || format!("record at offset {} is truncated", self.offset);
+        let record_length = read_u16(vec.get(1..3).ok_or_else(truncated)?) as usize;
+        if record_length == 0 {
+            return Err(truncated());
         }
-        let record_length = record_length + (buf[0] as usize) * 0x100;
-        sum += buf[0] as u32;
-
-        let mut vec = vec![0u8; record_length - 1];
-        let count = self.file.read(vec.as_mut_slice()).unwrap();
-        if count < record_length - 1 {
-            return None;
+        let record = vec.get(..3 + record_length).ok_or_else(truncated)?;
+        let rtype = match record_type(record[0]) {
+            Some(rtype) => rtype,
+            None => return Err(format!("bad record type {:02X}H at offset {}", record[0],
+                    self.offset))
+        };
+        if !checksum_ok(record) {
+            return Err(format!("bad checksum in record type {:?} at offset {}", rtype,
+                    self.offset));
         }
-        let vecsum: u32 = vec.iter().fold(0u32, |mut s, &v| {s += v as u32; s});
-        sum += vecsum;
+        Ok(Some(RecordRef { rtype, data: &record[3..record.len()-1] }))
+    }
+}
 
-        if self.file.read(&mut buf).unwrap() == 0 {
+impl<'a> Iterator for SliceReader<'a> {
+    type Item = RecordRef<'a>;
+
+    fn next(&mut self) -> Option<RecordRef<'a>> {
+        if self.error.is_some() {
             return None;
         }
-        sum += buf[0] as u32;
-
-        if sum % 0x100 != 0 {
-            panic!("bad checksum in record type {:?}", rtype);
+        match self.record() {
+            Ok(record) => {
+                let record = record?;
+                self.offset += record.data.len() + 4;
+                Some(record)
+            },
+            Err(reason) => {
+                self.error = Some(reason);
+                None
+            }
         }
-
-        Some(ObjectRecord { rtype: rtype, data: vec })
     }
 }
 
-pub fn make_obj_reader(mut file: File) -> Result<ObjReader, String> {
-    /* check first byte is 80 */
-    let mut buffer = [0; 1];
-    file.read(&mut buffer).unwrap();
-    if buffer[0] != 0x80 {
+/* how much of a file is read to check what it is */
+const DETECTION_PREFIX: u64 = 0x10000;
+
+pub fn make_obj_reader<R: Read + Seek>(mut file: R) -> Result<ObjReader<R>, String> {
+    /* check the start of the file is an OMF-86 module: it starts with
+     * THEADR, LHEADR or RHEADR, and has no record of an unknown type */
+    let start = file.stream_position().map_err(|e| e.to_string())?;
+    let mut prefix = Vec::new();
+    if file.by_ref().take(DETECTION_PREFIX).read_to_end(&mut prefix).is_err() {
         return Err("bad file".to_string());
     }
-    file.seek(SeekFrom::Start(0)).unwrap();
-    println!("file ok");
+    let detection = detect(&prefix);
+    match detection.format {
+        Format::Relocatable | Format::Absolute if detection.confidence > Confidence::Low => {},
+        _ => return Err(detection.to_string())
+    }
+    file.seek(SeekFrom::Start(start)).map_err(|e| e.to_string())?;
+
+    Ok(ObjReader { file, error: None })
+}
+
+#[test]
+fn index() {
+    assert_eq!(read_index(&[0x7f, 0x01]), Ok((0x7f, 1)));
+    assert_eq!(read_index(&[0x81, 0x02]), Ok((0x102, 2)));
+    assert!(read_index(&[0x81]).is_err());
+    assert!(read_index(&[]).is_err());
+}
+
+#[test]
+fn readers() {
+    use std::io::Cursor;
+
+    let vec = vec![0x80, 0x03, 0x00, 0x01, 0x41, 0x3b,
+            0x8a, 0x02, 0x00, 0x00, 0x74];
+    let records: Vec<ObjectRecord> = make_obj_reader(Cursor::new(vec.clone())).unwrap()
+            .collect();
+    assert_eq!(records.iter().map(|r| r.rtype).collect::<Vec<RecordType>>(),
+            vec![RecordType::THEADR, RecordType::MODEND]);
+    assert_eq!(records[0].data, vec![0x01, 0x41]);
+    let mut embedded = Cursor::new([vec![0xff], vec.clone()].concat());
+    embedded.set_position(1);
+    assert_eq!(make_obj_reader(embedded).unwrap().count(), 2);
+
+    let mut reader = make_slice_reader(&vec);
+    let theadr = reader.next().unwrap();
+    assert_eq!((theadr.rtype, theadr.data), (RecordType::THEADR, &vec[3..5]));
+    assert_eq!(reader.offset(), 6);
+    assert_eq!(reader.next().unwrap().to_record().data, vec![0x00]);
+    assert!(reader.next().is_none());
+    let mut reader = make_slice_reader(&vec[..8]);
+    assert!(reader.nth(1).is_none());
+    assert_eq!(reader.error(), Some("record at offset 6 is truncated"));
+
+    /* a bad record stops the reader, which tells why */
+    let mut bad = vec.clone();
+    bad[5] = 0x3c;
+    let mut reader = make_slice_reader(&bad);
+    assert!(reader.next().is_none());
+    assert_eq!(reader.error(), Some("bad checksum in record type THEADR at offset 0"));
+    assert!(reader.next().is_none());
+    bad[0] = 0x02;
+    let mut reader = make_slice_reader(&bad);
+    assert!(reader.next().is_none());
+    assert_eq!(reader.error(), Some("bad record type 02H at offset 0"));
+    let mut bad = vec.clone();
+    bad[10] = 0x75;
+    let mut reader = make_obj_reader(Cursor::new(bad.clone())).unwrap();
+    assert_eq!(reader.by_ref().count(), 1);
+    assert_eq!(reader.error(), Some("bad checksum in record type MODEND"));
+    bad[6] = 0x02;
+    let mut reader = make_obj_reader(Cursor::new(bad)).unwrap();
+    assert_eq!(reader.by_ref().count(), 1);
+    assert_eq!(reader.error(), Some("bad record type 02H"));
+
+    /* a checksum byte of 0 is accepted by both readers */
+    let mut unchecked = vec.clone();
+    unchecked[10] = 0;
+    assert_eq!(make_obj_reader(Cursor::new(unchecked.clone())).unwrap().count(), 2);
+    assert_eq!(make_slice_reader(&unchecked).count(), 2);
 
-    Ok(ObjReader { file: file })
+    /* Intel libraries and files with unknown record types are refused */
+    let mut unknown = vec.clone();
+    unknown.extend_from_slice(&[0x02, 0x01, 0x00, 0xfd]);
+    assert!(make_obj_reader(Cursor::new(unknown)).is_err());
+    assert!(make_obj_reader(Cursor::new(vec![0xa4, 0x01, 0x00, 0x5b])).is_err());
 }
@@ -0,0 +1,362 @@
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::Read;
+use std::io::SeekFrom;
+
+use ::objrec::*;
+
+/* records of the OMF-51 object format of the 8051 tools */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RecordType51 {
+    MODHDR,
+    MODEND,
+    CONTENT,
+    FIXUP,
+    SEGDEF,
+    SCOPDEF,
+    DEBITEM,
+    PUBDEF,
+    EXTDEF
+}
+
+pub struct Record51 {
+    pub rtype: RecordType51,
+    pub data: Vec<u8>
+}
+
+pub fn record_type51(num: u8) -> Option<RecordType51> {
+    Some(match num {
+        0x02 => RecordType51::MODHDR,
+        0x04 => RecordType51::MODEND,
+        0x06 => RecordType51::CONTENT,
+        0x08 => RecordType51::FIXUP,
+        0x0e => RecordType51::SEGDEF,
+        0x10 => RecordType51::SCOPDEF,
+        0x12 => RecordType51::DEBITEM,
+        0x16 => RecordType51::PUBDEF,
+        0x18 => RecordType51::EXTDEF,
+        _ => return None
+    })
+}
+
+pub struct Omf51Reader<R: Read = File> {
+    file: R
+}
+
+impl<R: Read> Iterator for Omf51Reader<R> {
+    type Item = Record51;
+
+    fn next(&mut self) -> Option<Record51> {
+        let (t, data, checksum_ok) = read_raw_record(&mut self.file).ok().flatten()?;
+        let rtype = match record_type51(t) {
+            Some(rtype) => rtype,
+            None => panic!("Bad OMF-51 record type: {:02X}H", t)
+        };
+        if !checksum_ok {
+            panic!("bad checksum in record type {:?}", rtype);
+        }
+        Some(Record51 { rtype, data })
+    }
+}
+
+pub fn make_omf51_reader<R: Read + Seek>(mut file: R) -> Result<Omf51Reader<R>, String> {
+    /* check first byte is a module header */
+    let mut buffer = [0; 1];
+    if file.read(&mut buffer).unwrap() == 0 || buffer[0] != 0x02 {
+        return Err("bad file".to_string());
+    }
+    file.seek(SeekFrom::Start(0)).unwrap();
+
+    Ok(Omf51Reader { file })
+}
+
+/* the address space of a segment or a symbol; NUMBER is for symbols only */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Space {
+    Code,
+    Xdata,
+    Data,
+    Idata,
+    Bit,
+    Number,
+    Other(u8)
+}
+
+fn space(info: u8) -> Space {
+    match info & 7 {
+        0 => Space::Code,
+        1 => Space::Xdata,
+        2 => Space::Data,
+        3 => Space::Idata,
+        4 => Space::Bit,
+        5 => Space::Number,
+        n => Space::Other(n)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Modhdr51 {
+    pub name: String,
+    /* FDH ASM51, FEH PL/M-51, FFH RL51 */
+    pub translator: u8
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Modend51 {
+    pub name: String,
+    /* bit n is set when register bank n is used */
+    pub register_banks: u8
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segdef51 {
+    pub id: u8,
+    pub space: Space,
+    pub register_bank: u8,
+    pub overlayable: bool,
+    pub empty: bool,
+    /* 0 absolute, 1 unit, 2 bit addressable, 3 inpage, 4 inblock, 5 page */
+    pub relocation: u8,
+    pub base: u16,
+    pub size: u16,
+    pub name: String
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Content51 {
+    pub segment: u8,
+    pub offset: u16,
+    pub data: Vec<u8>
+}
+
+/* what a fixup refers to */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operand51 {
+    Segment(u8),
+    External(u8),
+    Absolute,
+    Other(u8, u8)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fixup51 {
+    pub location: u16,
+    /* 0 low byte, 1 byte, 2 relative byte, 3 high byte, 4 word, 5 inblock,
+     * 6 bit, 7 conversion */
+    pub kind: u8,
+    pub operand: Operand51,
+    pub offset: u16
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScopeKind {
+    BeginModule,
+    BeginProcedure,
+    BeginDo,
+    EndModule,
+    EndProcedure,
+    EndDo,
+    Other(u8)
+}
+
+/* a symbol of a public, debug item or external record */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol51 {
+    /* the segment, or the external index for externals */
+    pub id: u8,
+    pub space: Space,
+    pub register_bank: u8,
+    pub variable: bool,
+    pub indirect: bool,
+    pub offset: u16,
+    pub name: String
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DebugItems {
+    Locals(Vec<Symbol51>),
+    Publics(Vec<Symbol51>),
+    Segments(Vec<Symbol51>),
+    /* segment, offset and line number */
+    Lines(Vec<(u8, u16, u16)>),
+    Other(u8)
+}
+
+fn byte(vec: &[u8], i: usize) -> Result<u8, String> {
+    vec.get(i).cloned().ok_or_else(|| "record is truncated".to_string())
+}
+
+fn word(vec: &[u8], i: usize) -> Result<u16, String> {
+    match vec.get(i..i+2) {
+        Some(bytes) => Ok(read_u16(bytes)),
+        None => Err("record is truncated".to_string())
+    }
+}
+
+fn name(vec: &[u8], i: usize) -> Result<(String, usize), String> {
+    read_name(&vec[i.min(vec.len())..])
+}
+
+pub fn read_modhdr51(vec: &[u8]) -> Result<Modhdr51, String> {
+    let (name, n) = name(vec, 0)?;
+    Ok(Modhdr51 { name, translator: byte(vec, n)? })
+}
+
+pub fn read_modend51(vec: &[u8]) -> Result<Modend51, String> {
+    let (name, n) = name(vec, 0)?;
+    Ok(Modend51 { name, register_banks: byte(vec, n + 2)? })
+}
+
+pub fn read_segdef51(vec: &[u8]) -> Result<Vec<Segdef51>, String> {
+    let mut result = Vec::new();
+    let mut i = 0;
+    while i < vec.len() {
+        let info = byte(vec, i + 1)?;
+        let (name, n) = name(vec, i + 8)?;
+        result.push(Segdef51 {
+            id: vec[i],
+            space: space(info),
+            register_bank: (info >> 3) & 3,
+            overlayable: info & 0x20 != 0,
+            empty: info & 0x80 != 0,
+            relocation: byte(vec, i + 2)?,
+            base: word(vec, i + 4)?,
+            size: word(vec, i + 6)?,
+            name
+        });
+        i += 8 + n;
+    }
+    Ok(result)
+}
+
+pub fn read_content51(vec: &[u8]) -> Result<Content51, String> {
+    Ok(Content51 {
+        segment: byte(vec, 0)?,
+        offset: word(vec, 1)?,
+        data: vec[3..].to_vec()
+    })
+}
+
+/* fixups of the preceding content record, 7 bytes each */
+pub fn read_fixup51(vec: &[u8]) -> Result<Vec<Fixup51>, String> {
+    if !vec.len().is_multiple_of(7) {
+        return Err("record is truncated".to_string());
+    }
+    Ok(vec.chunks(7).map(|f| Fixup51 {
+        location: read_u16(f),
+        kind: f[2],
+        operand: match f[3] {
+            0 => Operand51::Segment(f[4]),
+            1 => Operand51::External(f[4]),
+            2 => Operand51::Absolute,
+            n => Operand51::Other(n, f[4])
+        },
+        offset: read_u16(&f[5..])
+    }).collect())
+}
+
+pub fn read_scopdef(vec: &[u8]) -> Result<(ScopeKind, String), String> {
+    let kind = match byte(vec, 0)? {
+        0 => ScopeKind::BeginModule,
+        1 => ScopeKind::BeginProcedure,
+        2 => ScopeKind::BeginDo,
+        3 => ScopeKind::EndModule,
+        4 => ScopeKind::EndProcedure,
+        5 => ScopeKind::EndDo,
+        n => ScopeKind::Other(n)
+    };
+    let (name, _) = name(vec, 1)?;
+    Ok((kind, name))
+}
+
+/* id, information, offset, a reserved byte and the name */
+fn read_symbols51(vec: &[u8]) -> Result<Vec<Symbol51>, String> {
+    let mut result = Vec::new();
+    let mut i = 0;
+    while i < vec.len() {
+        let info = byte(vec, i + 1)?;
+        let (name, n) = name(vec, i + 5)?;
+        result.push(Symbol51 {
+            id: vec[i],
+            space: space(info),
+            register_bank: (info >> 3) & 3,
+            variable: info & 0x20 != 0,
+            indirect: info & 0x40 != 0,
+            offset: word(vec, i + 2)?,
+            name
+        });
+        i += 5 + n;
+    }
+    Ok(result)
+}
+
+pub fn read_pubdef51(vec: &[u8]) -> Result<Vec<Symbol51>, String> {
+    read_symbols51(vec)
+}
+
+/* the block byte, external index, information, a reserved byte and the
+ * name; there is no offset */
+pub fn read_extdef51(vec: &[u8]) -> Result<Vec<Symbol51>, String> {
+    let mut result = Vec::new();
+    let mut i = 0;
+    while i < vec.len() {
+        let info = byte(vec, i + 2)?;
+        let (name, n) = name(vec, i + 4)?;
+        result.push(Symbol51 {
+            id: vec[i + 1],
+            space: space(info),
+            register_bank: (info >> 3) & 3,
+            variable: info & 0x20 != 0,
+            indirect: info & 0x40 != 0,
+            offset: 0,
+            name
+        });
+        i += 4 + n;
+    }
+    Ok(result)
+}
+
+pub fn read_debitem(vec: &[u8]) -> Result<DebugItems, String> {
+    let items = &vec[1.min(vec.len())..];
+    Ok(match byte(vec, 0)? {
+        0 => DebugItems::Locals(read_symbols51(items)?),
+        1 => DebugItems::Publics(read_symbols51(items)?),
+        2 => DebugItems::Segments(read_symbols51(items)?),
+        3 => {
+            if !items.len().is_multiple_of(5) {
+                return Err("record is truncated".to_string());
+            }
+            DebugItems::Lines(items.chunks(5)
+                    .map(|l| (l[0], read_u16(&l[1..]), read_u16(&l[3..])))
+                    .collect())
+        },
+        n => DebugItems::Other(n)
+    })
+}
+
+#[test]
+fn omf51_definitions() {
+    let segdefs = read_segdef51(&[0x01, 0x28, 0x00, 0x00, 0x00, 0x00, 0x10, 0x00,
+            0x03, 0x53, 0x45, 0x47]).unwrap();
+    assert_eq!(segdefs, vec![Segdef51 { id: 1, space: Space::Code, register_bank: 1,
+            overlayable: true, empty: false, relocation: 0, base: 0, size: 0x10,
+            name: "SEG".to_string() }]);
+    let publics = read_pubdef51(&[0x01, 0x02, 0x04, 0x00, 0x00, 0x01, 0x58]).unwrap();
+    assert_eq!((publics[0].space, publics[0].offset, publics[0].name.as_str()),
+            (Space::Data, 4, "X"));
+    let externals = read_extdef51(&[0x02, 0x00, 0x40, 0x00, 0x01, 0x46]).unwrap();
+    assert_eq!((externals[0].id, externals[0].indirect), (0, true));
+    assert!(read_segdef51(&[0x01, 0x28, 0x00]).is_err());
+}
+
+#[test]
+fn omf51_fixups_and_debug_items() {
+    let fixups = read_fixup51(&[0x01, 0x00, 0x04, 0x01, 0x02, 0x00, 0x00]).unwrap();
+    assert_eq!(fixups, vec![Fixup51 { location: 1, kind: 4,
+            operand: Operand51::External(2), offset: 0 }]);
+    assert!(read_fixup51(&[0x01, 0x00]).is_err());
+    assert_eq!(read_debitem(&[0x03, 0x01, 0x10, 0x00, 0x0a, 0x00]).unwrap(),
+            DebugItems::Lines(vec![(1, 0x10, 10)]));
+    assert_eq!(read_scopdef(&[0x01, 0x01, 0x46]).unwrap(),
+            (ScopeKind::BeginProcedure, "F".to_string()));
+}
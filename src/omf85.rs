@@ -0,0 +1,337 @@
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::Read;
+use std::io::SeekFrom;
+
+use ::objrec::*;
+
+/* records of the OMF-85 object format of ISIS-II */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RecordType85 {
+    MODHDR,
+    MODEND,
+    CONTENT,
+    LINNUM,
+    EOF,
+    ANCESTOR,
+    LOCALS,
+    PUBLICS,
+    EXTNAMES,
+    EXTREF,
+    RELOC,
+    INTERSEG,
+    COMNAMES
+}
+
+pub struct Record85 {
+    pub rtype: RecordType85,
+    pub data: Vec<u8>
+}
+
+pub fn record_type85(num: u8) -> Option<RecordType85> {
+    Some(match num {
+        0x02 => RecordType85::MODHDR,
+        0x04 => RecordType85::MODEND,
+        0x06 => RecordType85::CONTENT,
+        0x08 => RecordType85::LINNUM,
+        0x0e => RecordType85::EOF,
+        0x10 => RecordType85::ANCESTOR,
+        0x12 => RecordType85::LOCALS,
+        0x16 => RecordType85::PUBLICS,
+        0x18 => RecordType85::EXTNAMES,
+        0x20 => RecordType85::EXTREF,
+        0x22 => RecordType85::RELOC,
+        0x24 => RecordType85::INTERSEG,
+        0x2e => RecordType85::COMNAMES,
+        _ => return None
+    })
+}
+
+pub struct Omf85Reader<R: Read = File> {
+    file: R
+}
+
+impl<R: Read> Iterator for Omf85Reader<R> {
+    type Item = Record85;
+
+    fn next(&mut self) -> Option<Record85> {
+        let (t, data, checksum_ok) = read_raw_record(&mut self.file).ok().flatten()?;
+        let rtype = match record_type85(t) {
+            Some(rtype) => rtype,
+            None => panic!("Bad OMF-85 record type: {:02X}H", t)
+        };
+        if !checksum_ok {
+            panic!("bad checksum in record type {:?}", rtype);
+        }
+        Some(Record85 { rtype, data })
+    }
+}
+
+pub fn make_omf85_reader<R: Read + Seek>(mut file: R) -> Result<Omf85Reader<R>, String> {
+    /* check first byte is a module header */
+    let mut buffer = [0; 1];
+    if file.read(&mut buffer).unwrap() == 0 || buffer[0] != 0x02 {
+        return Err("bad file".to_string());
+    }
+    file.seek(SeekFrom::Start(0)).unwrap();
+
+    Ok(Omf85Reader { file })
+}
+
+/* segment ids: 0 absolute, 1 code, 2 data, 3 stack, 4 memory, 5 reserved,
+ * 6 to 254 named common and 255 blank common */
+pub const ABSOLUTE: u8 = 0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Modhdr85 {
+    pub name: String,
+    /* 0 unspecified, 1 PL/M-80, 2 FORTRAN-80, 3 ASM80 */
+    pub translator: u8,
+    pub version: u8
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Modend85 {
+    pub main: bool,
+    /* start address of a main module */
+    pub segment: u8,
+    pub offset: u16
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Content85 {
+    pub segment: u8,
+    pub offset: u16,
+    pub data: Vec<u8>
+}
+
+/* which bytes of an address a relocation or reference patches */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Patch {
+    Low,
+    High,
+    Both,
+    Other(u8)
+}
+
+/* the names and offsets of a PUBLICS or LOCALS record */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbols85 {
+    pub segment: u8,
+    pub symbols: Vec<(u16, String)>
+}
+
+/* references to the name of an external or to a segment, at offsets of
+ * the preceding content record */
+#[derive(Debug, Clone, PartialEq)]
+pub struct References85 {
+    pub patch: Patch,
+    pub segment: Option<u8>,
+    /* external name index or 0, and offset */
+    pub offsets: Vec<(u16, u16)>
+}
+
+fn byte(vec: &[u8], i: usize) -> Result<u8, String> {
+    vec.get(i).cloned().ok_or_else(|| "record is truncated".to_string())
+}
+
+fn word(vec: &[u8], i: usize) -> Result<u16, String> {
+    match vec.get(i..i+2) {
+        Some(bytes) => Ok(read_u16(bytes)),
+        None => Err("record is truncated".to_string())
+    }
+}
+
+fn name(vec: &[u8], i: usize) -> Result<(String, usize), String> {
+    read_name(&vec[i.min(vec.len())..])
+}
+
+fn patch(b: u8) -> Patch {
+    match b {
+        1 => Patch::Low,
+        2 => Patch::High,
+        3 => Patch::Both,
+        n => Patch::Other(n)
+    }
+}
+
+pub fn read_modhdr85(vec: &[u8]) -> Result<Modhdr85, String> {
+    let (name, n) = name(vec, 0)?;
+    Ok(Modhdr85 { name, translator: byte(vec, n)?, version: byte(vec, n + 1)? })
+}
+
+pub fn read_modend85(vec: &[u8]) -> Result<Modend85, String> {
+    Ok(Modend85 {
+        main: byte(vec, 0)? == 1,
+        segment: byte(vec, 1)?,
+        offset: word(vec, 2)?
+    })
+}
+
+pub fn read_content85(vec: &[u8]) -> Result<Content85, String> {
+    Ok(Content85 {
+        segment: byte(vec, 0)?,
+        offset: word(vec, 1)?,
+        data: vec[3..].to_vec()
+    })
+}
+
+/* a segment, then offsets and names each followed by a zero byte */
+pub fn read_symbols85(vec: &[u8]) -> Result<Symbols85, String> {
+    let segment = byte(vec, 0)?;
+    let mut symbols = Vec::new();
+    let mut i = 1;
+    while i < vec.len() {
+        let offset = word(vec, i)?;
+        let (name, n) = name(vec, i + 2)?;
+        symbols.push((offset, name));
+        i += 2 + n + 1;
+    }
+    Ok(Symbols85 { segment, symbols })
+}
+
+/* names each followed by a zero byte; externals are numbered from 0 */
+pub fn read_extnames(vec: &[u8]) -> Result<Vec<String>, String> {
+    let mut result = Vec::new();
+    let mut i = 0;
+    while i < vec.len() {
+        let (name, n) = name(vec, i)?;
+        result.push(name);
+        i += n + 1;
+    }
+    Ok(result)
+}
+
+/* segment and line number pairs of a LINNUM record */
+pub fn read_linnum85(vec: &[u8]) -> Result<(u8, Vec<(u16, u16)>), String> {
+    let segment = byte(vec, 0)?;
+    if !(vec.len() - 1).is_multiple_of(4) {
+        return Err("record is truncated".to_string());
+    }
+    Ok((segment, vec[1..].chunks(4).map(|l| (read_u16(l), read_u16(&l[2..]))).collect()))
+}
+
+/* EXTREF has external index and offset pairs, RELOC and INTERSEG offsets,
+ * INTERSEG after the segment they refer to */
+pub fn read_references(rtype: RecordType85, vec: &[u8]) -> Result<References85, String> {
+    let mut i = 0;
+    let segment = if rtype == RecordType85::INTERSEG {
+        i += 1;
+        Some(byte(vec, 0)?)
+    } else {
+        None
+    };
+    let patch = patch(byte(vec, i)?);
+    i += 1;
+    let size = if rtype == RecordType85::EXTREF { 4 } else { 2 };
+    if !(vec.len() - i).is_multiple_of(size) {
+        return Err("record is truncated".to_string());
+    }
+    let offsets = vec[i..].chunks(size).map(|r| if size == 4 {
+        (read_u16(r), read_u16(&r[2..]))
+    } else {
+        (0, read_u16(r))
+    }).collect();
+    Ok(References85 { patch, segment, offsets })
+}
+
+/* segment ids and names of a COMNAMES record */
+pub fn read_comnames(vec: &[u8]) -> Result<Vec<(u8, String)>, String> {
+    let mut result = Vec::new();
+    let mut i = 0;
+    while i < vec.len() {
+        let (name, n) = name(vec, i + 1)?;
+        result.push((vec[i], name));
+        i += 1 + n;
+    }
+    Ok(result)
+}
+
+/* the contents and start address of an absolute module; fails when the
+ * module has relocatable contents or references */
+pub fn absolute_image(records: &[Record85]) -> Result<(Vec<Content85>, Option<u16>), String> {
+    let mut contents = Vec::new();
+    let mut start = None;
+    for orec in records {
+        match orec.rtype {
+            RecordType85::CONTENT => {
+                let content = read_content85(&orec.data)?;
+                if content.segment != ABSOLUTE {
+                    return Err(format!("contents of segment {} are relocatable",
+                            content.segment));
+                }
+                contents.push(content);
+            },
+            RecordType85::EXTREF | RecordType85::RELOC | RecordType85::INTERSEG =>
+                return Err("module has relocations".to_string()),
+            RecordType85::MODEND => {
+                let modend = read_modend85(&orec.data)?;
+                if modend.main {
+                    if modend.segment != ABSOLUTE {
+                        return Err("start address is relocatable".to_string());
+                    }
+                    start = Some(modend.offset);
+                }
+            },
+            _ => {}
+        }
+    }
+    Ok((contents, start))
+}
+
+fn hex_record(address: u16, rtype: u8, data: &[u8]) -> String {
+    let mut bytes = vec![data.len() as u8, (address >> 8) as u8, address as u8, rtype];
+    bytes.extend_from_slice(data);
+    let sum = bytes.iter().fold(0u8, |s, &b| s.wrapping_add(b));
+    bytes.push(sum.wrapping_neg());
+    let digits: Vec<String> = bytes.iter().map(|b| format!("{:02X}", b)).collect();
+    format!(":{}\n", digits.concat())
+}
+
+/* Intel HEX data records of 16 bytes at most, then the end record, which
+ * holds the start address as the 8080 loaders expect */
+pub fn intel_hex(contents: &[Content85], start: Option<u16>) -> String {
+    let mut result = String::new();
+    for content in contents {
+        for (i, line) in content.data.chunks(16).enumerate() {
+            let address = content.offset.wrapping_add(16 * i as u16);
+            result += &hex_record(address, 0, line);
+        }
+    }
+    result += &hex_record(start.unwrap_or(0), 1, &[]);
+    result
+}
+
+#[test]
+fn omf85_records() {
+    let publics = read_symbols85(&[0x01, 0x10, 0x00, 0x01, 0x41, 0x00,
+            0x20, 0x00, 0x01, 0x42, 0x00]).unwrap();
+    assert_eq!(publics, Symbols85 { segment: 1,
+            symbols: vec![(0x10, "A".to_string()), (0x20, "B".to_string())] });
+    assert_eq!(read_extnames(&[0x01, 0x58, 0x00, 0x01, 0x59, 0x00]).unwrap(),
+            vec!["X".to_string(), "Y".to_string()]);
+    let extref = read_references(RecordType85::EXTREF,
+            &[0x03, 0x01, 0x00, 0x04, 0x00]).unwrap();
+    assert_eq!((extref.patch, extref.offsets), (Patch::Both, vec![(1, 4)]));
+    let interseg = read_references(RecordType85::INTERSEG,
+            &[0x02, 0x01, 0x05, 0x00]).unwrap();
+    assert_eq!((interseg.segment, interseg.patch), (Some(2), Patch::Low));
+    assert!(read_references(RecordType85::RELOC, &[0x03, 0x01]).is_err());
+    assert_eq!(read_modend85(&[0x01, 0x00, 0x00, 0x01]).unwrap(),
+            Modend85 { main: true, segment: 0, offset: 0x100 });
+}
+
+#[test]
+fn hex_file() {
+    let records = vec![
+        Record85 { rtype: RecordType85::CONTENT, data: vec![0x00, 0x00, 0x01, 0xc3, 0x00, 0x01] },
+        Record85 { rtype: RecordType85::MODEND, data: vec![0x01, 0x00, 0x00, 0x01] }
+    ];
+    let (contents, start) = absolute_image(&records).unwrap();
+    assert_eq!(intel_hex(&contents, start),
+            ":03010000C3000138\n:00010001FE\n");
+    let relocatable = vec![
+        Record85 { rtype: RecordType85::CONTENT, data: vec![0x01, 0x00, 0x00, 0x00] }
+    ];
+    assert!(absolute_image(&relocatable).is_err());
+}
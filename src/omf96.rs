@@ -0,0 +1,138 @@
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::Read;
+use std::io::SeekFrom;
+
+use ::objrec::*;
+
+/* records of the OMF-96 object format of the 8096 tools; they are framed
+ * and checksummed like the other Intel formats. Only the types OMF-51 and
+ * OMF-85 agree on are named, the others are kept by type number */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RecordType96 {
+    MODHDR,
+    MODEND,
+    CONTENT,
+    PUBDEF,
+    EXTDEF,
+    Other(u8)
+}
+
+pub struct Record96 {
+    pub rtype: RecordType96,
+    pub data: Vec<u8>
+}
+
+pub fn record_type96(num: u8) -> RecordType96 {
+    match num {
+        0x02 => RecordType96::MODHDR,
+        0x04 => RecordType96::MODEND,
+        0x06 => RecordType96::CONTENT,
+        0x16 => RecordType96::PUBDEF,
+        0x18 => RecordType96::EXTDEF,
+        n => RecordType96::Other(n)
+    }
+}
+
+/* the start of a module header: the module name, then fields of the
+ * translator that are kept as bytes */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Modhdr96 {
+    pub name: String,
+    pub rest: Vec<u8>
+}
+
+/* the start of a content record: a segment id and an offset */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Content96 {
+    pub segment: u8,
+    pub offset: u16,
+    pub data: Vec<u8>
+}
+
+pub fn read_modhdr96(vec: &[u8]) -> Result<Modhdr96, String> {
+    let (name, n) = read_name(vec)?;
+    Ok(Modhdr96 { name, rest: vec[n..].to_vec() })
+}
+
+pub fn read_content96(vec: &[u8]) -> Result<Content96, String> {
+    if vec.len() < 3 {
+        return Err("record is truncated".to_string());
+    }
+    Ok(Content96 { segment: vec[0], offset: read_u16(&vec[1..]), data: vec[3..].to_vec() })
+}
+
+/* stops at the end, or at a truncated record or a bad checksum, and then
+ * tells why with error() */
+pub struct Omf96Reader<R: Read = File> {
+    file: R,
+    error: Option<String>
+}
+
+impl<R: Read> Omf96Reader<R> {
+    /* why the reader stopped before the end, if it did */
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+}
+
+impl<R: Read> Iterator for Omf96Reader<R> {
+    type Item = Record96;
+
+    fn next(&mut self) -> Option<Record96> {
+        if self.error.is_some() {
+            return None;
+        }
+        match read_raw_record(&mut self.file) {
+            Ok(Some((t, data, true))) => Some(Record96 { rtype: record_type96(t), data }),
+            Ok(Some((t, _, false))) => {
+                self.error = Some(format!("bad checksum in record type {:02X}H", t));
+                None
+            },
+            Ok(None) => None,
+            Err(reason) => {
+                self.error = Some(reason);
+                None
+            }
+        }
+    }
+}
+
+/* the file must start with a module header with a good checksum and a
+ * module name */
+pub fn make_omf96_reader<R: Read + Seek>(mut file: R) -> Result<Omf96Reader<R>, String> {
+    let start = file.stream_position().map_err(|e| e.to_string())?;
+    match read_raw_record(&mut file)? {
+        Some((0x02, data, true)) => { read_modhdr96(&data)?; },
+        Some((0x02, _, false)) => return Err("bad checksum in the module header".to_string()),
+        _ => return Err("no module header".to_string())
+    }
+    file.seek(SeekFrom::Start(start)).map_err(|e| e.to_string())?;
+
+    Ok(Omf96Reader { file, error: None })
+}
+
+#[test]
+fn omf96_records() {
+    use std::io::Cursor;
+
+    let file = vec![0x02, 0x03, 0x00, 0x01, 0x41, 0xb9,
+            0x06, 0x05, 0x00, 0x01, 0x10, 0x00, 0xff, 0xe5,
+            0x2a, 0x01, 0x00, 0xd5];
+    let mut reader = make_omf96_reader(Cursor::new(file.clone())).unwrap();
+    let records: Vec<Record96> = reader.by_ref().collect();
+    assert!(reader.error().is_none());
+    assert_eq!(records.iter().map(|r| r.rtype).collect::<Vec<RecordType96>>(),
+            vec![RecordType96::MODHDR, RecordType96::CONTENT, RecordType96::Other(0x2a)]);
+    assert_eq!(read_modhdr96(&records[0].data).unwrap().name, "A");
+    assert_eq!(read_content96(&records[1].data).unwrap(),
+            Content96 { segment: 1, offset: 0x10, data: vec![0xff] });
+
+    let mut bad = file.clone();
+    bad[13] = 0xe6;
+    let mut reader = make_omf96_reader(Cursor::new(bad)).unwrap();
+    assert_eq!(reader.by_ref().count(), 1);
+    assert_eq!(reader.error(), Some("bad checksum in record type 06H"));
+    assert!(make_omf96_reader(Cursor::new(vec![0x80])).is_err());
+    assert!(make_omf96_reader(Cursor::new(vec![0x02, 0x03, 0x00, 0x05, 0x41, 0xb5])).is_err());
+}
@@ -0,0 +1,233 @@
+use ::omf51::*;
+
+fn translator_to_string(translator: u8) -> &'static str {
+    match translator {
+        0xfd => "ASM51",
+        0xfe => "PL/M-51",
+        0xff => "RL51",
+        _ => "unknown"
+    }
+}
+
+fn space_to_string(space: Space) -> String {
+    match space {
+        Space::Code => "CODE".to_string(),
+        Space::Xdata => "XDATA".to_string(),
+        Space::Data => "DATA".to_string(),
+        Space::Idata => "IDATA".to_string(),
+        Space::Bit => "BIT".to_string(),
+        Space::Number => "NUMBER".to_string(),
+        Space::Other(n) => format!("<bad type {}>", n)
+    }
+}
+
+fn relocation_to_string(relocation: u8) -> String {
+    match relocation {
+        0 => "absolute".to_string(),
+        1 => "unit".to_string(),
+        2 => "bit addressable".to_string(),
+        3 => "inpage".to_string(),
+        4 => "inblock".to_string(),
+        5 => "page".to_string(),
+        n => format!("<bad relocation {}>", n)
+    }
+}
+
+fn fixup_kind_to_string(kind: u8) -> String {
+    match kind {
+        0 => "low byte".to_string(),
+        1 => "byte".to_string(),
+        2 => "relative byte".to_string(),
+        3 => "high byte".to_string(),
+        4 => "word".to_string(),
+        5 => "inblock".to_string(),
+        6 => "bit".to_string(),
+        7 => "conversion".to_string(),
+        n => format!("<bad type {}>", n)
+    }
+}
+
+/* externals have an index instead of a segment and an offset */
+fn print_symbol(symbol: &Symbol51, external: bool) {
+    print!("{}: {}", symbol.name, space_to_string(symbol.space));
+    if external {
+        print!(", external {}", symbol.id);
+    } else {
+        print!(", segment {}:{:04x}", symbol.id, symbol.offset);
+    }
+    if symbol.register_bank > 0 {
+        print!(", register bank {}", symbol.register_bank);
+    }
+    if symbol.variable {
+        print!(", variable");
+    }
+    if symbol.indirect {
+        print!(", indirectly callable");
+    }
+    println!();
+}
+
+fn print_symbols(title: &str, symbols: &[Symbol51]) {
+    println!("{}:", title);
+    for symbol in symbols {
+        print_symbol(symbol, false);
+    }
+}
+
+pub fn modhdr(orec: Record51) {
+    println!("Module Header Record");
+    println!("====================");
+    match read_modhdr51(&orec.data) {
+        Ok(modhdr) => {
+            println!("Module name: {}", modhdr.name);
+            println!("Translator: {} ({:02x})", translator_to_string(modhdr.translator),
+                    modhdr.translator);
+        },
+        Err(reason) => println!("undecodable module header: {}", reason)
+    }
+    println!();
+}
+
+pub fn modend(orec: Record51) {
+    println!("Module End Record");
+    println!("=================");
+    match read_modend51(&orec.data) {
+        Ok(modend) => {
+            println!("Module name: {}", modend.name);
+            print!("Register banks:");
+            for bank in (0..4).filter(|b| modend.register_banks & (1 << b) != 0) {
+                print!(" {}", bank);
+            }
+            println!();
+        },
+        Err(reason) => println!("undecodable module end: {}", reason)
+    }
+    println!();
+}
+
+pub fn segdef(orec: Record51) {
+    println!("Segment Definition Record");
+    println!("=========================");
+    match read_segdef51(&orec.data) {
+        Ok(segdefs) => for segdef in segdefs {
+            print!("{}: {}, {}, {}, base {:04x}, size {:04x}", segdef.id, segdef.name,
+                    space_to_string(segdef.space), relocation_to_string(segdef.relocation),
+                    segdef.base, segdef.size);
+            if segdef.register_bank > 0 {
+                print!(", register bank {}", segdef.register_bank);
+            }
+            if segdef.overlayable {
+                print!(", overlayable");
+            }
+            if segdef.empty {
+                print!(", empty");
+            }
+            println!();
+        },
+        Err(reason) => println!("undecodable segment definition: {}", reason)
+    }
+    println!();
+}
+
+pub fn content(orec: Record51) {
+    println!("Content Record");
+    println!("==============");
+    match read_content51(&orec.data) {
+        Ok(content) => {
+            println!("Segment: {}, offset: {:04x}", content.segment, content.offset);
+            print!("Data: ");
+            for (i, x) in content.data.iter().enumerate() {
+                if i > 0 && i % 16 == 0 {
+                    print!("\n      ");
+                }
+                print!(" {:02x}", x);
+            }
+            println!();
+        },
+        Err(reason) => println!("undecodable content: {}", reason)
+    }
+    println!();
+}
+
+pub fn fixup(orec: Record51) {
+    println!("Fixup Record");
+    println!("============");
+    match read_fixup51(&orec.data) {
+        Ok(fixups) => for fixup in fixups {
+            let operand = match fixup.operand {
+                Operand51::Segment(id) => format!("segment {}", id),
+                Operand51::External(id) => format!("external {}", id),
+                Operand51::Absolute => "absolute".to_string(),
+                Operand51::Other(block, id) => format!("<bad block {}> {}", block, id)
+            };
+            println!("{:04x}: {}, {}+{:04x}", fixup.location,
+                    fixup_kind_to_string(fixup.kind), operand, fixup.offset);
+        },
+        Err(reason) => println!("undecodable fixup: {}", reason)
+    }
+    println!();
+}
+
+pub fn scopdef(orec: Record51) {
+    println!("Scope Definition Record");
+    println!("=======================");
+    match read_scopdef(&orec.data) {
+        Ok((kind, name)) => {
+            let kind = match kind {
+                ScopeKind::BeginModule => "begin module".to_string(),
+                ScopeKind::BeginProcedure => "begin procedure".to_string(),
+                ScopeKind::BeginDo => "begin do".to_string(),
+                ScopeKind::EndModule => "end module".to_string(),
+                ScopeKind::EndProcedure => "end procedure".to_string(),
+                ScopeKind::EndDo => "end do".to_string(),
+                ScopeKind::Other(n) => format!("<bad block type {}>", n)
+            };
+            println!("{} {}", kind, name);
+        },
+        Err(reason) => println!("undecodable scope definition: {}", reason)
+    }
+    println!();
+}
+
+pub fn debitem(orec: Record51) {
+    println!("Debug Items Record");
+    println!("==================");
+    match read_debitem(&orec.data) {
+        Ok(DebugItems::Locals(symbols)) => print_symbols("Local symbols", &symbols),
+        Ok(DebugItems::Publics(symbols)) => print_symbols("Public symbols", &symbols),
+        Ok(DebugItems::Segments(symbols)) => print_symbols("Segment symbols", &symbols),
+        Ok(DebugItems::Lines(lines)) => {
+            println!("Line numbers:");
+            for (segment, offset, line) in lines {
+                println!("{}: segment {}:{:04x}", line, segment, offset);
+            }
+        },
+        Ok(DebugItems::Other(n)) => println!("<bad definition type {}>", n),
+        Err(reason) => println!("undecodable debug items: {}", reason)
+    }
+    println!();
+}
+
+pub fn pubdef(orec: Record51) {
+    println!("Public Definition Record");
+    println!("========================");
+    match read_pubdef51(&orec.data) {
+        Ok(symbols) => for symbol in &symbols {
+            print_symbol(symbol, false);
+        },
+        Err(reason) => println!("undecodable public definition: {}", reason)
+    }
+    println!();
+}
+
+pub fn extdef(orec: Record51) {
+    println!("External Definition Record");
+    println!("==========================");
+    match read_extdef51(&orec.data) {
+        Ok(symbols) => for symbol in &symbols {
+            print_symbol(symbol, true);
+        },
+        Err(reason) => println!("undecodable external definition: {}", reason)
+    }
+    println!();
+}
@@ -0,0 +1,191 @@
+use ::omf85::*;
+
+fn translator_to_string(translator: u8) -> &'static str {
+    match translator {
+        0 => "unspecified",
+        1 => "PL/M-80",
+        2 => "FORTRAN-80",
+        3 => "ASM80",
+        _ => "unknown"
+    }
+}
+
+fn segment_to_string(segment: u8) -> String {
+    match segment {
+        0 => "ABSOLUTE".to_string(),
+        1 => "CODE".to_string(),
+        2 => "DATA".to_string(),
+        3 => "STACK".to_string(),
+        4 => "MEMORY".to_string(),
+        5 => "<reserved>".to_string(),
+        255 => "blank COMMON".to_string(),
+        n => format!("COMMON {}", n)
+    }
+}
+
+fn patch_to_string(patch: Patch) -> String {
+    match patch {
+        Patch::Low => "low byte".to_string(),
+        Patch::High => "high byte".to_string(),
+        Patch::Both => "word".to_string(),
+        Patch::Other(n) => format!("<bad patch {}>", n)
+    }
+}
+
+pub fn modhdr(orec: Record85) {
+    println!("Module Header Record");
+    println!("====================");
+    match read_modhdr85(&orec.data) {
+        Ok(modhdr) => {
+            println!("Module name: {}", modhdr.name);
+            println!("Translator: {} ({}), version {:02x}",
+                    translator_to_string(modhdr.translator), modhdr.translator,
+                    modhdr.version);
+        },
+        Err(reason) => println!("undecodable module header: {}", reason)
+    }
+    println!();
+}
+
+pub fn modend(orec: Record85) {
+    println!("Module End Record");
+    println!("=================");
+    match read_modend85(&orec.data) {
+        Ok(modend) if modend.main => println!("Main module, start address: {}:{:04x}",
+                segment_to_string(modend.segment), modend.offset),
+        Ok(_) => println!("Not a main module"),
+        Err(reason) => println!("undecodable module end: {}", reason)
+    }
+    println!();
+}
+
+pub fn content(orec: Record85) {
+    println!("Content Record");
+    println!("==============");
+    match read_content85(&orec.data) {
+        Ok(content) => {
+            println!("Segment: {}, offset: {:04x}", segment_to_string(content.segment),
+                    content.offset);
+            print!("Data: ");
+            for (i, x) in content.data.iter().enumerate() {
+                if i > 0 && i % 16 == 0 {
+                    print!("\n      ");
+                }
+                print!(" {:02x}", x);
+            }
+            println!();
+        },
+        Err(reason) => println!("undecodable content: {}", reason)
+    }
+    println!();
+}
+
+pub fn linnum(orec: Record85) {
+    println!("Line Numbers Record");
+    println!("===================");
+    match read_linnum85(&orec.data) {
+        Ok((segment, lines)) => for (offset, line) in lines {
+            println!("{}: {}:{:04x}", line, segment_to_string(segment), offset);
+        },
+        Err(reason) => println!("undecodable line numbers: {}", reason)
+    }
+    println!();
+}
+
+pub fn eof(_orec: Record85) {
+    println!("End of File Record");
+    println!("==================");
+    println!();
+}
+
+pub fn ancestor(orec: Record85) {
+    println!("Ancestor Record");
+    println!("===============");
+    match ::objrec::read_name(&orec.data) {
+        Ok((name, _)) => println!("Module name: {}", name),
+        Err(reason) => println!("undecodable ancestor: {}", reason)
+    }
+    println!();
+}
+
+fn print_symbols(vec: &[u8]) {
+    match read_symbols85(vec) {
+        Ok(symbols) => for (offset, name) in symbols.symbols {
+            println!("{}: {}:{:04x}", name, segment_to_string(symbols.segment), offset);
+        },
+        Err(reason) => println!("undecodable symbols: {}", reason)
+    }
+}
+
+pub fn locals(orec: Record85) {
+    println!("Local Symbols Record");
+    println!("====================");
+    print_symbols(&orec.data);
+    println!();
+}
+
+pub fn publics(orec: Record85) {
+    println!("Public Declarations Record");
+    println!("==========================");
+    print_symbols(&orec.data);
+    println!();
+}
+
+pub fn extnames(orec: Record85) {
+    println!("External Names Record");
+    println!("=====================");
+    match read_extnames(&orec.data) {
+        Ok(names) => for (i, name) in names.iter().enumerate() {
+            println!("{}: {}", i, name);
+        },
+        Err(reason) => println!("undecodable external names: {}", reason)
+    }
+    println!();
+}
+
+pub fn references(orec: Record85) {
+    match orec.rtype {
+        RecordType85::EXTREF => {
+            println!("External References Record");
+            println!("==========================");
+        },
+        RecordType85::RELOC => {
+            println!("Relocation Record");
+            println!("=================");
+        },
+        _ => {
+            println!("Inter-segment References Record");
+            println!("===============================");
+        }
+    }
+    match read_references(orec.rtype, &orec.data) {
+        Ok(references) => {
+            print!("Patch: {}", patch_to_string(references.patch));
+            if let Some(segment) = references.segment {
+                print!(", segment: {}", segment_to_string(segment));
+            }
+            println!();
+            for (external, offset) in references.offsets {
+                if orec.rtype == RecordType85::EXTREF {
+                    println!("{:04x}: external {}", offset, external);
+                } else {
+                    println!("{:04x}", offset);
+                }
+            }
+        },
+        Err(reason) => println!("undecodable references: {}", reason)
+    }
+    println!();
+}
+
+pub fn comnames(orec: Record85) {
+    println!("Common Names Record");
+    println!("===================");
+    match read_comnames(&orec.data) {
+        Ok(names) => for (segment, name) in names {
+            println!("{}: {}", segment, name);
+        },
+        Err(reason) => println!("undecodable common names: {}", reason)
+    }
+    println!();
+}
@@ -0,0 +1,53 @@
+use ::omf96::*;
+
+fn print_bytes(label: &str, vec: &[u8]) {
+    print!("{}:", label);
+    for (i, x) in vec.iter().enumerate() {
+        if i > 0 && i % 16 == 0 {
+            print!("\n{:width$}", "", width = label.len() + 1);
+        }
+        print!(" {:02x}", x);
+    }
+    println!();
+}
+
+pub fn modhdr(orec: Record96) {
+    println!("Module Header Record (MODHDR)");
+    println!("=============================");
+    match read_modhdr96(&orec.data) {
+        Ok(modhdr) => {
+            println!("Module name: {}", modhdr.name);
+            print_bytes("Translator fields", &modhdr.rest);
+        },
+        Err(reason) => println!("undecodable module header: {}", reason)
+    }
+    println!();
+}
+
+pub fn content(orec: Record96) {
+    println!("Content Record (CONTENT)");
+    println!("========================");
+    match read_content96(&orec.data) {
+        Ok(content) => {
+            println!("Segment: {}, offset: {:04x}", content.segment, content.offset);
+            print_bytes("Data", &content.data);
+        },
+        Err(reason) => println!("undecodable content: {}", reason)
+    }
+    println!();
+}
+
+/* records whose layout is not decoded */
+pub fn other(orec: Record96) {
+    let title = match orec.rtype {
+        RecordType96::MODEND => "Module End Record (MODEND)".to_string(),
+        RecordType96::PUBDEF => "Public Definitions Record (PUBDEF)".to_string(),
+        RecordType96::EXTDEF => "External Definitions Record (EXTDEF)".to_string(),
+        RecordType96::Other(n) => format!("Record of type {:02X}H", n),
+        _ => format!("{:?} Record", orec.rtype)
+    };
+    println!("{}", title);
+    println!("{}", "=".repeat(title.len()));
+    print_bytes("Data", &orec.data);
+    println!();
+}
@@ -2,16 +2,38 @@ use std::str;
 
 use ::objrec::*;
 use ::typdef::*;
-
-fn read_u16(vec: &[u8]) -> u16 {
-    (vec[0] as u16) + 0x100 * (vec[1] as u16)
+use ::module::*;
+use ::comdat::*;
+use ::bakpat::*;
+use ::absrec::*;
+use ::coment::*;
+use ::borland::*;
+use ::block::*;
+use ::regint::*;
+
+/* the name of an external index, with its kind */
+fn external_name(module: &Module, index: usize) -> String {
+    match module.external(index) {
+        Some(external) => {
+            let scope = if external.local { "local " } else { "" };
+            match external.kind {
+                ExternalKind::Extern if external.local =>
+                    format!(" ({}, local)", external.name),
+                ExternalKind::Extern => format!(" ({})", external.name),
+                ExternalKind::Communal(_) =>
+                    format!(" ({}, {}communal)", external.name, scope),
+                ExternalKind::Comdat => format!(" ({}, COMDAT)", external.name)
+            }
+        },
+        None => String::new()
+    }
 }
 
 /* prints the dat info and returns the amount of bytes read */
-fn print_dat(vec: &[u8]) -> usize {
+fn print_dat(vec: &[u8], module: &Module, is32: bool) -> usize {
     let mut i: usize = 0;
     let dat = vec[i];
-    i = i + 1;
+    i += 1;
     let f = dat >> 7;
     print!("\tFrame: ");
     if f == 1 {
@@ -23,7 +45,7 @@ fn print_dat(vec: &[u8]) -> usize {
         if frame_method != 5 {
             let frame_datum = vec[i];
             print!(", datum={}", frame_datum);
-            i = i + 1;
+            i += 1;
         }
     }
     println!();
@@ -34,57 +56,44 @@ fn print_dat(vec: &[u8]) -> usize {
         print!("thread={}", target_thread);
     } else {
         let target_datum = vec[i];
-        print!("datum={}, ", target_datum);
-        i = i + 1;
+        i += 1;
         let target_method = dat & 7;
+        if target_method & 3 == 2 {	/* external index */
+            print!("datum={}{}, ", target_datum,
+                    external_name(module, target_datum as usize));
+        } else {
+            print!("datum={}, ", target_datum);
+        }
         print!("method={}", target_method);
         if target_method < 3 {
-            let target_displacement = read_u16(&vec[i..]);
+            let target_displacement = read_offset(&vec[i..], is32);
             print!(", displacement={}", target_displacement);
-            i = i + 2;
+            i += if is32 { 4 } else { 2 };
         }
     }
     println!();
-    return i;
+    i
 }
 
 /* prints the local symbols base and returns the number obytes read */
-fn print_loc_sym_base(vec: &[u8]) -> usize {
-    let group_index = vec[0];
-    let segment_index = vec[1];
-    if segment_index > 0 {
-        println!("Group index: {}, segment index: {}",
-                group_index, segment_index);
-        return 2;
-    } else {
-        let frame_number = read_u16(&vec[2..]);
-        println!("Group index: {}, segment index: {}, frame number: {}",
-                group_index, segment_index, frame_number);
-        return 4;
+fn print_loc_sym_base(group_index: usize, segment_index: usize, frame: Option<u16>) {
+    match frame {
+        Some(frame_number) => println!("Group index: {}, segment index: {}, frame number: {}",
+                group_index, segment_index, frame_number),
+        None => println!("Group index: {}, segment index: {}", group_index, segment_index)
     }
 }
 
-pub fn regint(orec: ObjectRecord) {
+pub fn regint(orec: ObjectRecord, module: &Module) {
     println!("Register Initialization Record (REGINT)");
     println!("=======================================");
-    let mut i = 0;
-    while i < orec.data.len() {
-        print!("regtyp={:02x}, ", orec.data[i]);
-        let regid = orec.data[0] >> 6;
-        i = i + 1;
-        let l = orec.data[0] & 1;
-        print!("regid={}, l={}, ", regid, l);
-        if l == 1 {
-            println!("regdat={:02x}", orec.data[i]);
-            i = i + print_dat(&orec.data[i..]);
-        } else {
-            i = i + print_loc_sym_base(&orec.data[i..]);
-            if regid <= 1 {
-                let reg_offs = read_u16(&orec.data[i..]);
-                print!("register offset: {}", reg_offs);
+    match read_regint(&orec.data) {
+        Ok(registers) => {
+            for register in registers {
+                println!("{}: {}", register.id, value_to_string(module, &register.value));
             }
-            println!();
-        }
+        },
+        Err(reason) => println!("undecodable REGINT: {}", reason)
     }
     println!();
 }
@@ -93,45 +102,37 @@ pub fn blkdef(orec: ObjectRecord) {
     println!("Block Definition Record (BLKDEF)");
     println!("================================");
 
+    let blkdef = match read_blkdef(&orec.data) {
+        Ok(blkdef) => blkdef,
+        Err(reason) => {
+            println!("undecodable BLKDEF: {}", reason);
+            println!();
+            return;
+        }
+    };
+
     /* block base */
-    let mut i = print_loc_sym_base(&orec.data[0..]);
+    print_loc_sym_base(blkdef.group, blkdef.segment,
+            if blkdef.segment == 0 { Some(blkdef.frame) } else { None });
 
     /* block information */
-    let name_len = orec.data[i] as usize;
-    if name_len != 0 {
-        let name = str::from_utf8(&orec.data[i+1..i+1+name_len]).unwrap();
-        println!("Name: {}", name);
-        i = i + name_len + 1;
+    if !blkdef.name.is_empty() {
+        println!("Name: {}", blkdef.name);
     }
-
-    let block_offset = read_u16(&orec.data[i..]);
-    println!("Block offset: {}", block_offset);
-    i = i + 2;
-
-    let block_length = read_u16(&orec.data[i..]);
-    println!("Block length: {}", block_length);
-    i = i + 2;
+    println!("Block offset: {}", blkdef.offset);
+    println!("Block length: {}", blkdef.length);
 
     /* procedure information */
-    let p = orec.data[i] >> 7;
-    i = i + 1;
-    if p == 1 {		/* there is a return address offset */
-        let l = (orec.data[i] >> 6) & 1;
-        let word1 = read_u16(&orec.data[i..]);
-        if l == 0 {	/* 2-byte return address */
-            println!("Return address: {}", word1);
-            i = i + 2;
-        } else {	/* 4-byte return address */
-            let word2 = read_u16(&orec.data[i+2..]);
-            println!("Return address: {}:{}", word1, word2);
-            i = i + 4;
-        }
+    if blkdef.procedure {
+        println!("Procedure: {}", if blkdef.far { "far" } else { "near" });
+    }
+    if let Some(return_offset) = blkdef.return_offset {
+        println!("Return address offset: {}", return_offset);
     }
 
     /* type index */
-    if name_len > 0 {
-        let type_index = orec.data[i];
-        println!("Type index: {}", type_index);
+    if !blkdef.name.is_empty() {
+        println!("Type index: {}", blkdef.type_index);
     }
 
     println!();
@@ -147,36 +148,26 @@ pub fn debsym(orec: ObjectRecord) {
     println!("Debug Symbols Record (DEBSYM)");
     println!("=============================");
 
-    let frame_info = orec.data[0];
-    let based = frame_info >> 7;
-    let long = (frame_info >> 6) & 1;
-    let meth = frame_info & 7;
-    println!("Based: {}, long: {}, method: {}", based, long, meth);
-
-    let mut i = match meth {
-        0 => 1 + print_loc_sym_base(&orec.data[1..]),
-        1 => {
-            let ext_index = orec.data[1];
-            println!("External index: {}", ext_index);
-            2
-        },
-        2 => {
-            let block_index = orec.data[1];
-            println!("Block index: {}", block_index);
-            2
-        },
-        _ => panic!("illegal method")
+    let debsym = match read_debsym(&orec.data) {
+        Ok(debsym) => debsym,
+        Err(reason) => {
+            println!("undecodable DEBSYM: {}", reason);
+            println!();
+            return;
+        }
     };
+    let meth = orec.data[0] & 7;
+    println!("Based: {}, long: {}, method: {}", debsym.based as u8, debsym.long as u8, meth);
 
-    while i < orec.data.len() {
-        let name_len = orec.data[i] as usize;
-        let name = str::from_utf8(&orec.data[i+1..i+1+name_len]).unwrap();
-        i = i + 1 + name_len;
-        let offset = read_u16(&orec.data[i..]);
-        let type_index = orec.data[i+2];
+    match debsym.base {
+        DebsymBase::Segment { group, segment, frame } => print_loc_sym_base(group, segment, frame),
+        DebsymBase::External(index) => println!("External index: {}", index),
+        DebsymBase::Block(index) => println!("Block index: {}", index)
+    }
+
+    for (name, offset, type_index) in debsym.symbols {
         println!("Name: {}, offset: {}, type index: {}",
                 name, offset, type_index);
-        i = i + 3;
     }
     println!();
 }
@@ -189,72 +180,211 @@ pub fn theadr(orec: ObjectRecord) {
     println!();
 }
 
-fn print_com_typ(vec: &[u8]) {
-    println!("Comment type: typedef");
-    let t = read_u16(&vec[0..]);
-    println!("Type: {} (0x{:04x})", t, t);
-    let s = read_u16(&vec[2..]);
-    println!("Size: {}", s);
-    let a = vec[4];
-    println!("Absolute type: {}", a);
-    if vec.len() > 5 {
-        print!("Other bytes:");
-        for x in &vec[5..] {
-            print!(" {:02x}", x);
+fn print_borland(class: u8, vec: &[u8]) {
+    let comment = match read_borland(class, vec) {
+        Ok(comment) => comment,
+        Err(reason) => {
+            println!("undecodable Borland comment {:02x}: {}", class, reason);
+            return;
+        }
+    };
+    match comment {
+        BorlandComment::ExternTypes(types) => {
+            println!("Comment type: external types");
+            for (index, t) in types {
+                println!("External: {}, type: {}", index, t);
+            }
+        },
+        BorlandComment::PublicTypes(types) => {
+            println!("Comment type: public types");
+            for (index, t) in types {
+                println!("Public: {}, type: {}", index, t);
+            }
+        },
+        BorlandComment::StructMembers(members) => {
+            println!("Comment type: structure members");
+            for member in members {
+                println!("{}: type: {}", member.name, member.value);
+            }
+        },
+        BorlandComment::Typedef(typedef) => {
+            println!("Comment type: typedef");
+            println!("Type: {} (0x{:04x})", typedef.index, typedef.index);
+            println!("Size: {}", typedef.size);
+            println!("Absolute type: {} ({})", typedef.tid, tid_to_string(typedef.tid));
+            if let Some(reference) = typedef.reference {
+                if typedef.refers_to_members() {
+                    println!("Member list: {}", reference);
+                } else {
+                    println!("Referenced type: {}", reference);
+                }
+            }
+            if !typedef.extra.is_empty() {
+                print!("Other bytes:");
+                for x in &typedef.extra {
+                    print!(" {:02x}", x);
+                }
+                println!();
+            }
+        },
+        BorlandComment::EnumMembers(members) => {
+            println!("Comment type: enumeration members");
+            for member in members {
+                println!("{} = {}", member.name, member.value);
+            }
+        },
+        BorlandComment::BeginScope(offset) if class == 0xec =>
+            println!("Comment type: begin scope at {:08x}", offset),
+        BorlandComment::BeginScope(offset) =>
+            println!("Comment type: begin scope at {:04x}", offset),
+        BorlandComment::Locals(locals) => {
+            println!("Comment type: local symbols");
+            for l in locals {
+                println!("{}: type:{}(0x{:04x}), group:{}, segment:{}, offset:{:04x}",
+                        l.name, l.type_index, l.type_index, l.group, l.segment, l.offset);
+            }
+        },
+        BorlandComment::EndScope(offset) if class == 0xed =>
+            println!("Comment type: end scope at {:08x}", offset),
+        BorlandComment::EndScope(offset) =>
+            println!("Comment type: end scope at {:04x}", offset),
+        BorlandComment::SourceLines { index, lines } => {
+            println!("Comment type: source lines, index: {}", index);
+            for (line, offset) in lines {
+                println!("Line: {}, offset: {:04x}", line, offset);
+            }
+        },
+        BorlandComment::Source { index, name, time } =>
+            println!("Comment type: filname ({}), index: {}, time: {:08x}",
+                    name, index, time),
+        BorlandComment::Dependency { ref name, .. } if name.is_empty() =>
+            println!("Comment type: end of dependencies"),
+        BorlandComment::Dependency { time, name } =>
+            println!("Comment type: dependency ({}), time: {:08x}", name, time),
+        BorlandComment::Compiler { language, model } =>
+            println!("Comment type: compdef: {}-{} ({}, {} model)", language, model,
+                    language_to_string(language), model_to_string(model)),
+        BorlandComment::Other(class, bytes) => {
+            print!("Comment type: Borland {:02x}:", class);
+            for x in bytes {
+                print!(" {:02x}", x);
+            }
+            println!();
         }
-        println!();
     }
 }
 
-fn print_com_sym(vec: &[u8]) {
-    let mut i = 0;
-    while i < vec.len() {
-        let len = vec[i] as usize;
-        let s = str::from_utf8(&vec[i+1..i+1+len]).unwrap();
-        i = i + 1 + len;
-        let t = read_u16(&vec[i..]); 
-        let g = vec[i+2];
-        let seg = vec[i+3];
-        let o = read_u16(&vec[i+4..]);
-        println!("{}: type:{}(0x{:04x}), group:{}, segment:{}, offset:{:04x}",
-            s, t, t, g, seg, o);
-        i = i + 6;
+fn print_extern_pairs(title: &str, pairs: &[(usize, usize)], module: &Module) {
+    println!("Comment type: {}", title);
+    for &(external, default) in pairs {
+        println!("External: {}{}, default resolution: {}{}",
+                external, external_name(module, external),
+                default, external_name(module, default));
+    }
+}
+
+fn print_comment(comment: &Comment, module: &Module) {
+    match *comment {
+        Comment::Translator(ref name) =>
+            println!("Comment type: translator\n{}", name),
+        Comment::Copyright(ref text) =>
+            println!("Comment type: copyright\n{}", text),
+        Comment::LibrarySpecifier(ref name) =>
+            println!("Comment type: library specifier: {}", name),
+        Comment::DosVersion(version) =>
+            println!("Comment type: MS-DOS version: {}", version),
+        Comment::MemoryModel { processor, model, optimized, ref text } => {
+            println!("Comment type: memory model ({})", text);
+            if let Some(processor) = processor {
+                println!("Processor: {}", processor);
+            }
+            if let Some(model) = model {
+                println!("Model: {}", model);
+            }
+            println!("Optimized: {}", optimized);
+        },
+        Comment::DosSeg => println!("Comment type: DOSSEG"),
+        Comment::DefaultLibrary(ref name) =>
+            println!("Comment type: default library search: {}", name),
+        Comment::Impdef(ref import) => {
+            println!("Comment type: IMPDEF");
+            println!("Internal name: {}, module: {}, entry: {}",
+                    import.internal, import.module, import.entry);
+        },
+        Comment::Expdef(ref export) => {
+            println!("Comment type: EXPDEF");
+            print!("Exported name: {}", export.exported);
+            if !export.internal.is_empty() {
+                print!(", internal name: {}", export.internal);
+            }
+            if let Some(ordinal) = export.ordinal {
+                print!(", ordinal: {}", ordinal);
+            }
+            println!();
+            println!("Resident: {}, no data: {}, parameter words: {}",
+                    export.resident, export.no_data, export.parameters);
+        },
+        Comment::Incdef { extdef_delta, linnum_delta } => {
+            println!("Comment type: INCDEF");
+            println!("EXTDEF delta: {}, LINNUM delta: {}",
+                    extdef_delta as i16, linnum_delta as i16);
+        },
+        Comment::ProtectedLibrary =>
+            println!("Comment type: protected memory library"),
+        Comment::Lnkdir { flags, pseudocode_version, codeview_version } => {
+            println!("Comment type: LNKDIR");
+            println!("Output new .EXE: {}, omit CodeView publics: {}, \
+                    run MPC: {}", flags & 0x01 != 0, flags & 0x02 != 0,
+                    flags & 0x04 != 0);
+            println!("Pseudocode version: {}, CodeView version: {}",
+                    pseudocode_version, codeview_version);
+        },
+        Comment::BigEndian => println!("Comment type: big-endian"),
+        Comment::PreCompile => println!("Comment type: precompiled types"),
+        Comment::Extension(subtype, _) =>
+            println!("Comment type: OMF extension {:02x}", subtype),
+        Comment::DebugStyle { version, ref style } =>
+            println!("Comment type: debug style: {} version {}", style, version),
+        Comment::LinkPass(pass) => println!("Comment type: linkpass: {}", pass),
+        Comment::LibraryModule(ref name) =>
+            println!("Comment type: library module: {}", name),
+        Comment::Exestr(ref text) => println!("Comment type: EXESTR\n{}", text),
+        Comment::Incerr => println!("Comment type: INCERR"),
+        Comment::Nopad(ref segments) => {
+            println!("Comment type: NOPAD");
+            for &segment in segments {
+                println!("Segment: {} ({})", segment, module.segment_name(segment));
+            }
+        },
+        Comment::WeakExterns(ref pairs) => print_extern_pairs("WKEXT", pairs, module),
+        Comment::LazyExterns(ref pairs) => print_extern_pairs("LZEXT", pairs, module),
+        Comment::Other(_) => println!("Unknown comment type")
     }
 }
 
-pub fn coment(orec: ObjectRecord) {
+pub fn coment(orec: ObjectRecord, module: &Module) {
     println!("Comment Record (COMENT)");
     println!("=======================");
 
-    let np = (orec.data[0] & 0x80) != 0;
-    let nl = (orec.data[0] & 0x40) != 0;
-    println!("No purge: {}\nNo list: {}", np, nl);
+    let coment = match read_coment(&orec.data) {
+        Ok(coment) => coment,
+        Err(reason) => {
+            println!("undecodable COMENT: {}", reason);
+            println!();
+            return;
+        }
+    };
+    println!("No purge: {}\nNo list: {}", coment.no_purge, coment.no_list);
 
-    let comment_type = orec.data[1];
-    match comment_type {
-        0x00 | 0x80 => {
-            let mut i = 2; 
-            while orec.data[i] < 0x20 { i = i + 1 } /* unprintable characters */
-            println!("Comment type: translator\n{}",
-            str::from_utf8(&orec.data[i..]).unwrap())},
-        0xa1 => println!("Comment type: extended"),
-        0xa2 => println!("Comment type: linkpass: {}",
-                    orec.data[2]),
-        0xea => println!("Comment type: compdef: {}-{}",
-                    orec.data[2], orec.data[3]),
-        0xe3 => print_com_typ(&orec.data[2..]),
-        0xe8 => {
-            let len = orec.data[3] as usize;
-            println!("Comment type: filname ({})",
-                    str::from_utf8(&orec.data[4..4+len]).unwrap())},
-        0xe6 => print_com_sym(&orec.data[2..]),
-        _ => println!("Unknown comment type")
+    match coment.class {
+        0xe0 ..= 0xef => print_borland(coment.class, &orec.data[2..]),
+        _ => print_comment(&coment.comment, module)
     }
     println!();
 
 }
 
-pub fn modend(orec: ObjectRecord) {
+pub fn modend(orec: ObjectRecord, module: &Module) {
     println!("Module End Record (MODEND)");
     println!("==========================");
 
@@ -267,7 +397,7 @@ pub fn modend(orec: ObjectRecord) {
         if module_type & 1 == 1 {
             println!("Start address contains a relocatable address reference");
         }
-        print_dat(&orec.data[1..]);
+        print_dat(&orec.data[1..], module, orec.rtype == RecordType::MODEND32);
     }
     println!();
 }
@@ -275,18 +405,56 @@ pub fn modend(orec: ObjectRecord) {
 pub fn extdef(orec: ObjectRecord) {
     println!("External Names Definition Record (EXTDEF)");
     println!("=========================================");
-    let mut p = &orec.data[..];
-    while !p.is_empty() {
-        let length = p[0] as usize;
-        let str = &p[0..length+1];
-        let type_index = p[length+1] as usize;
-        let name = str::from_utf8(&str[1..]).unwrap();
-        print!("Name: {}", name);
-        if type_index > 0 {
-            print!(", type index: {}", type_index);
-        }
-        println!();
-        p = &p[length+2..];
+    print_external_names(&orec.data);
+    println!();
+}
+
+pub fn lextdef(orec: ObjectRecord) {
+    println!("Local External Names Definition Record (LEXTDEF)");
+    println!("================================================");
+    print_external_names(&orec.data);
+    println!();
+}
+
+fn print_external_names(vec: &[u8]) {
+    match read_extdef(vec) {
+        Ok(names) => for (name, type_index) in names {
+            print!("Name: {}", name);
+            if type_index > 0 {
+                print!(", type index: {}", type_index);
+            }
+            println!();
+        },
+        Err(reason) => println!("undecodable external names: {}", reason)
+    }
+}
+
+pub fn cextdef(orec: ObjectRecord, module: &Module) {
+    println!("COMDAT External Names Definition Record (CEXTDEF)");
+    println!("=================================================");
+    match read_cextdef(&orec.data) {
+        Ok(names) => {
+            for (name_index, type_index) in names {
+                print!("Name: {} ({})", module.name(name_index), name_index);
+                if type_index > 0 {
+                    print!(", type index: {}", type_index);
+                }
+                println!();
+            }
+        },
+        Err(reason) => println!("undecodable CEXTDEF: {}", reason)
+    }
+    println!();
+}
+
+pub fn alias(orec: ObjectRecord) {
+    println!("Alias Definition Record (ALIAS)");
+    println!("===============================");
+    match read_aliases(&orec.data) {
+        Ok(aliases) => for (alias, substitute) in aliases {
+            println!("Alias: {}, substitute: {}", alias, substitute);
+        },
+        Err(reason) => println!("undecodable ALIAS: {}", reason)
     }
     println!();
 }
@@ -295,91 +463,158 @@ pub fn typdef(orec: ObjectRecord) {
     println!("Type Definition Record (TYPDEF)");
     println!("================================");
     let name_len = orec.data[0] as usize;
+    if name_len + 1 > orec.data.len() {
+        println!("undecodable TYPDEF: name is truncated");
+        println!();
+        return;
+    }
     if name_len != 0 {
-        let name = str::from_utf8(&orec.data[1..name_len+1]).unwrap();
+        let name = String::from_utf8_lossy(&orec.data[1..name_len+1]);
         println!("Name: {}", name);
     }
     let i = name_len + 1;
-    let type_string = typdef_to_string(&orec.data[i..]);
-    println!("Type: {}", type_string);
+    match typdef_to_string(&orec.data[i..]) {
+        Ok(type_string) => println!("Type: {}", type_string),
+        Err(reason) => {
+            println!("undecodable TYPDEF: {}", reason);
+            print!("Leaves:");
+            for x in &orec.data[i..] {
+                print!(" {:02x}", x);
+            }
+            println!();
+        }
+    }
     println!();
 }
 
 pub fn pubdef(orec: ObjectRecord) {
     println!("Public Names Definition Record (PUBDEF)");
     println!("=======================================");
+    print_public_names(&orec.data, orec.rtype == RecordType::PUBDEF32);
+    println!();
+}
 
-    let base_group_index = orec.data[0];
-    println!("Base group index: {}", base_group_index);
-
-    let base_segment_index = orec.data[1];
-    println!("Base segment index: {}", base_segment_index);
+pub fn lpubdef(orec: ObjectRecord) {
+    println!("Local Public Names Definition Record (LPUBDEF)");
+    println!("==============================================");
+    print_public_names(&orec.data, orec.rtype == RecordType::LPUBDEF32);
+    println!();
+}
 
-    let i;
-    if base_segment_index == 0 {
-        let base_frame = read_u16(&orec.data[2..]);
-        println!("Base frame: {}", base_frame);
-        i = 4;
-    } else {
-        i = 2;
+fn print_public_names(vec: &[u8], is32: bool) {
+    let (group, segment, frame, _) = match read_public_base(vec) {
+        Ok(base) => base,
+        Err(reason) => {
+            println!("undecodable public names: {}", reason);
+            return;
+        }
+    };
+    println!("Base group index: {}", group);
+    println!("Base segment index: {}", segment);
+    if let Some(frame) = frame {
+        println!("Base frame: {}", frame);
     }
 
-    let mut p = &orec.data[i..];
-    while !p.is_empty() {
-        let length = p[0] as usize;
-        let name = str::from_utf8(&p[1..length+1]).unwrap();
-        let public_offset = read_u16(&p[length+1..]);
-        let type_index = p[length+3] as usize;
-        print!("Name: {}, public offset: {}", name, public_offset);
-        if type_index > 0 {
-            print!(", type index: {}", type_index);
-        }
-        println!();
-        p = &p[length+4..];
+    match read_publics(vec, false, is32) {
+        Ok(symbols) => for symbol in symbols {
+            print!("Name: {}, public offset: {}", symbol.name, symbol.offset);
+            if symbol.type_index > 0 {
+                print!(", type index: {}", symbol.type_index);
+            }
+            println!();
+        },
+        Err(reason) => println!("undecodable public names: {}", reason)
     }
-    println!();
 }
 
 pub fn linnum(orec: ObjectRecord) {
     println!("Line Numbers Record (LINNUM)");
     println!("===========================");
 
-    let base_group_index = orec.data[0];
-    println!("Base group index: {}", base_group_index);
-
-    let base_segment_index = orec.data[1];
-    println!("Base segment index: {}", base_segment_index);
-
-    let mut i = 2;
-    while i < orec.data.len() {
-        let line = read_u16(&orec.data[i..]);
-        let offset = read_u16(&orec.data[i+2..]);
-        println!("Line: {}, offset: {}", line, offset);
-        i = i + 4;
+    match read_linnum(&orec.data) {
+        Ok(linnum) => {
+            println!("Base group index: {}", linnum.group);
+            println!("Base segment index: {}", linnum.segment);
+            for (line, offset) in linnum.lines {
+                println!("Line: {}, offset: {}", line, offset);
+            }
+        },
+        Err(reason) => println!("undecodable LINNUM: {}", reason)
     }
     println!();
 }
 
-pub fn lnames(orec: ObjectRecord) { 
+pub fn lnames(orec: ObjectRecord, first: usize) {
     println!("List of Names Record (LNAMES)");
     println!("=============================");
-    let mut p = &orec.data[..];
-    let mut count = 0;
+    print_names(&orec.data, first);
+    println!();
+}
+
+pub fn llnames(orec: ObjectRecord, first: usize) {
+    println!("Local List of Names Record (LLNAMES)");
+    println!("====================================");
+    print_names(&orec.data, first);
+    println!();
+}
+
+/* the names of the record, numbered from the name table index of the first */
+fn print_names(vec: &[u8], first: usize) {
+    let mut p = vec;
+    let mut names = Vec::new();
     while !p.is_empty() {
-        count = count + 1;
         let length = p[0] as usize;
         let s: &[u8] = &p[0..length+1];
-        let name = str::from_utf8(&s[1..]).unwrap();
-        println!("{}: {:?}", count, name);
+        names.push(String::from_utf8_lossy(&s[1..]));
         p = &p[length+1..];
     }
+    for (i, name) in names.iter().enumerate() {
+        println!("{}: {:?}", first + i, name);
+    }
+}
+
+pub fn vernum(orec: ObjectRecord) {
+    println!("OMF Version Number Record (VERNUM)");
+    println!("==================================");
+    match orec.data.first() {
+        Some(&length) => {
+            let length = (length as usize).min(orec.data.len() - 1);
+            println!("Version: {}", String::from_utf8_lossy(&orec.data[1..1+length]));
+        },
+        None => println!("undecodable VERNUM: record is empty")
+    }
+    println!();
+}
+
+pub fn vendext(orec: ObjectRecord) {
+    println!("Vendor-specific OMF Extension Record (VENDEXT)");
+    println!("==============================================");
+    if orec.data.len() < 2 {
+        println!("undecodable VENDEXT: record is truncated");
+        println!();
+        return;
+    }
+    println!("Vendor number: {}", read_u16(&orec.data));
+    print!("Extension:");
+    for x in &orec.data[2..] {
+        print!(" {:02x}", x);
+    }
+    println!();
     println!();
 }
 
 pub fn segdef(orec: ObjectRecord) {
     println!("Segment Definition Record (SEGDEF)");
     println!("=================================");
-    let acbp = orec.data[0];
+    let segdef = match read_segdef(&orec.data, orec.rtype == RecordType::SEGDEF32) {
+        Ok(segdef) => segdef,
+        Err(reason) => {
+            println!("undecodable SEGDEF: {}", reason);
+            println!();
+            return;
+        }
+    };
+    let acbp = segdef.acbp;
     let a = (acbp & 0xe0) >> 5;
     let c = (acbp & 0x1c) >> 2;
     let b1 = (acbp & 0x02) >> 1;               /* b1 because of joe bug */
@@ -412,27 +647,14 @@ pub fn segdef(orec: ObjectRecord) {
     println!("P: {}", if p == 1 { "true" } else {"false" });
 
     /* frame number and offset when A is 0 */
-    let i;
-    if a == 0 {
-        let frame_number = read_u16(&orec.data[1..]);
-        let offset = orec.data[3];
+    if let Some((frame_number, offset)) = segdef.frame {
         println!("Frame number: {}", frame_number);
         println!("Offset: {}", offset);
-        i = 4;
-    } else {
-        i = 1;
     }
-    let segment_length = read_u16(&orec.data[i..]);
-    println!("Segment length: {}", segment_length);
-
-    let segment_name_index = orec.data[i+2];
-    println!("Segment name index: {}", segment_name_index);
-
-    let class_name_index = orec.data[i+3];
-    println!("Class name index: {}", class_name_index);
-
-    let overlay_name_index = orec.data[i+4];
-    println!("Overlay name index: {}", overlay_name_index);
+    println!("Segment length: {}", segdef.length);
+    println!("Segment name index: {}", segdef.name_index);
+    println!("Class name index: {}", segdef.class_index);
+    println!("Overlay name index: {}", segdef.overlay_index);
 
     println!();
 }
@@ -454,13 +676,13 @@ pub fn grpdef(orec: ObjectRecord) {
             _ => "?????"
         };
         println!("{}: {}", typstr, orec.data[i+1]);
-        i = i + 2;
+        i += 2;
     }
 
     println!();
 }
 
-pub fn fixupp(orec: ObjectRecord) {
+pub fn fixupp(orec: ObjectRecord, module: &Module) {
     println!("Fixup Record (FIXUPP)");
     println!("=====================");
 
@@ -469,7 +691,7 @@ pub fn fixupp(orec: ObjectRecord) {
         if orec.data[i] & 0x80 != 0 {	/* fixup field */
             print!("Fixup field: ");
             let locat = (orec.data[i+1] as u32) + 256*(orec.data[i] as u32);
-            i = i + 2;
+            i += 2;
             let m = (locat >>14 ) & 1;
             let loc = (locat >> 10) & 7;
             let data = locat & 0x3ff;
@@ -479,7 +701,7 @@ pub fn fixupp(orec: ObjectRecord) {
                 print!("self-relative, ");
             }
             println!("loc={}, data={:03x}", loc, data);
-            i = i + print_dat(&orec.data[i..]);
+            i = i + print_dat(&orec.data[i..], module, orec.rtype == RecordType::FIXUPP32);
         } else {		/* thread field */
             let data = orec.data[i];
             let number = data & 3;
@@ -488,7 +710,7 @@ pub fn fixupp(orec: ObjectRecord) {
             let index = orec.data[i+1];
             println!("Thread field: {} thread: {}, method: {}, index: {}",
                 d, number, method, index);
-            i = i + 2;
+            i += 2;
         }
     }
     
@@ -499,22 +721,273 @@ pub fn ledata(orec: ObjectRecord) {
     println!("Logical Enumerated Data Record (LEDATA)");
     println!("=======================================");
 
-    let segment_index = orec.data[0];
-    println!("Segment index: {}", segment_index);
+    print_data(&orec.data, orec.rtype == RecordType::LEDATA32, false);
+}
 
-    let data_offset = read_u16(&orec.data[1..]);
-    println!("Enumerated data offset: {}", data_offset);
+pub fn lidata(orec: ObjectRecord) {
+    println!("Logical Iterated Data Record (LIDATA)");
+    println!("=====================================");
+    print_data(&orec.data, orec.rtype == RecordType::LIDATA32, true);
+}
 
-    let mut i = 0;
+fn print_data(vec: &[u8], is32: bool, iterated: bool) {
+    let data = match read_data(vec, is32, iterated) {
+        Ok(data) => data,
+        Err(reason) => {
+            println!("undecodable data: {}", reason);
+            println!();
+            return;
+        }
+    };
+    println!("Segment index: {}", data.segment);
+    if iterated {
+        println!("Iterated data offset: {}", data.offset);
+    } else {
+        println!("Enumerated data offset: {}", data.offset);
+    }
+
+    print!("Data: ");
+    for (i, x) in data.data.iter().enumerate() {
+        if i > 0 && i % 16 == 0 {
+            print!("\n      ");
+        }
+        print!(" {:02x}", x);
+    }
+    println!();
+    println!();
+}
+
+fn print_communals(vec: &[u8]) {
+    match read_comdef(vec) {
+        Ok(communals) => {
+            for (name, type_index, communal) in communals {
+                print!("Name: {}", name);
+                if type_index > 0 {
+                    print!(", type index: {}", type_index);
+                }
+                println!(", {}", communal);
+            }
+        },
+        Err(reason) => println!("undecodable COMDEF: {}", reason)
+    }
+}
+
+pub fn comdef(orec: ObjectRecord) {
+    println!("Communal Names Definition Record (COMDEF)");
+    println!("=========================================");
+    print_communals(&orec.data);
+    println!();
+}
+
+pub fn lcomdef(orec: ObjectRecord) {
+    println!("Local Communal Names Definition Record (LCOMDEF)");
+    println!("================================================");
+    print_communals(&orec.data);
+    println!();
+}
+
+pub fn comdat(orec: ObjectRecord, module: &Module) {
+    println!("Initialized Communal Data Record (COMDAT)");
+    println!("=========================================");
+    let is32 = orec.rtype == RecordType::COMDAT32;
+    let comdat = match read_comdat(&orec.data, is32) {
+        Ok(comdat) => comdat,
+        Err(reason) => {
+            println!("undecodable COMDAT: {}", reason);
+            println!();
+            return;
+        }
+    };
+    println!("Name: {} ({})", module.name(comdat.name_index), comdat.name_index);
+    println!("Continuation: {}, iterated: {}, local: {}, data in code segment: {}",
+            comdat.continuation, comdat.iterated, comdat.local,
+            comdat.code_segment);
+    println!("Selection: {}", comdat.selection);
+    println!("Allocation: {}", comdat.allocation);
+    println!("Alignment: {}", align_to_string(comdat.align));
+    println!("Enumerated data offset: {}", comdat.offset);
+    if comdat.type_index > 0 {
+        println!("Type index: {}", comdat.type_index);
+    }
     print!("Data: ");
-    while i+3 < orec.data.len() {
+    for (i, x) in comdat.data.iter().enumerate() {
         if i > 0 && i % 16 == 0 {
             print!("\n      ");
         }
-        print!(" {:02x}", orec.data[i+3]);
-        i = i + 1;
+        print!(" {:02x}", x);
+    }
+    println!();
+    println!();
+}
+
+pub fn linsym(orec: ObjectRecord, module: &Module) {
+    println!("Symbol Line Numbers Record (LINSYM)");
+    println!("===================================");
+    let is32 = orec.rtype == RecordType::LINSYM32;
+    match read_linsym(&orec.data, is32) {
+        Ok(linsym) => {
+            println!("Name: {} ({})", module.name(linsym.name_index),
+                    linsym.name_index);
+            println!("Continuation: {}", linsym.continuation);
+            for (line, offset) in linsym.lines {
+                println!("Line: {}, offset: {}", line, offset);
+            }
+        },
+        Err(reason) => println!("undecodable LINSYM: {}", reason)
+    }
+    println!();
+}
+
+fn print_patches(bakpat: &Bakpat) {
+    println!("Location: {}", bakpat.location);
+    for &(offset, value) in &bakpat.patches {
+        println!("Offset: {}, value: {}", offset, value);
+    }
+}
+
+pub fn bakpat(orec: ObjectRecord) {
+    println!("Backpatch Record (BAKPAT)");
+    println!("=========================");
+    match read_bakpat(&orec.data, orec.rtype == RecordType::BAKPAT32) {
+        Ok(bakpat) => {
+            if let Target::Segment(segment) = bakpat.target {
+                println!("Segment index: {}", segment);
+            }
+            print_patches(&bakpat);
+        },
+        Err(reason) => println!("undecodable BAKPAT: {}", reason)
     }
     println!();
+}
+
+pub fn nbkpat(orec: ObjectRecord, module: &Module) {
+    println!("Named Backpatch Record (NBKPAT)");
+    println!("===============================");
+    match read_nbkpat(&orec.data, orec.rtype == RecordType::NBKPAT32) {
+        Ok(bakpat) => {
+            if let Target::Comdat(name_index) = bakpat.target {
+                println!("Name: {} ({})", module.name(name_index), name_index);
+            }
+            print_patches(&bakpat);
+        },
+        Err(reason) => println!("undecodable NBKPAT: {}", reason)
+    }
+    println!();
+}
+
+pub fn rheadr(orec: ObjectRecord) {
+    println!("R-Module Header Record (RHEADR)");
+    println!("===============================");
+    match read_rheadr(&orec.data) {
+        Ok(rheadr) => {
+            println!("Module name: {}", rheadr.name);
+            println!("Attribute: {:02x}", rheadr.attribute);
+            println!("Segments: {}, groups: {}, overlays: {}",
+                    rheadr.segment_count, rheadr.group_count,
+                    rheadr.overlay_count);
+            println!("Overlay record offset: {}", rheadr.overlay_offset);
+            println!("Static size: {}, maximum: {}",
+                    rheadr.static_size, rheadr.max_static_size);
+            println!("Dynamic storage: {}, maximum: {}",
+                    rheadr.dynamic_storage, rheadr.max_dynamic_storage);
+        },
+        Err(reason) => println!("undecodable RHEADR: {}", reason)
+    }
+    println!();
+}
+
+pub fn lheadr(orec: ObjectRecord) {
+    println!("L-Module Header Record (LHEADR)");
+    println!("===============================");
+    match read_lheadr(&orec.data) {
+        Ok(name) => println!("Module name: {}", name),
+        Err(reason) => println!("undecodable LHEADR: {}", reason)
+    }
+    println!();
+}
+
+pub fn ovldef(orec: ObjectRecord) {
+    println!("Overlay Definition Record (OVLDEF)");
+    println!("==================================");
+    match read_ovldef(&orec.data) {
+        Ok(ovldef) => {
+            println!("Overlay name: {}", ovldef.name);
+            println!("Overlay location: {}", ovldef.location);
+            println!("Attribute: {:02x}", ovldef.attribute);
+            if let Some(index) = ovldef.shared {
+                println!("Shared overlay index: {}", index);
+            }
+            if let Some(index) = ovldef.adjacent {
+                println!("Adjacent overlay index: {}", index);
+            }
+        },
+        Err(reason) => println!("undecodable OVLDEF: {}", reason)
+    }
+    println!();
+}
+
+pub fn endrec(orec: ObjectRecord) {
+    println!("End Record (ENDREC)");
+    println!("===================");
+    match orec.data.first() {
+        Some(0) => println!("End of overlay"),
+        Some(1) => println!("End of block"),
+        Some(n) => println!("<bad end type {:x}>", n),
+        None => println!("undecodable ENDREC: record is truncated")
+    }
+    println!();
+}
+
+fn print_physical_data(vec: &[u8], iterated: bool) {
+    match read_physical_data(vec, iterated) {
+        Ok(data) => {
+            println!("Frame number: {}, offset: {}, address: {:05x}",
+                    data.frame, data.offset, data.address());
+            print!("Data: ");
+            for (i, x) in data.data.iter().enumerate() {
+                if i > 0 && i % 16 == 0 {
+                    print!("\n      ");
+                }
+                print!(" {:02x}", x);
+            }
+            println!();
+        },
+        Err(reason) => println!("undecodable physical data: {}", reason)
+    }
+}
+
+pub fn pedata(orec: ObjectRecord) {
+    println!("Physical Enumerated Data Record (PEDATA)");
+    println!("========================================");
+    print_physical_data(&orec.data, false);
+    println!();
+}
+
+pub fn pidata(orec: ObjectRecord) {
+    println!("Physical Iterated Data Record (PIDATA)");
+    println!("======================================");
+    print_physical_data(&orec.data, true);
+    println!();
+}
+
+pub fn locsym(orec: ObjectRecord) {
+    println!("Local Symbols Record (LOCSYM)");
+    println!("=============================");
+    print_public_names(&orec.data, false);
+    println!();
+}
+
+pub fn libhed(orec: ObjectRecord) {
+    println!("Library Header Record (LIBHED)");
+    println!("==============================");
+    match read_libhed(&orec.data) {
+        Ok(libhed) => {
+            println!("Modules: {}", libhed.module_count);
+            println!("Module names at block {}, byte {}",
+                    libhed.block, libhed.byte);
+        },
+        Err(reason) => println!("undecodable LIBHED: {}", reason)
+    }
     println!();
 }
 
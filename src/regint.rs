@@ -0,0 +1,194 @@
+use std::fmt;
+
+use ::objrec::*;
+use ::fixup::*;
+use ::module::*;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RegisterId {
+    CsIp,
+    SsSp,
+    Ds,
+    Es
+}
+
+/* the initial value of a register: a logical base, with the register offset
+ * for CS:IP and SS:SP, or a frame and target like a fixup */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RegisterValue {
+    Base {
+        group: usize,
+        segment: usize,
+        frame: u16,
+        offset: Option<u16>
+    },
+    Fixup {
+        frame: Reference,
+        target: Reference,
+        displacement: u32
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Register {
+    pub id: RegisterId,
+    pub value: RegisterValue
+}
+
+impl fmt::Display for RegisterId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RegisterId::CsIp => write!(f, "CS:IP"),
+            RegisterId::SsSp => write!(f, "SS:SP"),
+            RegisterId::Ds => write!(f, "DS"),
+            RegisterId::Es => write!(f, "ES")
+        }
+    }
+}
+
+fn byte(vec: &[u8], i: usize) -> Result<u8, String> {
+    vec.get(i).cloned().ok_or_else(|| "record is truncated".to_string())
+}
+
+fn word(vec: &[u8], i: usize) -> Result<u16, String> {
+    match vec.get(i..i+2) {
+        Some(bytes) => Ok(read_u16(bytes)),
+        None => Err("record is truncated".to_string())
+    }
+}
+
+/* register type bytes, each followed by a fixup style DAT when L is set or
+ * by a logical base and, for CS:IP and SS:SP, the register offset */
+pub fn read_regint(vec: &[u8]) -> Result<Vec<Register>, String> {
+    let mut result = Vec::new();
+    let mut i = 0;
+    while i < vec.len() {
+        let regtyp = vec[i];
+        i += 1;
+        let id = match regtyp >> 6 {
+            0 => RegisterId::CsIp,
+            1 => RegisterId::SsSp,
+            2 => RegisterId::Ds,
+            _ => RegisterId::Es
+        };
+        let value = if regtyp & 1 == 1 {
+            let (frame, target, displacement, n) =
+                read_fixdat(&vec[i..], &Threads::default(), false)?;
+            i += n;
+            RegisterValue::Fixup { frame, target, displacement }
+        } else {
+            let (group, n) = read_index(&vec[i.min(vec.len())..])?;
+            i += n;
+            let (segment, n) = read_index(&vec[i.min(vec.len())..])?;
+            i += n;
+            /* a frame number only follows when there is no group or segment */
+            let mut frame = 0;
+            if group == 0 && segment == 0 {
+                frame = word(vec, i)?;
+                i += 2;
+            }
+            let offset = match id {
+                RegisterId::CsIp | RegisterId::SsSp => {
+                    i += 2;
+                    Some(word(vec, i - 2)?)
+                },
+                _ => None
+            };
+            RegisterValue::Base { group, segment, frame, offset }
+        };
+        result.push(Register { id, value });
+    }
+    Ok(result)
+}
+
+/* the start address of a MODEND or MODEND32 record, if it has one */
+pub fn read_start(vec: &[u8], is32: bool) -> Result<Option<RegisterValue>, String> {
+    let module_type = byte(vec, 0)?;
+    if module_type & 0x40 == 0 {
+        return Ok(None);
+    }
+    let (frame, target, displacement, _) = read_fixdat(&vec[1..], &Threads::default(), is32)?;
+    Ok(Some(RegisterValue::Fixup { frame, target, displacement }))
+}
+
+fn reference_name(module: &Module, reference: Reference) -> String {
+    match reference {
+        Reference::Segment(i) => module.segment_name(i).to_string(),
+        Reference::Group(i) => format!("group {}", i),
+        Reference::External(i) => module.external(i).map_or("?", |e| &e.name).to_string(),
+        Reference::Frame(frame) => format!("{:04x}", frame),
+        Reference::Location => "location".to_string(),
+        Reference::Target => "target".to_string()
+    }
+}
+
+/* a register value with the names of the module */
+pub fn value_to_string(module: &Module, value: &RegisterValue) -> String {
+    match *value {
+        RegisterValue::Base { group, segment, frame, offset } => {
+            let base = if segment > 0 {
+                module.segment_name(segment).to_string()
+            } else if group > 0 {
+                format!("group {}", group)
+            } else {
+                format!("{:04x}", frame)
+            };
+            match offset {
+                Some(offset) => format!("{}:{:04x}", base, offset),
+                None => base
+            }
+        },
+        RegisterValue::Fixup { frame, target, displacement } => {
+            let target = format!("{}:{:04x}", reference_name(module, target), displacement);
+            match frame {
+                Reference::Target => target,
+                frame => format!("{} (frame {})", target, reference_name(module, frame))
+            }
+        }
+    }
+}
+
+/* the initial registers of a program: the REGINT values, with CS:IP from
+ * the MODEND start address when REGINT does not give it */
+pub fn entry_state(module: &Module) -> Vec<Register> {
+    let mut result = module.registers.clone();
+    if !result.iter().any(|r| r.id == RegisterId::CsIp) {
+        if let Some(start) = module.start {
+            result.insert(0, Register { id: RegisterId::CsIp, value: start });
+        }
+    }
+    result
+}
+
+#[test]
+fn regint() {
+    let registers = read_regint(&[0x00, 0x00, 0x01, 0x10, 0x00,
+            0x40, 0x01, 0x02, 0x00, 0x01,
+            0x80, 0x00, 0x00, 0x00, 0xb8,
+            0xc1, 0x56, 0x02]).unwrap();
+    assert_eq!(registers, vec![
+        Register { id: RegisterId::CsIp,
+            value: RegisterValue::Base { group: 0, segment: 1, frame: 0, offset: Some(0x10) } },
+        Register { id: RegisterId::SsSp,
+            value: RegisterValue::Base { group: 1, segment: 2, frame: 0, offset: Some(0x100) } },
+        Register { id: RegisterId::Ds,
+            value: RegisterValue::Base { group: 0, segment: 0, frame: 0xb800, offset: None } },
+        Register { id: RegisterId::Es,
+            value: RegisterValue::Fixup { frame: Reference::Target,
+                target: Reference::External(2), displacement: 0 } }]);
+    assert!(read_regint(&[0x40, 0x01, 0x02, 0x00]).is_err());
+    let registers = read_regint(&[0x80, 0x81, 0x02, 0x00, 0x80, 0x00, 0x00, 0x00, 0xb8]).unwrap();
+    assert_eq!(registers, vec![
+        Register { id: RegisterId::Ds,
+            value: RegisterValue::Base { group: 0x102, segment: 0, frame: 0, offset: None } },
+        Register { id: RegisterId::Ds,
+            value: RegisterValue::Base { group: 0, segment: 0, frame: 0xb800, offset: None } }]);
+}
+
+#[test]
+fn start_address() {
+    assert_eq!(read_start(&[0x80], false).unwrap(), None);
+    assert_eq!(read_start(&[0xc1, 0x50, 0x01, 0x20, 0x00], false).unwrap(),
+            Some(RegisterValue::Fixup { frame: Reference::Target,
+                target: Reference::Segment(1), displacement: 0x20 }));
+}
@@ -1,7 +1,9 @@
+use std::fmt;
 use std::str;
 
-enum Leaf {
-    Number(u16),
+#[derive(Clone, Debug)]
+pub enum Leaf {
+    Number(u32),
     String(String),
     Index(u8),
     Null
@@ -13,67 +15,125 @@ struct LeafReader <'a> {
     leaf_counter: i32
 }
 
-impl <'a, 'b> LeafReader <'a> {
+impl <'a> LeafReader <'a> {
+
+    fn byte(&self, offset: usize) -> Result<u8, String> {
+        match self.vec.get(self.index + offset) {
+            Some(&b) => Ok(b),
+            None => Err(format!("leaf at offset {} is truncated", self.index))
+        }
+    }
 
     /* before every 8 leaves: skip the EN byte */
     fn check_index(&mut self) {
         if self.leaf_counter % 8 == 0 {
-            self.index = self.index +1
+            self.index += 1
         }
     }
 
-    fn read_null(&mut self) -> Leaf {
-        self.index = self.index + 1;
-        self.leaf_counter = self.leaf_counter + 1;
-        Leaf::Null
+    fn read_null(&mut self) -> Result<Leaf, String> {
+        self.index += 1;
+        self.leaf_counter += 1;
+        Ok(Leaf::Null)
+    }
+
+    fn read_index(&mut self) -> Result<Leaf, String> {
+        let result = self.byte(1)?;
+        self.index += 2;
+        self.leaf_counter += 1;
+        Ok(Leaf::Index(result))
+    }
+
+    fn read_u8(&mut self) -> Result<Leaf, String> {
+        let result = self.byte(0)? as u32;
+        self.index += 1;
+        self.leaf_counter += 1;
+        Ok(Leaf::Number(result))
     }
 
-    fn read_index(&mut self) -> Leaf {
-        let result = self.vec[self.index + 1];
-        self.index = self.index + 2;
-        self.leaf_counter = self.leaf_counter + 1;
-        Leaf::Index(result)
+    /* 81H, 84H, 88H: a 2, 3 or 4 byte number follows */
+    fn read_number(&mut self, size: usize) -> Result<Leaf, String> {
+        let mut result: u32 = 0;
+        for i in (1..=size).rev() {
+            result = (result << 8) | self.byte(i)? as u32;
+        }
+        self.index += 1 + size;
+        self.leaf_counter += 1;
+        Ok(Leaf::Number(result))
     }
 
-    fn read_u8(&mut self) -> Leaf {
-        let result = self.vec[self.index] as u16;
-        self.index = self.index + 1;
-        self.leaf_counter = self.leaf_counter + 1;
-        Leaf::Number(result)
+    fn read_string(&mut self) -> Result<Leaf, String> {
+        let length = self.byte(1)? as usize;
+        let start = self.index + 2;
+        if start + length > self.vec.len() {
+            return Err(format!("string leaf at offset {} is truncated", self.index));
+        }
+        let result = match str::from_utf8(&self.vec[start..start+length]) {
+            Ok(result) => result,
+            Err(_) => return Err(format!("string leaf at offset {} is not text",
+                    self.index))
+        };
+        self.index = start + length;
+        self.leaf_counter += 1;
+        Ok(Leaf::String(result.to_string()))
     }
 
-    fn read_u16(&mut self) -> Leaf {
-        let result = (self.vec[self.index+1] as u16) + 256*(self.vec[self.index+2] as u16);
-        self.index = self.index + 3;
-        self.leaf_counter = self.leaf_counter + 1;
-        Leaf::Number(result)
+    /* the next leaf, which has to be there */
+    fn next_leaf(&mut self, what: &str) -> Result<Leaf, String> {
+        match self.next() {
+            Some(leaf) => leaf,
+            None => Err(format!("missing {} leaf", what))
+        }
     }
 
-    fn read_string(&mut self) -> Leaf {
-        let length = self.vec[self.index + 1] as usize;
-        let result = str::from_utf8(&self.vec[self.index+2..self.index+2+length]).unwrap();
-        self.index = self.index + 2 + length;
-        self.leaf_counter = self.leaf_counter + 1;
-        Leaf::String(result.to_string())
+    fn next_number(&mut self, what: &str) -> Result<u32, String> {
+        match self.next_leaf(what)? {
+            Leaf::Number(num) => Ok(num),
+            leaf => Err(format!("{}: expected a number, found {}", what, leaf))
+        }
+    }
+
+    fn next_index(&mut self, what: &str) -> Result<u8, String> {
+        match self.next_leaf(what)? {
+            Leaf::Index(index) => Ok(index),
+            leaf => Err(format!("{}: expected an index, found {}", what, leaf))
+        }
+    }
+
+    fn next_null(&mut self, what: &str) -> Result<(), String> {
+        match self.next_leaf(what)? {
+            Leaf::Null => Ok(()),
+            leaf => Err(format!("{}: expected null, found {}", what, leaf))
+        }
     }
 
 }
 
 impl <'a> Iterator for LeafReader <'a> {
-    type Item = Leaf;
+    type Item = Result<Leaf, String>;
 
-    fn next(&mut self) -> Option<Leaf> {
+    fn next(&mut self) -> Option<Result<Leaf, String>> {
 
         if self.index < self.vec.len() {
             self.check_index();
-            match self.vec[self.index] {
-                0 ... 0x7f => Some(self.read_u8()),
-                0x80 => Some(self.read_null()),
-                0x81 => Some(self.read_u16()),
-                0x82 => Some(self.read_string()),
-                0x83 => Some(self.read_index()),
-                _ => panic!("bad byte: {}", self.vec[self.index])
+            if self.index >= self.vec.len() {
+                return None;
             }
+            let result = match self.vec[self.index] {
+                0 ..= 0x7f => self.read_u8(),
+                0x80 => self.read_null(),
+                0x81 => self.read_number(2),
+                0x82 => self.read_string(),
+                0x83 => self.read_index(),
+                0x84 => self.read_number(3),
+                0x88 => self.read_number(4),
+                b => Err(format!("unknown leaf {:02X}H at offset {}", b, self.index))
+            };
+            if result.is_err() {
+                /* no leaves after an error */
+                self.index = self.vec.len();
+            }
+            Some(result)
         } else {
             None
         }
@@ -81,150 +141,270 @@ impl <'a> Iterator for LeafReader <'a> {
     }
 }
 
-fn make_leaf_reader(vec: &[u8]) -> LeafReader {
+fn make_leaf_reader(vec: &[u8]) -> LeafReader<'_> {
     LeafReader {
-        vec: vec,
+        vec,
         index: 0,
         leaf_counter: 0
     }
 }
 
-fn typdef_label(mut leaf_reader: LeafReader) -> String {
-    let leaf = leaf_reader.next().unwrap();
-    match leaf {
-        Leaf::Null => {},
-        _ => panic!("bad leaf")
+/* encodes a number leaf in the shortest form */
+pub fn number_leaf(value: u32) -> Vec<u8> {
+    match value {
+        0 ..= 0x7f => vec![value as u8],
+        0x80 ..= 0xffff => vec![0x81, value as u8, (value >> 8) as u8],
+        0x10000 ..= 0xffffff =>
+            vec![0x84, value as u8, (value >> 8) as u8, (value >> 16) as u8],
+        _ => vec![0x88, value as u8, (value >> 8) as u8, (value >> 16) as u8,
+                (value >> 24) as u8]
     }
-    let ret = leaf_reader.next().unwrap();
-    let retstr = match ret {
-        Leaf::Number(0x72) => "long",
-        Leaf::Number(0x73) => "short",
-        _ => "unknown"
-    };
-    format!("label ({})", retstr)
 }
 
-fn typdef_procedure(mut leaf_reader: LeafReader) -> String {
-    let null_leaf = leaf_reader.next().unwrap();
-    match null_leaf {
-        Leaf::Null => {},
-        _ => panic!("bad leaf")
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Distance {
+    Long,
+    Short,
+    Unknown
+}
+
+/* the variable type of a Microsoft NEAR leaf */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum VariableType {
+    Array,
+    Structure,
+    Scalar,
+    Other(u32)
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ScalarKind {
+    Unsigned,
+    Signed,
+    Real
+}
+
+/* a decoded TYPDEF, type and list references are kept as indices */
+#[derive(Clone, Debug)]
+pub enum TypeDef {
+    Null,
+    Label(Distance),
+    Procedure {
+        ret: Option<u8>,
+        distance: Distance,
+        params: u32,
+        list: Option<u8>
+    },
+    Parameter(u8),
+    Array {
+        bits: u32,
+        element: u8
+    },
+    Structure {
+        bits: u32,
+        members: u32,
+        types: u8,
+        names: u8
+    },
+    Scalar {
+        bits: u32,
+        kind: ScalarKind
+    },
+    List(Vec<Leaf>),
+    Near {
+        variable: VariableType,
+        bits: u32
+    },
+    Far {
+        elements: u32,
+        element: u8
+    },
+    String(String),
+    Index(u8),
+    Unknown(u32)
+}
+
+impl fmt::Display for Distance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Distance::Long => write!(f, "long"),
+            Distance::Short => write!(f, "short"),
+            Distance::Unknown => write!(f, "unknown")
+        }
     }
-    let typ_leaf = leaf_reader.next().unwrap();
-    let typstr = match typ_leaf {
-        Leaf::Index(index) => format!("T{} ", index),
-        Leaf::Null => format!(""),
-        _ => panic!("unknown")
-    };
-    let ret_leaf = leaf_reader.next().unwrap();
-    let retstr = match ret_leaf {
-        Leaf::Number(0x72) => "long",
-        Leaf::Number(0x73) => "short",
-        _ => "unknown"
-    };
-    let num_leaf = leaf_reader.next().unwrap();
-    let num = match num_leaf {
-        Leaf::Number(num) => num,
-        _ => panic!("unknown")
-    };
-    if num > 0 {
-        let lst_leaf = leaf_reader.next().unwrap();
-        let lst_index = match lst_leaf {
-            Leaf::Index(index) => index,
-            _ => panic!("unknown")
-        };
-        format!("procedure ({}, L{}) {}{}", num, lst_index, typstr, retstr)
-    } else {
-        format!("procedure ({}) {}{}", num, typstr, retstr)
+}
+
+impl fmt::Display for VariableType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            VariableType::Array => write!(f, "array"),
+            VariableType::Structure => write!(f, "structure"),
+            VariableType::Scalar => write!(f, "scalar"),
+            VariableType::Other(n) => write!(f, "type {:02X}H", n)
+        }
     }
 }
 
-fn typdef_parameter(mut leaf_reader: LeafReader) -> String {
-    let index_leaf = leaf_reader.next().unwrap();
-    let type_index = match index_leaf {
-        Leaf::Index(index) => index,
-        _ => panic!("unknown")
-    };
-    format!("parameter T{}", type_index)
+impl fmt::Display for ScalarKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ScalarKind::Unsigned => write!(f, "u"),
+            ScalarKind::Signed => write!(f, "i"),
+            ScalarKind::Real => write!(f, "r")
+        }
+    }
 }
 
-fn typdef_array(mut leaf_reader: LeafReader) -> String {
-    let length_leaf = leaf_reader.next().unwrap();
-    let length = match length_leaf {
-        Leaf::Number(length) => length,
-        _ => panic!("unknown")
-    };
-    let index_leaf = leaf_reader.next().unwrap();
-    let type_index = match index_leaf {
-        Leaf::Index(index) => index,
-        _ => panic!("unknown")
-    };
-    format!("array ({}-bit) T{}", length, type_index)
+impl fmt::Display for Leaf {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Leaf::Number(num) => write!(f, "number:{}", num),
+            Leaf::String(ref str) => write!(f, "'{}'", str),
+            Leaf::Index(index) => write!(f, "index:{}", index),
+            Leaf::Null => write!(f, "null")
+        }
+    }
 }
 
-fn typdef_struct(mut leaf_reader: LeafReader) -> String {
-    let length_leaf = leaf_reader.next().unwrap();
-    let length = match length_leaf {
-        Leaf::Number(length) => length,
-        _ => panic!("unknown")
-    };
-    let num_leaf = leaf_reader.next().unwrap();
-    let num = match num_leaf {
-        Leaf::Number(num) => num,
-        _ => panic!("unknown")
-    };
-    let type_leaf = leaf_reader.next().unwrap();
-    let type_index = match type_leaf {
-        Leaf::Index(index) => index,
-        _ => panic!("unknown")
+impl fmt::Display for TypeDef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TypeDef::Null => write!(f, "null"),
+            TypeDef::Label(distance) => write!(f, "label ({})", distance),
+            TypeDef::Procedure { ret, distance, params, list } => {
+                let typstr = match ret {
+                    Some(index) => format!("T{} ", index),
+                    None => String::new()
+                };
+                match list {
+                    Some(index) => write!(f, "procedure ({}, L{}) {}{}",
+                            params, index, typstr, distance),
+                    None => write!(f, "procedure ({}) {}{}",
+                            params, typstr, distance)
+                }
+            },
+            TypeDef::Parameter(index) => write!(f, "parameter T{}", index),
+            TypeDef::Array { bits, element } =>
+                write!(f, "array ({}-bit) T{}", bits, element),
+            TypeDef::Structure { bits, members, types, names } =>
+                write!(f, "structure ({}-bit, {}) Types:L{} Names:L{}",
+                        bits, members, types, names),
+            TypeDef::Scalar { bits, kind } => write!(f, "{}{}", kind, bits),
+            TypeDef::List(ref leaves) => {
+                let items: Vec<String> =
+                        leaves.iter().map(|l| l.to_string()).collect();
+                write!(f, "list ({})", items.join(", "))
+            },
+            TypeDef::Near { variable, bits } =>
+                write!(f, "near {} ({}-bit)", variable, bits),
+            TypeDef::Far { elements, element } =>
+                write!(f, "far array ({}) T{}", elements, element),
+            TypeDef::String(ref s) => write!(f, "string: {}", s),
+            TypeDef::Index(i) => write!(f, "index: {}", i),
+            TypeDef::Unknown(_) => write!(f, "???")
+        }
+    }
+}
+
+fn read_distance(leaf: Leaf) -> Distance {
+    match leaf {
+        Leaf::Number(0x72) => Distance::Long,
+        Leaf::Number(0x73) => Distance::Short,
+        _ => Distance::Unknown
+    }
+}
+
+fn typdef_label(mut leaf_reader: LeafReader) -> Result<TypeDef, String> {
+    leaf_reader.next_null("label")?;
+    let ret = leaf_reader.next_leaf("label distance")?;
+    Ok(TypeDef::Label(read_distance(ret)))
+}
+
+fn typdef_procedure(mut leaf_reader: LeafReader) -> Result<TypeDef, String> {
+    leaf_reader.next_null("procedure")?;
+    let ret = match leaf_reader.next_leaf("procedure return type")? {
+        Leaf::Index(index) => Some(index),
+        Leaf::Null => None,
+        leaf => return Err(format!(
+                "procedure return type: expected an index, found {}", leaf))
     };
-    let name_leaf = leaf_reader.next().unwrap();
-    let name_index = match name_leaf {
-        Leaf::Index(index) => index,
-        _ => panic!("unknown")
+    let ret_leaf = leaf_reader.next_leaf("procedure distance")?;
+    let distance = read_distance(ret_leaf);
+    let num = leaf_reader.next_number("procedure parameter count")?;
+    let list = if num > 0 {
+        Some(leaf_reader.next_index("procedure parameter list")?)
+    } else {
+        None
     };
-    format!("structure ({}-bit, {}) Types:L{} Names:L{}",
-            length, num, type_index, name_index)
+    Ok(TypeDef::Procedure { ret, distance, params: num, list })
+}
+
+fn typdef_parameter(mut leaf_reader: LeafReader) -> Result<TypeDef, String> {
+    let type_index = leaf_reader.next_index("parameter type")?;
+    Ok(TypeDef::Parameter(type_index))
+}
+
+fn typdef_array(mut leaf_reader: LeafReader) -> Result<TypeDef, String> {
+    let length = leaf_reader.next_number("array length")?;
+    let type_index = leaf_reader.next_index("array element type")?;
+    Ok(TypeDef::Array { bits: length, element: type_index })
 }
 
-fn typdef_scalar(mut leaf_reader: LeafReader) -> String {
-    let length_leaf = leaf_reader.next().unwrap();
-    let length = match length_leaf {
-        Leaf::Number(length) => length,
-        _ => panic!("unknown")
+fn typdef_struct(mut leaf_reader: LeafReader) -> Result<TypeDef, String> {
+    let length = leaf_reader.next_number("structure length")?;
+    let num = leaf_reader.next_number("structure member count")?;
+    let type_index = leaf_reader.next_index("structure type list")?;
+    let name_index = leaf_reader.next_index("structure name list")?;
+    Ok(TypeDef::Structure {
+        bits: length,
+        members: num,
+        types: type_index,
+        names: name_index
+    })
+}
+
+fn typdef_scalar(mut leaf_reader: LeafReader) -> Result<TypeDef, String> {
+    let length = leaf_reader.next_number("scalar length")?;
+    let kind = match leaf_reader.next_leaf("scalar type")? {
+        Leaf::Number(0x7c) => ScalarKind::Unsigned,
+        Leaf::Number(0x7d) => ScalarKind::Signed,
+        Leaf::Number(0x7e) => ScalarKind::Real,
+        leaf => return Err(format!("scalar type: unknown type {}", leaf))
     };
-    let type_leaf = leaf_reader.next().unwrap();
-    let type_string = match type_leaf {
-        Leaf::Number(0x7c) => "u",
-        Leaf::Number(0x7d) => "i",
-        Leaf::Number(0x7e) => "r",
-        _ => panic!("unknown")
+    Ok(TypeDef::Scalar { bits: length, kind })
+}
+
+fn typdef_list(leaf_reader: LeafReader) -> Result<TypeDef, String> {
+    Ok(TypeDef::List(leaf_reader.collect::<Result<Vec<Leaf>, String>>()?))
+}
+
+/* Microsoft: 62H, variable type, length in bits */
+fn typdef_near(mut leaf_reader: LeafReader) -> Result<TypeDef, String> {
+    let variable = match leaf_reader.next_number("near variable type")? {
+        0x77 => VariableType::Array,
+        0x79 => VariableType::Structure,
+        0x7b => VariableType::Scalar,
+        n => VariableType::Other(n)
     };
-    format!("{}{}", type_string, length)
+    let bits = leaf_reader.next_number("near length")?;
+    Ok(TypeDef::Near { variable, bits })
 }
 
-fn typdef_list(leaf_reader: LeafReader) -> String {
-    let mut result: String = "list (".to_owned();
-    let mut first = true;
-    for leaf in leaf_reader {
-        if !first {
-            result.push_str(", ");
-        }
-        first = false;
-        let item_string = match leaf {
-            Leaf::Number(num) => format!("number:{}", num),
-            Leaf::String(str) => format!("'{}'", str),
-            Leaf::Index(index) => format!("index:{}", index),
-            Leaf::Null => format!("null")
-        };
-        result.push_str(&item_string);
+/* Microsoft: 61H, 77H (array), number of elements, element type index */
+fn typdef_far(mut leaf_reader: LeafReader) -> Result<TypeDef, String> {
+    match leaf_reader.next_number("far variable type")? {
+        0x77 => {},
+        n => return Err(format!("far variable type: expected array, found {:02X}H", n))
     }
-    result.push_str(")");
-    return result;
+    let elements = leaf_reader.next_number("far number of elements")?;
+    let element = leaf_reader.next_index("far element type")?;
+    Ok(TypeDef::Far { elements, element })
 }
 
-fn typdef_by_number(leaf_reader: LeafReader, n: u16) -> String {
+fn typdef_by_number(leaf_reader: LeafReader, n: u32) -> Result<TypeDef, String> {
     match n {
+        0x61 => typdef_far(leaf_reader),
+        0x62 => typdef_near(leaf_reader),
         0x71 => typdef_label(leaf_reader),
         0x74 => typdef_procedure(leaf_reader),
         0x75 => typdef_parameter(leaf_reader),
@@ -232,73 +412,77 @@ fn typdef_by_number(leaf_reader: LeafReader, n: u16) -> String {
         0x79 => typdef_struct(leaf_reader),
         0x7b => typdef_scalar(leaf_reader),
         0x7f => typdef_list(leaf_reader),
-        _ => format!("???")
+        _ => Ok(TypeDef::Unknown(n))
     }
 }
 
-pub fn typdef_to_string(vec: &[u8]) -> String {
+/* decodes the leaves of a TYPDEF, without the name */
+pub fn read_typdef(vec: &[u8]) -> Result<TypeDef, String> {
     let mut leaf_reader = make_leaf_reader(vec);
-    let leaf = leaf_reader.next().unwrap();
-    match leaf {
+    match leaf_reader.next_leaf("type")? {
         Leaf::Number(n) => typdef_by_number(leaf_reader, n),
-        Leaf::String(s) => format!("string: {}", s),
-        Leaf::Index(i) => format!("index: {}", i),
-        Leaf::Null => format!("null")
+        Leaf::String(s) => Ok(TypeDef::String(s)),
+        Leaf::Index(i) => Ok(TypeDef::Index(i)),
+        Leaf::Null => Ok(TypeDef::Null)
     }
 }
 
+pub fn typdef_to_string(vec: &[u8]) -> Result<String, String> {
+    read_typdef(vec).map(|t| t.to_string())
+}
+
 #[test]
 fn null() {
-    assert_eq!(typdef_to_string(&[0x00, 0x80]), "null");
+    assert_eq!(typdef_to_string(&[0x00, 0x80]).unwrap(), "null");
 }
 
 #[test]
 fn label() {
-    assert_eq!(typdef_to_string(&[0x00, 0x71, 0x80, 0x73]), "label (short)");
+    assert_eq!(typdef_to_string(&[0x00, 0x71, 0x80, 0x73]).unwrap(), "label (short)");
 }
 
 #[test]
 fn procedure() {
-    assert_eq!(typdef_to_string(&[0x00, 0x74, 0x80, 0x80, 0x73, 0x03, 0x83, 0x13]),
+    assert_eq!(typdef_to_string(&[0x00, 0x74, 0x80, 0x80, 0x73, 0x03, 0x83, 0x13]).unwrap(),
             "procedure (3, L19) short");
-    assert_eq!(typdef_to_string(&[0x00, 0x74, 0x80, 0x83, 0x10, 0x73, 0x01, 0x83, 0x22]),
+    assert_eq!(typdef_to_string(&[0x00, 0x74, 0x80, 0x83, 0x10, 0x73, 0x01, 0x83, 0x22]).unwrap(),
             "procedure (1, L34) T16 short");
-    assert_eq!(typdef_to_string(&[0x00, 0x74, 0x80, 0x80, 0x73, 0x00]),
+    assert_eq!(typdef_to_string(&[0x00, 0x74, 0x80, 0x80, 0x73, 0x00]).unwrap(),
             "procedure (0) short");
 }
 
 #[test]
 fn parameter() {
-    assert_eq!(typdef_to_string(&[0x00, 0x75, 0x83, 0x04]), "parameter T4");
+    assert_eq!(typdef_to_string(&[0x00, 0x75, 0x83, 0x04]).unwrap(), "parameter T4");
 }
 
 #[test]
 fn array() {
-    assert_eq!(typdef_to_string(&[0x00, 0x77, 0x60, 0x83, 0x27]),
+    assert_eq!(typdef_to_string(&[0x00, 0x77, 0x60, 0x83, 0x27]).unwrap(),
             "array (96-bit) T39");
-    assert_eq!(typdef_to_string(&[0x00, 0x77, 0x81, 0xc0, 0x12, 0x83, 0x02]),
+    assert_eq!(typdef_to_string(&[0x00, 0x77, 0x81, 0xc0, 0x12, 0x83, 0x02]).unwrap(),
             "array (4800-bit) T2");
-    assert_eq!(typdef_to_string(&[0x00, 0x77, 0x30, 0x83, 0x02]),
+    assert_eq!(typdef_to_string(&[0x00, 0x77, 0x30, 0x83, 0x02]).unwrap(),
             "array (48-bit) T2");
-    assert_eq!(typdef_to_string(&[0x00, 0x77, 0x00, 0x83, 0x02]),
+    assert_eq!(typdef_to_string(&[0x00, 0x77, 0x00, 0x83, 0x02]).unwrap(),
             "array (0-bit) T2");
 }
 
 #[test]
 fn structure() {
-    assert_eq!(typdef_to_string(&[0x00, 0x79, 0x40, 0x02, 0x83, 0x19, 0x83, 0x1a]),
+    assert_eq!(typdef_to_string(&[0x00, 0x79, 0x40, 0x02, 0x83, 0x19, 0x83, 0x1a]).unwrap(),
             "structure (64-bit, 2) Types:L25 Names:L26");
     assert_eq!(typdef_to_string(&[0x00, 0x79, 0x81, 0xb8, 0x01, 0x01,
-            0x83, 0x23, 0x83, 0x24]),
+            0x83, 0x23, 0x83, 0x24]).unwrap(),
             "structure (440-bit, 1) Types:L35 Names:L36");
 }
 
 #[test]
 fn scalar() {
-    assert_eq!(typdef_to_string(&[0x00, 0x7b, 0x10, 0x7c]), "u16");
-    assert_eq!(typdef_to_string(&[0x00, 0x7b, 0x08, 0x7c]), "u8");
-    assert_eq!(typdef_to_string(&[0x00, 0x7b, 0x10, 0x7d]), "i16");
-    assert_eq!(typdef_to_string(&[0x00, 0x7b, 0x20, 0x7e]), "r32");
+    assert_eq!(typdef_to_string(&[0x00, 0x7b, 0x10, 0x7c]).unwrap(), "u16");
+    assert_eq!(typdef_to_string(&[0x00, 0x7b, 0x08, 0x7c]).unwrap(), "u8");
+    assert_eq!(typdef_to_string(&[0x00, 0x7b, 0x10, 0x7d]).unwrap(), "i16");
+    assert_eq!(typdef_to_string(&[0x00, 0x7b, 0x20, 0x7e]).unwrap(), "r32");
 }
 
 #[test]
@@ -307,10 +491,68 @@ fn list() {
             0x82, 0x08, 0x4c, 0x41, 0x53, 0x54, 0x4e, 0x41, 0x4d, 0x45,
             0x82, 0x09, 0x46, 0x49, 0x52, 0x53, 0x54, 0x4e, 0x41, 0x4d, 0x45,
             0x82, 0x02, 0x4d, 0x49,
-            0x82, 0x06, 0x41, 0x4d, 0x4f, 0x55, 0x4e, 0x54]),
+            0x82, 0x06, 0x41, 0x4d, 0x4f, 0x55, 0x4e, 0x54]).unwrap(),
             "list ('LASTNAME', 'FIRSTNAME', 'MI', 'AMOUNT')");
     assert_eq!(typdef_to_string(&[0x00, 0x7f,
-            0x83, 0x05, 0x83, 0x06, 0x83, 0x06, 0x83, 0x05, 0x83, 0x06]),
+            0x83, 0x05, 0x83, 0x06, 0x83, 0x06, 0x83, 0x05, 0x83, 0x06]).unwrap(),
             "list (index:5, index:6, index:6, index:5, index:6)");
-    assert_eq!(typdef_to_string(&[0x00, 0x7f, 0x83, 0x22]), "list (index:34)");
+    assert_eq!(typdef_to_string(&[0x00, 0x7f, 0x83, 0x22]).unwrap(), "list (index:34)");
+}
+
+#[test]
+fn large_numbers() {
+    assert_eq!(typdef_to_string(&[0x00, 0x77, 0x84, 0xa0, 0x86, 0x01, 0x83, 0x02]).unwrap(),
+            "array (100000-bit) T2");
+    assert_eq!(typdef_to_string(&[0x00, 0x79, 0x88, 0x00, 0x00, 0x00, 0x01, 0x02,
+            0x83, 0x19, 0x83, 0x1a]).unwrap(),
+            "structure (16777216-bit, 2) Types:L25 Names:L26");
+    assert!(typdef_to_string(&[0x00, 0x77, 0x84, 0xa0, 0x86]).is_err());
+}
+
+#[test]
+fn write_numbers() {
+    assert_eq!(number_leaf(0x60), vec![0x60]);
+    assert_eq!(number_leaf(4800), vec![0x81, 0xc0, 0x12]);
+    assert_eq!(number_leaf(100000), vec![0x84, 0xa0, 0x86, 0x01]);
+    assert_eq!(number_leaf(0x1000000), vec![0x88, 0x00, 0x00, 0x00, 0x01]);
+    for &value in &[0, 0x7f, 0x80, 0xffff, 0x10000, 0xffffff, 0xffffffff] {
+        let mut leaves = vec![0x00, 0x77];
+        leaves.extend(number_leaf(value));
+        leaves.extend(&[0x83, 0x01]);
+        assert_eq!(typdef_to_string(&leaves).unwrap(),
+                format!("array ({}-bit) T1", value));
+    }
+}
+
+#[test]
+fn near() {
+    assert_eq!(typdef_to_string(&[0x00, 0x62, 0x7b, 0x10]).unwrap(),
+            "near scalar (16-bit)");
+    assert_eq!(typdef_to_string(&[0x00, 0x62, 0x77, 0x81, 0x00, 0x01]).unwrap(),
+            "near array (256-bit)");
+    assert_eq!(typdef_to_string(&[0x00, 0x62, 0x79, 0x84, 0x00, 0x00, 0x02]).unwrap(),
+            "near structure (131072-bit)");
+    assert_eq!(typdef_to_string(&[0x00, 0x62, 0x77, 0x88, 0x00, 0x00, 0x00, 0x01]).unwrap(),
+            "near array (16777216-bit)");
+}
+
+#[test]
+fn far() {
+    assert_eq!(typdef_to_string(&[0x00, 0x61, 0x77, 0x81, 0xe8, 0x03, 0x83, 0x01]).unwrap(),
+            "far array (1000) T1");
+    assert!(typdef_to_string(&[0x00, 0x61, 0x7b, 0x10]).is_err());
+}
+
+#[test]
+fn undecodable() {
+    assert_eq!(typdef_to_string(&[0x00, 0x77, 0x81, 0xc0]),
+            Err("leaf at offset 2 is truncated".to_string()));
+    assert_eq!(typdef_to_string(&[0x00, 0x77, 0x60, 0x7c]),
+            Err("array element type: expected an index, found number:124".to_string()));
+    assert_eq!(typdef_to_string(&[0x00, 0x7f, 0x82, 0x05, 0x41]),
+            Err("string leaf at offset 2 is truncated".to_string()));
+    assert_eq!(typdef_to_string(&[0x00, 0x75, 0x90, 0x01]),
+            Err("unknown leaf 90H at offset 2".to_string()));
+    assert_eq!(typdef_to_string(&[0x00, 0x7b, 0x10]),
+            Err("missing scalar type leaf".to_string()));
 }
@@ -0,0 +1,290 @@
+use std::fmt;
+
+use ::typdef::*;
+
+/* limits when resolving: TYPDEFs can refer to each other, and to the same
+ * type from many list entries */
+const MAX_DEPTH: usize = 32;
+const MAX_NODES: usize = 4096;
+
+/* a TYPDEF with its list and type references followed */
+#[derive(Clone, Debug)]
+pub enum Type {
+    Void,
+    Label(Distance),
+    Procedure {
+        ret: Box<Type>,
+        distance: Distance,
+        params: Vec<Type>
+    },
+    Array {
+        bits: u32,
+        element: Box<Type>
+    },
+    Structure {
+        bits: u32,
+        members: Vec<(String, Type)>
+    },
+    Scalar {
+        bits: u32,
+        kind: ScalarKind
+    },
+    Near {
+        variable: VariableType,
+        bits: u32
+    },
+    Far {
+        elements: u32,
+        element: Box<Type>
+    },
+    Unresolved(usize)
+}
+
+impl Type {
+    /* size in bits, when the type has one and it fits in 32 bits */
+    pub fn bits(&self) -> Option<u32> {
+        match *self {
+            Type::Array { bits, .. } => Some(bits),
+            Type::Structure { bits, .. } => Some(bits),
+            Type::Scalar { bits, .. } => Some(bits),
+            Type::Near { bits, .. } => Some(bits),
+            Type::Far { elements, ref element } =>
+                element.bits().and_then(|bits| bits.checked_mul(elements)),
+            _ => None
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Type::Void => write!(f, "void"),
+            Type::Label(distance) => write!(f, "{} label", distance),
+            Type::Procedure { ref ret, distance, ref params } => {
+                let params: Vec<String> =
+                        params.iter().map(|p| p.to_string()).collect();
+                write!(f, "{} procedure({})", distance, params.join(", "))?;
+                match **ret {
+                    Type::Void => Ok(()),
+                    ref ret => write!(f, ": {}", ret)
+                }
+            },
+            Type::Array { bits, ref element } => {
+                match element.bits() {
+                    Some(element_bits) if element_bits > 0 =>
+                        write!(f, "{}[{}]", element, bits / element_bits),
+                    _ => write!(f, "{}[{}-bit]", element, bits)
+                }
+            },
+            Type::Structure { ref members, .. } => {
+                let members: Vec<String> = members.iter()
+                        .map(|(name, typ)| format!("{}: {}", name, typ))
+                        .collect();
+                write!(f, "struct {{ {} }}", members.join("; "))
+            },
+            Type::Scalar { bits, kind } => write!(f, "{}{}", kind, bits),
+            Type::Near { variable, bits } =>
+                write!(f, "near {}({}-bit)", variable, bits),
+            Type::Far { elements, ref element } => match **element {
+                /* the element of a far array is usually a near variable */
+                Type::Near { variable, bits } =>
+                    write!(f, "far {}({}-bit)[{}]", variable, bits, elements),
+                ref element => write!(f, "far {}[{}]", element, elements)
+            },
+            Type::Unresolved(index) => write!(f, "T{}", index)
+        }
+    }
+}
+
+/* all TYPDEFs of a module in order, type indices start at 1 */
+pub struct TypeTable {
+    pub types: Vec<(String, Result<TypeDef, String>)>
+}
+
+impl TypeTable {
+
+    pub fn new() -> TypeTable {
+        TypeTable { types: Vec::new() }
+    }
+
+    /* adds the data of a TYPDEF record: name followed by the leaves */
+    pub fn add(&mut self, vec: &[u8]) {
+        let name_len = vec.first().map_or(0, |&b| b as usize);
+        if name_len + 1 > vec.len() {
+            self.types.push((String::new(), Err("name is truncated".to_string())));
+            return;
+        }
+        let name = String::from_utf8_lossy(&vec[1..name_len+1]).to_string();
+        self.types.push((name, read_typdef(&vec[name_len+1..])));
+    }
+
+    pub fn get(&self, index: usize) -> Option<&TypeDef> {
+        if index == 0 || index > self.types.len() {
+            None
+        } else {
+            self.types[index - 1].1.as_ref().ok()
+        }
+    }
+
+    /* the leaves of a list type */
+    fn list(&self, index: usize) -> &[Leaf] {
+        match self.get(index) {
+            Some(TypeDef::List(leaves)) => leaves,
+            _ => &[]
+        }
+    }
+
+    /* the type tree of a type index, 0 is no type */
+    pub fn resolve(&self, index: usize) -> Type {
+        self.resolve_path(index, &mut Vec::new(), &mut 0)
+    }
+
+    /* a type met again on the path from the root, or past the limits, is
+     * left unresolved */
+    fn resolve_path(&self, index: usize, path: &mut Vec<usize>, nodes: &mut usize) -> Type {
+        if index == 0 {
+            return Type::Void;
+        }
+        if path.contains(&index) || path.len() > MAX_DEPTH || *nodes > MAX_NODES {
+            return Type::Unresolved(index);
+        }
+        *nodes += 1;
+        path.push(index);
+        let result = self.resolve_type(index, path, nodes);
+        path.pop();
+        result
+    }
+
+    fn resolve_type(&self, index: usize, path: &mut Vec<usize>, nodes: &mut usize) -> Type {
+        match self.get(index) {
+            Some(&TypeDef::Label(distance)) => Type::Label(distance),
+            Some(&TypeDef::Procedure { ret, distance, list, .. }) => {
+                let ret = ret.map_or(Type::Void,
+                        |r| self.resolve_path(r as usize, path, nodes));
+                let params = match list {
+                    Some(list) => self.list(list as usize).iter()
+                        .map(|leaf| match *leaf {
+                            Leaf::Index(i) => self.resolve_path(i as usize, path, nodes),
+                            _ => Type::Unresolved(0)
+                        })
+                        .collect(),
+                    None => Vec::new()
+                };
+                Type::Procedure { ret: Box::new(ret), distance, params }
+            },
+            Some(&TypeDef::Parameter(typ)) => self.resolve_path(typ as usize, path, nodes),
+            Some(&TypeDef::Array { bits, element }) => Type::Array {
+                bits,
+                element: Box::new(self.resolve_path(element as usize, path, nodes))
+            },
+            Some(&TypeDef::Structure { bits, types, names, .. }) => {
+                let types = self.list(types as usize);
+                let names = self.list(names as usize);
+                let members = types.iter().zip(names.iter())
+                    .map(|(typ, name)| {
+                        let name = match *name {
+                            Leaf::String(ref s) => s.clone(),
+                            ref leaf => leaf.to_string()
+                        };
+                        let typ = match *typ {
+                            Leaf::Index(i) => self.resolve_path(i as usize, path, nodes),
+                            _ => Type::Unresolved(0)
+                        };
+                        (name, typ)
+                    })
+                    .collect();
+                Type::Structure { bits, members }
+            },
+            Some(&TypeDef::Scalar { bits, kind }) => Type::Scalar { bits, kind },
+            Some(&TypeDef::Near { variable, bits }) => Type::Near { variable, bits },
+            Some(&TypeDef::Far { elements, element }) => Type::Far {
+                elements,
+                element: Box::new(self.resolve_path(element as usize, path, nodes))
+            },
+            _ => Type::Unresolved(index)
+        }
+    }
+
+    /* the full declaration of a type index */
+    pub fn declaration(&self, index: usize) -> String {
+        self.resolve(index).to_string()
+    }
+
+}
+
+impl Default for TypeTable {
+    fn default() -> TypeTable {
+        TypeTable::new()
+    }
+}
+
+#[cfg(test)]
+fn test_table() -> TypeTable {
+    let mut table = TypeTable::new();
+    /* T1 */ table.add(&[0x00, 0x00, 0x7b, 0x08, 0x7c]);
+    /* T2 */ table.add(&[0x00, 0x00, 0x7b, 0x10, 0x7d]);
+    /* T3 */ table.add(&[0x00, 0x00, 0x77, 0x60, 0x83, 0x01]);
+    /* T4 */ table.add(&[0x00, 0x00, 0x7f, 0x83, 0x03, 0x83, 0x02]);
+    /* T5 */ table.add(&[0x00, 0x00, 0x7f,
+            0x82, 0x08, 0x4c, 0x41, 0x53, 0x54, 0x4e, 0x41, 0x4d, 0x45,
+            0x82, 0x06, 0x41, 0x4d, 0x4f, 0x55, 0x4e, 0x54]);
+    /* T6 */ table.add(&[0x00, 0x00, 0x79, 0x70, 0x02, 0x83, 0x04, 0x83, 0x05]);
+    /* T7 */ table.add(&[0x00, 0x00, 0x75, 0x83, 0x02]);
+    /* T8 */ table.add(&[0x00, 0x00, 0x7f, 0x83, 0x07, 0x83, 0x07]);
+    /* T9 */ table.add(&[0x00, 0x00, 0x74, 0x80, 0x83, 0x01, 0x72, 0x02, 0x83, 0x08]);
+    /* T10 */ table.add(&[0x00, 0x00, 0x71, 0x80, 0x73]);
+    /* T11 */ table.add(&[0x00, 0x00, 0x77, 0x60]);
+    /* T12 */ table.add(&[0x00, 0x00, 0x62, 0x7b, 0x10]);
+    /* T13 */ table.add(&[0x00, 0x00, 0x61, 0x77, 0x81, 0xe8, 0x03, 0x83, 0x0c]);
+    table
+}
+
+#[test]
+fn resolve_structure() {
+    let table = test_table();
+    assert_eq!(table.declaration(3), "u8[12]");
+    assert_eq!(table.declaration(6), "struct { LASTNAME: u8[12]; AMOUNT: i16 }");
+}
+
+#[test]
+fn resolve_procedure() {
+    let table = test_table();
+    assert_eq!(table.declaration(9), "long procedure(i16, i16): u8");
+    assert_eq!(table.declaration(10), "short label");
+}
+
+#[test]
+fn resolve_communal() {
+    let table = test_table();
+    assert_eq!(table.declaration(12), "near scalar(16-bit)");
+    assert_eq!(table.declaration(13), "far scalar(16-bit)[1000]");
+    assert_eq!(table.resolve(13).bits(), Some(16000));
+    let huge = Type::Far { elements: 0x10000000, element: Box::new(table.resolve(12)) };
+    assert_eq!(huge.bits(), None);
+}
+
+#[test]
+fn resolve_bad_index() {
+    let table = test_table();
+    assert_eq!(table.declaration(0), "void");
+    assert_eq!(table.declaration(42), "T42");
+    assert_eq!(table.declaration(11), "T11");
+}
+
+#[test]
+fn resolve_self_reference() {
+    let mut table = TypeTable::new();
+    /* T1 */ table.add(&[0x00, 0x00, 0x74, 0x80, 0x83, 0x03, 0x72, 0x02, 0x83, 0x02]);
+    /* T2 */ table.add(&[0x00, 0x00, 0x7f, 0x83, 0x01, 0x83, 0x01]);
+    /* T3 */ table.add(&[0x00, 0x00, 0x7b, 0x08, 0x7c]);
+    assert_eq!(table.declaration(1), "long procedure(T1, T1): u8");
+
+    /* procedures whose parameters are the next procedure twice */
+    let mut table = TypeTable::new();
+    for i in 0..40 {
+        let list = 2 * i + 2;
+        table.add(&[0x00, 0x00, 0x74, 0x80, 0x80, 0x72, 0x02, 0x83, list as u8]);
+        table.add(&[0x00, 0x00, 0x7f, 0x83, list as u8 + 1, 0x83, list as u8 + 1]);
+    }
+    assert!(table.declaration(1).len() < 100000);
+}